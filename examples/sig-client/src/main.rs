@@ -63,6 +63,7 @@ fn run() -> Result {
         entries.shuffle(&mut rand::rng());
         entries.truncate(count);
         entries.sort_unstable();
+        solana_native_sigverify::sort_for_dedup(&mut entries);
     }
 
 
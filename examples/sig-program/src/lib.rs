@@ -16,18 +16,21 @@ solana_program::entrypoint!(process_instruction);
 /// account.
 const SIGVERIFY_PROGRAM_ID: Pubkey =
     solana_program::pubkey!("4VEPe5EMrGkcucbmZXYRSGBKAGt8eXfJYQtMtu2cP3he");
+const _: () =
+    solana_sigverify::assert_sigverify_program_id(&SIGVERIFY_PROGRAM_ID);
 
 fn process_instruction<'a>(
     _program_id: &'a Pubkey,
     accounts: &'a [AccountInfo],
     _instruction: &'a [u8],
 ) -> Result<(), ProgramError> {
-    let mut verifier = solana_sigverify::Ed25519Verifier::default();
+    let mut verifier =
+        solana_sigverify::Ed25519Verifier::new(SIGVERIFY_PROGRAM_ID);
     for account in accounts {
         if solana_program::sysvar::instructions::check_id(account.key) {
             verifier.set_ix_sysvar(account)?;
         } else {
-            verifier.set_sigverify_account(account, &SIGVERIFY_PROGRAM_ID)?;
+            verifier.set_trusted_sigverify_account(account)?;
         }
     }
 
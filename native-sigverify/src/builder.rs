@@ -0,0 +1,190 @@
+use alloc::vec::Vec;
+
+use solana_program::instruction::Instruction;
+use solana_program::pubkey::Pubkey;
+
+use crate::{
+    check_entries, checked_len, write_instruction_data, BuildError, Dedup,
+    Entry,
+};
+
+/// Strategy for deduplicating messages in instruction data.
+///
+/// Public keys are always deduplicated.  See [`InstructionBuilder`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum MessageDedup {
+    /// Every message is included in the instruction data.
+    None,
+
+    /// A message is reused if it’s a prefix of an earlier message.
+    Prefix,
+
+    /// A message is reused if it’s a suffix of an earlier message.
+    Suffix,
+
+    /// A message is reused if it appears anywhere in an earlier message.
+    ///
+    /// This is what [`new_instruction_data`](crate::new_instruction_data)
+    /// does.
+    #[default]
+    Substring,
+}
+
+/// A builder for native signature verification program instructions.
+///
+/// Collects entries one at a time and produces instruction data or an
+/// instruction.  Compared to [`new_instruction_data`], it allows controlling
+/// how messages are deduplicated (see [`MessageDedup`]) and can sort the
+/// entries by message length to maximise deduplication potential.
+///
+/// ```
+/// # use solana_native_sigverify::*;
+/// let mut builder = InstructionBuilder::new();
+/// builder
+///     .push(&[1; 64], &[2; 32], b"foo")
+///     .push(&[3; 64], &[2; 32], b"foobar")
+///     .sort_by_message_len(true);
+/// let len = builder.data_len().unwrap();
+/// let instruction = builder.build(ED25519_PROGRAM_ID).unwrap();
+/// assert_eq!(len, instruction.data.len());
+/// ```
+///
+/// [`new_instruction_data`]: crate::new_instruction_data
+#[derive(Clone, Debug, Default)]
+pub struct InstructionBuilder<'a> {
+    entries: Vec<Entry<'a>>,
+    sort: bool,
+    dedup: MessageDedup,
+}
+
+impl<'a> InstructionBuilder<'a> {
+    /// Creates a new empty builder.
+    ///
+    /// By default, entries aren’t sorted and messages are deduplicated with
+    /// [`MessageDedup::Substring`] strategy.
+    pub fn new() -> Self { Self::default() }
+
+    /// Adds a signature to verify.
+    pub fn push(
+        &mut self,
+        signature: &'a [u8; 64],
+        pubkey: &'a [u8; 32],
+        message: &'a [u8],
+    ) -> &mut Self {
+        self.entries.push(Entry { signature, pubkey, message });
+        self
+    }
+
+    /// Sets whether entries should be sorted by message length.
+    ///
+    /// If enabled, entries are ordered starting from the longest message
+    /// (order of entries with messages of equal length is preserved).  This
+    /// way longer messages are written first and shorter ones have a chance to
+    /// reuse them.  Note that order of signatures in the instruction differs
+    /// from order in which they were added.
+    pub fn sort_by_message_len(&mut self, sort: bool) -> &mut Self {
+        self.sort = sort;
+        self
+    }
+
+    /// Sets message deduplication strategy.
+    pub fn message_dedup(&mut self, dedup: MessageDedup) -> &mut Self {
+        self.dedup = dedup;
+        self
+    }
+
+    /// Returns entries added so far in order they were added.
+    pub fn entries(&self) -> &[Entry<'a>] { &self.entries }
+
+    /// Returns length of the instruction data the builder would produce.
+    ///
+    /// Returns the same errors as [`Self::build_data`].
+    pub fn data_len(&self) -> Result<usize, BuildError> {
+        let entries = self.sorted_entries();
+        check_entries(&entries)?;
+        checked_len(Dedup::with_strategy(&entries, self.dedup).len())
+    }
+
+    /// Builds instruction data for a call of a native signature verification
+    /// program.
+    ///
+    /// Returns an error if there are more than 255 entries, message length of
+    /// any entry is longer than 65535 bytes or the resulting data wouldn’t be
+    /// addressable with 16-bit offsets.
+    pub fn build_data(&self) -> Result<Vec<u8>, BuildError> {
+        let entries = self.sorted_entries();
+        check_entries(&entries)?;
+        let dedup = Dedup::with_strategy(&entries, self.dedup);
+        let capacity = checked_len(dedup.len())?;
+        let mut data = Vec::with_capacity(capacity);
+        let len = write_instruction_data(data.spare_capacity_mut(), &dedup);
+        debug_assert_eq!(capacity, len);
+        // SAFETY: Per interface of write_instruction_data, all data up to len
+        // bytes have been initialised.
+        unsafe { data.set_len(len) };
+        Ok(data)
+    }
+
+    /// Builds an instruction calling given native signature verification
+    /// program.
+    ///
+    /// See [`Self::build_data`] for possible errors.
+    pub fn build(&self, program_id: Pubkey) -> Result<Instruction, BuildError> {
+        let data = self.build_data()?;
+        Ok(Instruction { program_id, accounts: Vec::new(), data })
+    }
+
+    fn sorted_entries(&self) -> Vec<Entry<'a>> {
+        let mut entries = self.entries.clone();
+        if self.sort {
+            entries
+                .sort_by_key(|entry| core::cmp::Reverse(entry.message.len()));
+        }
+        entries
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{new_instruction_data, parse_data, ED25519_PROGRAM_ID};
+
+    #[test]
+    fn test_builder() {
+        let mut builder = InstructionBuilder::new();
+        builder
+            .push(&[1; 64], &[2; 32], b"oo")
+            .push(&[3; 64], &[4; 32], b"fo")
+            .push(&[5; 64], &[2; 32], b"foo");
+        let entries = builder.entries().to_vec();
+
+        // No sorting, default deduplication matches new_instruction_data.
+        let data = builder.build_data().unwrap();
+        assert_eq!(new_instruction_data(&entries).unwrap(), data);
+        assert_eq!(Ok(data.len()), builder.data_len());
+        assert_eq!(2 + 3 * 78 + 2 * 32 + 2 + 2 + 3, data.len());
+
+        // With sorting, `foo` comes first and other messages reuse it.
+        builder.sort_by_message_len(true);
+        let data = builder.build_data().unwrap();
+        assert_eq!(Ok(data.len()), builder.data_len());
+        assert_eq!(2 + 3 * 78 + 2 * 32 + 3, data.len());
+        let parsed =
+            parse_data(&data).unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(vec![entries[2], entries[0], entries[1]], parsed);
+
+        let mut check = |dedup, want: usize| {
+            builder.message_dedup(dedup);
+            assert_eq!(Ok(2 + 3 * 78 + 2 * 32 + want), builder.data_len());
+        };
+        check(MessageDedup::None, 3 + 2 + 2);
+        check(MessageDedup::Prefix, 3 + 2);
+        check(MessageDedup::Suffix, 3 + 2);
+        check(MessageDedup::Substring, 3);
+
+        let instruction = builder.build(ED25519_PROGRAM_ID).unwrap();
+        assert_eq!(ED25519_PROGRAM_ID, instruction.program_id);
+        assert_eq!(data, instruction.data);
+    }
+}
@@ -27,11 +27,38 @@
 //!
 //! This crate offers functions for creating instruction calling the native
 //! signature verification programs as well as parsing their instruction data.
+//!
+//! The crate is `no_std` though it requires the `alloc` crate.
+
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+use alloc::vec;
+use alloc::vec::Vec;
 
 use solana_program::instruction::Instruction;
 use solana_program::pubkey::Pubkey;
 
+mod builder;
+mod secp256k1;
+mod secp256r1;
+#[cfg(feature = "serde")]
+mod serde_impl;
 mod stdx;
+#[cfg(feature = "wasm")]
+mod wasm;
+
+pub use builder::{InstructionBuilder, MessageDedup};
+pub use secp256k1::{
+    new_secp256k1_instruction, new_secp256k1_instruction_data,
+    parse_secp256k1_data, Secp256k1Entry, Secp256k1Iter, SecpSignatureOffsets,
+};
+pub use secp256r1::{
+    new_secp256r1_instruction, new_secp256r1_instruction_data,
+    parse_secp256r1_data, Secp256r1Entry, Secp256r1Iter,
+    MAX_SECP256R1_SIGNATURES,
+};
 
 
 /// Offsets used in instruction data of native signature verification programs.
@@ -44,7 +71,9 @@ mod stdx;
 /// All integers are stored as little-endian.
 // Copied from but we’re using
 // https://github.com/solana-labs/solana/blob/master/sdk/src/ed25519_instruction.rs
-#[derive(Copy, Clone, bytemuck::Zeroable, bytemuck::Pod)]
+#[derive(
+    Copy, Clone, Debug, PartialEq, Eq, bytemuck::Zeroable, bytemuck::Pod,
+)]
 #[repr(C)]
 pub struct SignatureOffsets {
     pub signature_offset: u16, // offset to ed25519 signature of 64 bytes
@@ -58,8 +87,71 @@ pub struct SignatureOffsets {
 
 const OFF_SIZE: usize = core::mem::size_of::<SignatureOffsets>();
 
+impl SignatureOffsets {
+    /// Position of the `signature_offset` field in the encoded offsets.
+    pub const SIGNATURE_OFFSET_RANGE: core::ops::Range<usize> = 0..2;
+    /// Position of the `signature_instruction_index` field in the encoded
+    /// offsets.
+    pub const SIGNATURE_INSTRUCTION_INDEX_RANGE: core::ops::Range<usize> = 2..4;
+    /// Position of the `pubkey_offset` field in the encoded offsets.
+    pub const PUBKEY_OFFSET_RANGE: core::ops::Range<usize> = 4..6;
+    /// Position of the `pubkey_instruction_index` field in the encoded
+    /// offsets.
+    pub const PUBKEY_INSTRUCTION_INDEX_RANGE: core::ops::Range<usize> = 6..8;
+    /// Position of the `message_offset` field in the encoded offsets.
+    pub const MESSAGE_OFFSET_RANGE: core::ops::Range<usize> = 8..10;
+    /// Position of the `message_size` field in the encoded offsets.
+    pub const MESSAGE_SIZE_RANGE: core::ops::Range<usize> = 10..12;
+    /// Position of the `message_instruction_index` field in the encoded
+    /// offsets.
+    pub const MESSAGE_INSTRUCTION_INDEX_RANGE: core::ops::Range<usize> = 12..14;
+
+    /// Constructs offsets of a signature whose all parts are stored in the
+    /// instruction data itself.
+    ///
+    /// All instruction index fields are set to `u16::MAX` which the native
+    /// programs interpret as the current instruction.  This is the layout used
+    /// by instruction data created by this crate.  Use [`Self::to_le_bytes`]
+    /// to encode the result.
+    pub const fn inline(
+        signature_offset: u16,
+        pubkey_offset: u16,
+        message_offset: u16,
+        message_size: u16,
+    ) -> Self {
+        Self {
+            signature_offset,
+            signature_instruction_index: u16::MAX,
+            pubkey_offset,
+            pubkey_instruction_index: u16::MAX,
+            message_offset,
+            message_size,
+            message_instruction_index: u16::MAX,
+        }
+    }
+
+    /// Decodes signature offsets from their little-endian representation as
+    /// stored in the instruction data.
+    pub fn from_le_bytes(bytes: &[u8; OFF_SIZE]) -> Self {
+        let bytes: &[[u8; 2]; 7] = bytemuck::must_cast_ref(bytes);
+        bytemuck::must_cast(bytes.map(u16::from_le_bytes))
+    }
+
+    /// Encodes signature offsets into their little-endian representation as
+    /// stored in the instruction data.
+    pub fn to_le_bytes(&self) -> [u8; OFF_SIZE] {
+        let fields: [u16; 7] = bytemuck::must_cast(*self);
+        bytemuck::must_cast(fields.map(u16::to_le_bytes))
+    }
+}
+
 
 /// A parse signature from the Ed25519 native program.
+///
+/// The derived ordering compares signature first and is there so that entries
+/// can be sorted deterministically (e.g. for deduplication or binary search).
+/// It does nothing to help reusing messages in instruction data; to order
+/// entries for that purpose, use [`sort_for_dedup`].
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Entry<'a> {
     pub signature: &'a [u8; 64],
@@ -67,6 +159,125 @@ pub struct Entry<'a> {
     pub message: &'a [u8],
 }
 
+impl<'a> Entry<'a> {
+    /// Constructs an entry checking that the message isn’t too long.
+    ///
+    /// Returns [`EntryError::MessageTooLong`] if the message is longer than
+    /// 65535 bytes since such entry cannot be included in instruction data.
+    /// This catches the error when the entry is created rather than when
+    /// instruction data is built.  Note that an entry with a valid message may
+    /// still not fit in instruction data together with other entries (see
+    /// [`BuildError::DataTooLong`]).
+    ///
+    /// Fields of the entry are public so it can also be constructed directly
+    /// without the check.
+    pub fn new(
+        signature: &'a [u8; 64],
+        pubkey: &'a [u8; 32],
+        message: &'a [u8],
+    ) -> Result<Self, EntryError> {
+        match u16::try_from(message.len()) {
+            Ok(_) => Ok(Self { signature, pubkey, message }),
+            Err(_) => Err(EntryError::MessageTooLong(message.len())),
+        }
+    }
+
+    /// Constructs an entry whose message is a hash of the actual message.
+    ///
+    /// For large messages, including the entire message in the instruction
+    /// data is costly.  Instead, the signer may sign a 32-byte hash of the
+    /// message (see [`hash_message`]) in which case only the hash needs to be
+    /// included in the instruction.  From the point of view of the native
+    /// signature verification program, the hash simply is the signed message.
+    ///
+    /// **Security:** The hash function must be collision-resistant.  The
+    /// signature attests only to the hash so anyone able to find another
+    /// message with the same hash could claim the signature for it.  Both the
+    /// signer and the party checking the signature must also agree that the
+    /// signed bytes are a hash rather than a raw 32-byte message.  Applications
+    /// should make sure the two cannot be confused, for example by never
+    /// signing raw 32-byte messages with the same key.
+    #[inline]
+    pub fn with_message_hash(
+        signature: &'a [u8; 64],
+        pubkey: &'a [u8; 32],
+        hash: &'a [u8; 32],
+    ) -> Self {
+        Self { signature, pubkey, message: &hash[..] }
+    }
+}
+
+/// A signature with owned signature, public key and message.
+///
+/// This is an owned counterpart of [`Entry`] useful when borrowing is
+/// impractical, e.g. when entries need to be stored across await points.
+///
+/// With `borsh` feature enabled, the type implements Borsh serialisation.
+/// Signature and public key are encoded as fixed-size arrays and message as
+/// a length-prefixed vector.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
+pub struct OwnedEntry {
+    pub signature: [u8; 64],
+    pub pubkey: [u8; 32],
+    pub message: Vec<u8>,
+}
+
+impl OwnedEntry {
+    /// Returns an [`Entry`] borrowing from this object.
+    pub fn as_entry(&self) -> Entry<'_> {
+        Entry {
+            signature: &self.signature,
+            pubkey: &self.pubkey,
+            message: &self.message,
+        }
+    }
+}
+
+impl From<Entry<'_>> for OwnedEntry {
+    fn from(entry: Entry<'_>) -> Self {
+        Self {
+            signature: *entry.signature,
+            pubkey: *entry.pubkey,
+            message: entry.message.to_vec(),
+        }
+    }
+}
+
+/// Types which can be viewed as an [`Entry`].
+///
+/// This allows functions creating instructions to accept [`Entry`] as well as
+/// [`OwnedEntry`] objects.
+pub trait AsEntry {
+    /// Returns an [`Entry`] borrowing from this object.
+    fn as_entry(&self) -> Entry<'_>;
+}
+
+impl AsEntry for Entry<'_> {
+    fn as_entry(&self) -> Entry<'_> { *self }
+}
+
+impl AsEntry for OwnedEntry {
+    fn as_entry(&self) -> Entry<'_> { OwnedEntry::as_entry(self) }
+}
+
+impl<T: AsEntry + ?Sized> AsEntry for &T {
+    fn as_entry(&self) -> Entry<'_> { (**self).as_entry() }
+}
+
+/// Hashes a message for use with [`Entry::with_message_hash`].
+///
+/// Uses SHA-256 which is also the hash available to Solana programs through
+/// the `sol_sha256` syscall so on-chain code can cheaply compute the same hash
+/// of the message.
+#[inline]
+pub fn hash_message(message: &[u8]) -> [u8; 32] {
+    solana_program::hash::hash(message).to_bytes()
+}
+
 
 /// Address of the Ed25519 native program.
 pub const ED25519_PROGRAM_ID: Pubkey = solana_program::ed25519_program::ID;
@@ -82,65 +293,425 @@ pub const SECP256R1_PROGRAM_ID: Pubkey =
 /// Creates an instruction calling a native signature verification program.
 ///
 /// `program_id` specifies the address of the signature verification program and
-/// typically is one of [`ED25519_PROGRAM_ID`], [`SECP256K1_PROGRAM_ID`] or
-/// [`SECP256R1_PROGRAM_ID`].  The function can be used for other signature
-/// verification programs so long as they use the same calling convention.
+/// typically is [`ED25519_PROGRAM_ID`].  The function can be used for other
+/// signature verification programs so long as they use the same calling
+/// convention.  Secp256k1 and Secp256r1 programs use different public key
+/// formats; use [`new_secp256k1_instruction`] or [`new_secp256r1_instruction`]
+/// to call them.
 ///
 /// See [`new_instruction_data`] for possible error conditions and notes about
 /// space optimisation.
 pub fn new_instruction(
     program_id: Pubkey,
-    entries: &[Entry],
-) -> Option<Instruction> {
+    entries: &[impl AsEntry],
+) -> Result<Instruction, BuildError> {
     let data = new_instruction_data(entries)?;
-    Some(Instruction { program_id, accounts: Vec::new(), data })
+    Ok(Instruction { program_id, accounts: Vec::new(), data })
 }
 
 
 /// Creates instruction data for a call of a native signature verification
 /// program.
 ///
-/// Returns `None` if there are more than 255 entries or message length of any
-/// entry is longer than 65535 bytes.  However, observe that Solana upper limit
-/// for instruction data is about 1100 (lower in practice).  This function does
-/// not check this size limit and may return instruction data which don’t fit in
-/// a Solana transaction.
+/// Returns an error if there are more than 255 entries, message length of any
+/// entry is longer than 65535 bytes or the resulting data wouldn’t be
+/// addressable with 16-bit offsets (see [`BuildError`]).  However, observe that
+/// Solana upper limit for instruction data is about 1100 (lower in practice).
+/// This function does not check this size limit and may return instruction
+/// data which don’t fit in a Solana transaction.  Use [`fits_in_transaction`]
+/// to check that.
 ///
 /// Tries to conserve space by reusing messages and public keys if possible.  In
 /// current implementation this is done in two ways.  Firstly, if the same
 /// public key is used for multiple signatures, that public key is included in
-/// instruction data only once.  Secondly, if a later message is a prefix of an
-/// earlier one, the message isn’t included for the second time.
+/// instruction data only once.  Secondly, if a later message is a substring
+/// (e.g. a prefix or a suffix) of an earlier one, the message isn’t included
+/// for the second time.
 ///
-/// The second optimisation doesn’t work if signature for a prefix is earlier in
-/// the `entries` than the full message.  Depending on the nature of the
+/// The second optimisation doesn’t work if signature for a substring is earlier
+/// in the `entries` than the full message.  Depending on the nature of the
 /// entries, it may be useful to sort them by the message length (starting from
 /// the longest message) to maximise space optimisation potential.
-pub fn new_instruction_data(entries: &[Entry]) -> Option<Vec<u8>> {
-    u8::try_from(entries.len()).ok()?;
-
-    // Calculate the length of the instruction.  If we manage to deduplicate
-    // messages we may end up with something shorter.  This is the largest we
-    // may possibly use.
-    let mut capacity = (2 + (OFF_SIZE + 64 + 32) * entries.len()) as u16;
-    for entry in entries {
-        let len = u16::try_from(entry.message.len()).ok()?;
-        capacity = capacity.checked_add(len)?;
-    }
+/// [`sort_for_dedup`] and [`new_instruction_data_sorted`] do exactly that.
+pub fn new_instruction_data(
+    entries: &[impl AsEntry],
+) -> Result<Vec<u8>, BuildError> {
+    let entries = entries.iter().map(AsEntry::as_entry).collect::<Vec<_>>();
+    let mut data = Vec::new();
+    new_instruction_data_reuse(&mut data, &entries)?;
+    Ok(data)
+}
 
-    let mut data = Vec::with_capacity(usize::from(capacity));
-    let len = write_instruction_data(data.spare_capacity_mut(), entries);
+/// Creates instruction data for a call of a native signature verification
+/// program reusing given buffer.
+///
+/// This is like [`new_instruction_data`] except that the data is written into
+/// `buf` replacing its previous contents.  The buffer’s capacity is reused
+/// which avoids allocating a new vector when building many instructions in
+/// a loop.  The resulting bytes are identical to those returned by
+/// `new_instruction_data`.  On error, the buffer is left empty.
+pub fn new_instruction_data_reuse(
+    buf: &mut Vec<u8>,
+    entries: &[Entry],
+) -> Result<(), BuildError> {
+    buf.clear();
+    check_entries(entries)?;
+    let dedup = Dedup::new(entries);
+    let capacity = checked_len(dedup.len())?;
+    buf.reserve_exact(capacity);
+    let len = write_instruction_data(buf.spare_capacity_mut(), &dedup);
+    debug_assert_eq!(capacity, len);
     // SAFETY: Per interface of write_instruction_data, all data up to len bytes
     // have been initialised.
-    unsafe { data.set_len(len) };
+    unsafe { buf.set_len(len) };
+    Ok(())
+}
+
+/// Creates instruction data for a call of a native signature verification
+/// program with entries taken from an iterator.
+///
+/// This is like [`new_instruction_data`] except that it accepts any iterator
+/// of entries (e.g. ones mapped from another collection) rather than a slice.
+/// Since building the data requires two passes over the entries and
+/// deduplication refers back to earlier entries, the entries are collected
+/// into a temporary vector first.  To build data from owned entries, map them
+/// with [`OwnedEntry::as_entry`].
+pub fn new_instruction_data_iter<'a>(
+    entries: impl IntoIterator<Item = Entry<'a>>,
+) -> Result<Vec<u8>, BuildError> {
+    let entries = entries.into_iter().collect::<Vec<_>>();
+    let mut data = Vec::new();
+    new_instruction_data_reuse(&mut data, &entries)?;
+    Ok(data)
+}
+
+/// Creates instruction data for a call of a native signature verification
+/// program with entries sorted by message length.
+///
+/// This is like [`new_instruction_data`] except that entries are first sorted
+/// by message length starting from the longest (order of entries with messages
+/// of equal length is preserved).  This maximises potential for message
+/// deduplication since shorter messages come after messages they may be
+/// a substring of.  Note that order of signatures in the resulting data differs
+/// from order of `entries`.
+///
+/// Returns the same errors as `new_instruction_data`.  In particular,
+/// [`BuildError::MessageTooLong`] holds index within the sorted entries.
+pub fn new_instruction_data_sorted(
+    entries: &[Entry],
+) -> Result<Vec<u8>, BuildError> {
+    let mut entries = entries.to_vec();
+    sort_for_dedup(&mut entries);
+    new_instruction_data(&entries)
+}
+
+/// Sorts entries such that they can be encoded in instruction data as
+/// compactly as possible.
+///
+/// Entries are sorted by message length starting from the longest.  Sort is
+/// stable so order of entries with messages of equal length is preserved.
+/// This way messages which are substrings of other messages come after them
+/// and can be deduplicated by [`new_instruction_data`].  This is the order
+/// [`new_instruction_data_sorted`] uses.
+///
+/// Note that the derived [`Ord`] implementation of [`Entry`] doesn’t achieve
+/// that.  If deterministic order is desired as well, sort entries with
+/// `sort_unstable` first and then call this function.
+pub fn sort_for_dedup(entries: &mut [Entry]) {
+    entries.sort_by_key(|entry| core::cmp::Reverse(entry.message.len()));
+}
+
+/// Writes instruction data for a call of a native signature verification
+/// program into given buffer.
+///
+/// This is a variant of [`new_instruction_data`] which doesn’t allocate memory.
+/// Returns number of bytes written to the start of `dst`.  Returns
+/// [`BuildError::BufferTooSmall`] if `dst` is too small to hold the data; use
+/// [`instruction_data_len`] to determine the size needed.  See
+/// `new_instruction_data` for other errors.
+///
+/// Unlike `new_instruction_data`, this function never uses maps for
+/// deduplication which makes it slower for large batches.  The result is
+/// identical regardless.
+pub fn new_instruction_data_into(
+    dst: &mut [u8],
+    entries: &[Entry],
+) -> Result<usize, BuildError> {
+    check_entries(entries)?;
+    let dedup = Dedup::with_options(entries, MessageDedup::default(), false);
+    let len = checked_len(dedup.len())?;
+    let dst = dst.get_mut(..len).ok_or(BuildError::BufferTooSmall(len))?;
+    // SAFETY: write_instruction_data writes only initialised bytes.
+    let dst = unsafe { stdx::as_uninit_mut(dst) };
+    Ok(write_instruction_data(dst, &dedup))
+}
+
+/// Calculates length of instruction data created by [`new_instruction_data`]
+/// without allocating it.
+///
+/// The length accounts for message and public key deduplication performed by
+/// `new_instruction_data`.  Returns the same errors as that function.  This can
+/// be used to check whether given entries fit in a transaction before building
+/// the instruction.
+pub fn instruction_data_len(entries: &[Entry]) -> Result<usize, BuildError> {
+    check_entries(entries)?;
+    checked_len(Dedup::new(entries).len())
+}
+
+/// Reports which messages and public keys [`new_instruction_data`] reuses.
+///
+/// Returns a vector whose `i`-th element describes how `entries[i]` is encoded
+/// in the instruction data, i.e. whether its message and public key are
+/// written into the data or point at those of an earlier entry.  This mirrors
+/// decisions made when building the data and can be used to tune order of
+/// entries (see [`sort_for_dedup`]).  Returns the same errors as
+/// `new_instruction_data` except that length of the data isn’t checked.
+pub fn dedup_report(entries: &[Entry]) -> Result<Vec<DedupInfo>, BuildError> {
+    check_entries(entries)?;
+    let dedup = Dedup::new(entries);
+    Ok((0..entries.len())
+        .map(|idx| DedupInfo {
+            message: dedup.message(idx),
+            pubkey: dedup.pubkey(idx),
+        })
+        .collect())
+}
+
+/// Describes how an entry is encoded in instruction data.
+///
+/// Returned by [`dedup_report`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DedupInfo {
+    /// Index of an earlier entry whose message contains this entry’s message
+    /// and offset of the message within it.  `None` if the message is written
+    /// into the instruction data.
+    pub message: Option<(usize, u16)>,
+
+    /// Index of an earlier entry with the same public key.  `None` if the
+    /// public key is written into the instruction data.
+    pub pubkey: Option<usize>,
+}
+
+/// Maximum length of instruction data which fits in a Solana transaction.
+///
+/// Solana transactions are limited to 1232 bytes.  This is what remains of that
+/// limit in the smallest possible transaction: a legacy transaction with
+/// a single signature and a single instruction which uses no accounts.  The
+/// limit is a heuristic.  In practice the budget is lower since it depends on
+/// the rest of the transaction (other signatures, accounts and instructions).
+pub const MAX_INSTRUCTION_DATA_LEN: usize = 1232 -
+    (1 + 64) - // signatures
+    3 - // message header
+    (1 + 2 * 32) - // account keys: fee payer and the program
+    32 - // recent blockhash
+    1 - // number of instructions
+    (1 + 1 + 2); // program index, accounts and data length
+
+/// Checks whether instruction data for given entries fits in a transaction.
+///
+/// Calculates length of the instruction data (see [`instruction_data_len`])
+/// and compares it against [`MAX_INSTRUCTION_DATA_LEN`].  Note that this is
+/// a heuristic.  A `true` result doesn’t guarantee that a transaction with the
+/// instruction will fit in size limits since the actual budget depends on the
+/// rest of the transaction.  On the other hand, `false` means that the
+/// instruction definitely doesn’t fit.
+pub fn fits_in_transaction(entries: &[Entry]) -> bool {
+    instruction_data_len(entries)
+        .is_ok_and(|len| len <= MAX_INSTRUCTION_DATA_LEN)
+}
+
+/// Checks that entries can be encoded in an instruction.
+///
+/// Returns an error if there are more than 255 entries or message of any entry
+/// is longer than 65535 bytes.
+fn check_entries(entries: &[Entry]) -> Result<(), BuildError> {
+    if u8::try_from(entries.len()).is_err() {
+        return Err(BuildError::TooManyEntries(entries.len()));
+    }
+    for (index, entry) in entries.iter().enumerate() {
+        let len = entry.message.len();
+        if u16::try_from(len).is_err() {
+            return Err(BuildError::MessageTooLong { index, len });
+        }
+    }
+    Ok(())
+}
 
-    Some(data)
+/// Checks that instruction data of given length is addressable with 16-bit
+/// offsets.
+fn checked_len(len: usize) -> Result<usize, BuildError> {
+    match u16::try_from(len) {
+        Ok(_) => Ok(len),
+        Err(_) => Err(BuildError::DataTooLong(len)),
+    }
+}
+
+/// Number of entries above which [`Dedup`] uses maps.
+const DEDUP_MAP_THRESHOLD: usize = 16;
+
+/// Lookups used to deduplicate messages and public keys.
+///
+/// For small batches this simply scans earlier entries.  That’s quadratic in
+/// number of entries so for larger batches indexes of the first occurrence of
+/// each public key and message are collected into maps and duplicates are
+/// found in logarithmic time.  An earlier message may contain the message as
+/// a prefix or suffix even if it’s not its exact duplicate so entries before
+/// the first exact duplicate still need to be scanned, and messages which
+/// have no exact duplicates need the full scan.  This way both methods choose
+/// the same source and produce identical results.
+struct Dedup<'a, 'b> {
+    entries: &'b [Entry<'a>],
+    strategy: MessageDedup,
+    first: Option<FirstIndices<'a>>,
+}
+
+/// Indexes of the first entry using given public key and given message.
+struct FirstIndices<'a> {
+    pubkeys: alloc::collections::BTreeMap<&'a [u8; 32], usize>,
+    messages: alloc::collections::BTreeMap<&'a [u8], usize>,
+}
+
+impl<'a, 'b> Dedup<'a, 'b> {
+    fn new(entries: &'b [Entry<'a>]) -> Self {
+        Self::with_strategy(entries, MessageDedup::default())
+    }
+
+    fn with_strategy(entries: &'b [Entry<'a>], strategy: MessageDedup) -> Self {
+        let use_maps = entries.len() > DEDUP_MAP_THRESHOLD;
+        Self::with_options(entries, strategy, use_maps)
+    }
+
+    fn with_options(
+        entries: &'b [Entry<'a>],
+        strategy: MessageDedup,
+        use_maps: bool,
+    ) -> Self {
+        let first = use_maps.then(|| {
+            let mut pubkeys = alloc::collections::BTreeMap::new();
+            let mut messages = alloc::collections::BTreeMap::new();
+            for (idx, entry) in entries.iter().enumerate() {
+                pubkeys.entry(entry.pubkey).or_insert(idx);
+                messages.entry(entry.message).or_insert(idx);
+            }
+            FirstIndices { pubkeys, messages }
+        });
+        Self { entries, strategy, first }
+    }
+
+    /// Looks for an earlier message which message of `entries[idx]` can
+    /// reuse; see [`find_message`].
+    fn message(&self, idx: usize) -> Option<(usize, u16)> {
+        if self.strategy == MessageDedup::None {
+            return None;
+        }
+        if let Some(first) = self.first.as_ref() {
+            let message = self.entries[idx].message;
+            let pos = first.messages[message];
+            if pos < idx {
+                // The exact duplicate is a prefix match so the scan would
+                // stop at it at the latest.  Check whether any earlier
+                // message contains this one as prefix or suffix.
+                let earlier = &self.entries[..pos];
+                return find_affix(earlier, message, self.strategy)
+                    .or(Some((pos, 0)));
+            }
+        }
+        find_message(self.entries, idx, self.strategy)
+    }
+
+    /// Looks for an earlier entry with the same public key as `entries[idx]`.
+    fn pubkey(&self, idx: usize) -> Option<usize> {
+        let pubkey = self.entries[idx].pubkey;
+        match self.first.as_ref() {
+            Some(first) => Some(first.pubkeys[pubkey]).filter(|&pos| pos < idx),
+            None => {
+                self.entries[..idx].iter().position(|ent| ent.pubkey == pubkey)
+            }
+        }
+    }
+
+    /// Returns length of instruction data with all messages inlined.
+    ///
+    /// Accounts for deduplication done by [`write_instruction_data`] but
+    /// doesn’t check any limits.
+    fn len(&self) -> usize {
+        let mut len = 2 + (OFF_SIZE + 64) * self.entries.len();
+        for idx in 0..self.entries.len() {
+            if self.message(idx).is_none() {
+                len += self.entries[idx].message.len();
+            }
+            if self.pubkey(idx).is_none() {
+                len += 32;
+            }
+        }
+        len
+    }
+}
+
+/// Looks for an earlier message which message of `entries[idx]` can reuse.
+///
+/// A message can be reused if it’s a substring of an earlier message (limited
+/// to prefixes or suffixes depending on the `strategy`).  Returns index of the
+/// entry with the earlier message and offset of `entries[idx].message` within
+/// it.  Returns `None` if the message needs to be included in the instruction
+/// data.
+///
+/// Prefixes and suffixes are checked first.  Only if that fails, earlier
+/// messages are scanned for the message at arbitrary offset.  The scan is
+/// O(n·m) in lengths of the messages but it doesn’t slow down the common case
+/// of small batches of short messages noticeably.
+fn find_message(
+    entries: &[Entry],
+    idx: usize,
+    strategy: MessageDedup,
+) -> Option<(usize, u16)> {
+    let message = entries[idx].message;
+    let earlier = &entries[..idx];
+    find_affix(earlier, message, strategy).or_else(|| {
+        if strategy != MessageDedup::Substring || message.is_empty() {
+            return None;
+        }
+        earlier.iter().enumerate().find_map(|(pos, ent)| {
+            let offset = ent
+                .message
+                .windows(message.len())
+                .position(|window| window == message)?;
+            Some((pos, offset as u16))
+        })
+    })
+}
+
+/// Looks for the first of `earlier` messages which starts or ends with
+/// `message`.
+///
+/// Whether prefixes and suffixes are considered depends on the `strategy`.
+/// Returns index of the entry and offset of `message` within its message.
+fn find_affix(
+    earlier: &[Entry],
+    message: &[u8],
+    strategy: MessageDedup,
+) -> Option<(usize, u16)> {
+    let prefix =
+        matches!(strategy, MessageDedup::Prefix | MessageDedup::Substring);
+    let suffix =
+        matches!(strategy, MessageDedup::Suffix | MessageDedup::Substring);
+    earlier.iter().enumerate().find_map(|(pos, ent)| {
+        if prefix && ent.message.starts_with(message) {
+            Some((pos, 0))
+        } else if suffix && ent.message.ends_with(message) {
+            Some((pos, (ent.message.len() - message.len()) as u16))
+        } else {
+            None
+        }
+    })
 }
 
 fn write_instruction_data(
     dst: &mut [core::mem::MaybeUninit<u8>],
-    entries: &[Entry],
+    dedup: &Dedup,
 ) -> usize {
+    let entries = dedup.entries;
+
     // The structure of the instruction data is:
     //   count:   u8
     //   zero:    u8
@@ -151,6 +722,18 @@ fn write_instruction_data(
 
     let mut len = 2 + entries.len() * OFF_SIZE;
     let (head, mut dst) = dst.split_at_mut(len);
+
+    /// Reads a little-endian u16 field at given position of encoded offsets.
+    ///
+    /// SAFETY: Caller must guarantee that the field has been initialised.
+    unsafe fn read_field(
+        offsets: &[core::mem::MaybeUninit<u8>; OFF_SIZE],
+        range: core::ops::Range<usize>,
+    ) -> u16 {
+        let field = &offsets[range];
+        u16::from_le_bytes([field[0].assume_init(), field[1].assume_init()])
+    }
+
     let (entries_dst, rest) =
         stdx::as_chunks_mut::<{ OFF_SIZE }, _>(&mut head[2..]);
     assert_eq!((entries.len(), 0), (entries_dst.len(), rest.len()));
@@ -170,16 +753,15 @@ fn write_instruction_data(
         let Entry { signature, pubkey, message } = entries[idx];
 
         // Append message but deduplicate if the message has already been used
-        // or the message is prefix of a message which has already been used.
-        let pos = entries[..idx]
-            .iter()
-            .position(|ent| ent.message.starts_with(message));
-        let message_offset = if let Some(pos) = pos {
+        // or the message is prefix or suffix of a message which has already
+        // been used.
+        let message_offset = if let Some((pos, delta)) = dedup.message(idx) {
             let offsets = &entries_dst[pos];
             // SAFETY: All offsets prior to idx have been initialised.
-            u16::from_le_bytes(unsafe {
-                [offsets[8].assume_init(), offsets[9].assume_init()]
-            })
+            let offset = unsafe {
+                read_field(offsets, SignatureOffsets::MESSAGE_OFFSET_RANGE)
+            };
+            offset + delta
         } else {
             append!(message)
         };
@@ -188,31 +770,153 @@ fn write_instruction_data(
         let signature_offset = append!(signature);
 
         // Append pubkey, but deduplicate if the key has already been used.
-        let pos = entries[..idx].iter().position(|ent| ent.pubkey == pubkey);
-        let pubkey_offset = if let Some(pos) = pos {
+        let pubkey_offset = if let Some(pos) = dedup.pubkey(idx) {
             let offsets = &entries_dst[pos];
             // SAFETY: All offsets prior to idx have been initialised.
-            u16::from_le_bytes(unsafe {
-                [offsets[4].assume_init(), offsets[5].assume_init()]
-            })
+            unsafe {
+                read_field(offsets, SignatureOffsets::PUBKEY_OFFSET_RANGE)
+            }
         } else {
             append!(pubkey)
         };
 
         // Fill in the entry.
-        let offsets = SignatureOffsets {
-            signature_offset: u16::from_le(signature_offset),
+        let offsets = SignatureOffsets::inline(
+            signature_offset,
+            pubkey_offset,
+            message_offset,
+            message.len() as u16,
+        );
+        stdx::write_slice(&mut entries_dst[idx], &offsets.to_le_bytes());
+    }
+
+    len
+}
+
+
+/// Creates native signature verification program instruction which fits in
+/// given budget by moving messages into an auxiliary data instruction.
+///
+/// Native signature verification programs can read messages from data of other
+/// instructions in the transaction.  This function takes advantage of that and
+/// produces two instructions: a call to the `program_id` native program and an
+/// auxiliary data instruction calling `data_program_id` whose instruction data
+/// holds messages which didn’t fit in the native instruction.  The caller must
+/// include the auxiliary instruction in the transaction at index
+/// `data_instruction_index` (that index is what the native instruction refers
+/// to) and choose `data_program_id` such that the call succeeds regardless of
+/// its instruction data.  If all the messages fit in the native instruction,
+/// data of the auxiliary instruction is empty and the native instruction is
+/// the same as one returned by [`new_instruction`].
+///
+/// Messages are moved starting from the longest until length of the native
+/// instruction data is at most `budget` bytes.  Signatures and public keys are
+/// always inlined.  Messages and public keys are deduplicated the same way
+/// [`new_instruction_data`] does.  Returns [`BuildError::BudgetExceeded`] if
+/// the native instruction data doesn’t fit in the budget even with all the
+/// messages moved.  Returns [`BuildError::BadInstructionIndex`] if
+/// `data_instruction_index` is `u16::MAX` since native programs treat that
+/// value as a reference to the native instruction itself.
+///
+/// **Note** that signatures whose messages are in the auxiliary instruction
+/// are not recognised by [`parse_data`] (and by extension are ignored by the
+/// solana-sigverify program).  Use [`parse_instructions`] to parse them.
+pub fn new_split_instructions(
+    program_id: Pubkey,
+    data_program_id: Pubkey,
+    data_instruction_index: u16,
+    entries: &[Entry],
+    budget: usize,
+) -> Result<(Instruction, Instruction), BuildError> {
+    check_entries(entries)?;
+    if data_instruction_index == u16::MAX {
+        return Err(BuildError::BadInstructionIndex);
+    }
+
+    // For each entry, earlier message the entry reuses (see find_message).
+    let dedup = Dedup::new(entries);
+    let source =
+        (0..entries.len()).map(|idx| dedup.message(idx)).collect::<Vec<_>>();
+
+    // Calculate length of the native instruction data with all messages
+    // inlined and then move messages, starting from the longest, until the
+    // data fits the budget.
+    let mut primaries = (0..entries.len())
+        .filter(|&idx| source[idx].is_none())
+        .collect::<Vec<_>>();
+    let mut len = dedup.len();
+
+    primaries
+        .sort_by_key(|&idx| core::cmp::Reverse(entries[idx].message.len()));
+    let mut referenced = vec![false; entries.len()];
+    let mut aux_len = 0;
+    for idx in primaries {
+        if len <= budget {
+            break;
+        }
+        referenced[idx] = true;
+        len -= entries[idx].message.len();
+        aux_len += entries[idx].message.len();
+    }
+    if len > budget {
+        return Err(BuildError::BudgetExceeded(len));
+    }
+    checked_len(len)?;
+    checked_len(aux_len)?;
+
+    fn append(dst: &mut Vec<u8>, bytes: &[u8]) -> u16 {
+        let offset = dst.len() as u16;
+        dst.extend_from_slice(bytes);
+        offset
+    }
+
+    let mut data = Vec::with_capacity(len);
+    data.extend_from_slice(&[entries.len() as u8, 0]);
+    data.resize(2 + entries.len() * OFF_SIZE, 0);
+    let mut aux = Vec::with_capacity(aux_len);
+    let mut offsets = Vec::<SignatureOffsets>::with_capacity(entries.len());
+    for (idx, entry) in entries.iter().enumerate() {
+        let (message_offset, message_instruction_index) =
+            if let Some((pos, delta)) = source[idx] {
+                let offsets = &offsets[pos];
+                (
+                    offsets.message_offset + delta,
+                    offsets.message_instruction_index,
+                )
+            } else if referenced[idx] {
+                (append(&mut aux, entry.message), data_instruction_index)
+            } else {
+                (append(&mut data, entry.message), u16::MAX)
+            };
+
+        let signature_offset = append(&mut data, entry.signature);
+
+        let pubkey_offset = match dedup.pubkey(idx) {
+            Some(pos) => offsets[pos].pubkey_offset,
+            None => append(&mut data, entry.pubkey),
+        };
+
+        offsets.push(SignatureOffsets {
+            signature_offset,
             signature_instruction_index: u16::MAX,
-            pubkey_offset: u16::from_le(pubkey_offset),
+            pubkey_offset,
             pubkey_instruction_index: u16::MAX,
-            message_offset: u16::from_le(message_offset),
-            message_size: message.len() as u16,
-            message_instruction_index: u16::MAX,
-        };
-        stdx::write_slice(&mut entries_dst[idx], bytemuck::bytes_of(&offsets));
+            message_offset,
+            message_size: entry.message.len() as u16,
+            message_instruction_index,
+        });
+    }
+    for (dst, offsets) in data[2..].chunks_exact_mut(OFF_SIZE).zip(offsets) {
+        dst.copy_from_slice(&offsets.to_le_bytes());
     }
 
-    len
+    let native = Instruction { program_id, accounts: Vec::new(), data };
+    let aux = Instruction {
+        program_id: data_program_id,
+        accounts: Vec::new(),
+        data: aux,
+    };
+    Ok((native, aux))
 }
 
 
@@ -236,20 +940,126 @@ fn write_instruction_data(
 /// The iterator does *not* support fetching keys, signatures or messages from
 /// other instructions (which is something native signature verification
 /// programs support) and if that feature is used such entries will be reported
-/// as [`Error::UnsupportedFeature`] errors.
+/// as [`Error::UnsupportedFeature`] errors.  Use [`parse_data_with_resolver`]
+/// to parse such entries.
 ///
-/// Returns [`Error::BadData`] if the data is malformed.
+/// Message of each entry is exactly `message_size` bytes long as specified in
+/// the offsets.  In particular, if the message is stored as a prefix of
+/// another, longer message (see [`new_instruction_data`]), the parsed message
+/// is the prefix rather than the longer message it shares storage with.
+///
+/// The function assumes 32-byte public keys as used by the Ed25519 program.
+/// Use [`parse_secp256k1_data`] or [`parse_secp256r1_data`] to parse data of
+/// Secp256k1 or Secp256r1 program calls.
+///
+/// Returns [`Error::BadData`] if the data is malformed.  The function and the
+/// returned iterator never panic, even if given arbitrary bytes.  Offsets and
+/// sizes are converted to `usize` before any arithmetic and all reads are
+/// bounds-checked so offsets pointing past the data (or ranges which would
+/// overflow `u16`) are reported as [`Error::BadData`] errors.
 pub fn parse_data<'a>(data: &'a [u8]) -> Result<Iter<'a>, BadData> {
+    let entries = split_offsets(data)?;
+    Ok(Iter { entries: entries.iter(), data })
+}
+
+/// Returns signature offsets entries from the instruction data.
+///
+/// Checks the header of the data (see [`parse_data`]) and returns `count`
+/// 14-byte offsets entries which follow it.
+fn split_offsets(data: &[u8]) -> Result<&[[u8; OFF_SIZE]], BadData> {
     match stdx::split_at::<2, u8>(data) {
         Some(([count, 0], rest)) => {
-            stdx::as_chunks::<14, u8>(rest).0.get(..usize::from(*count))
+            stdx::as_chunks::<OFF_SIZE, u8>(rest).0.get(..usize::from(*count))
         }
         _ => None,
     }
-    .map(|entries| Iter { entries: entries.iter(), data })
     .ok_or(BadData)
 }
 
+/// Calls `f` with each signature offsets entry in the instruction data.
+///
+/// This is a lower-level alternative to [`parse_data`] which doesn’t decode
+/// signatures, public keys or messages.  Instead, it passes the raw
+/// [`SignatureOffsets`] to the callback, including entries which reference
+/// data in other instructions, and lets the caller decide how to resolve them
+/// (e.g. by looking the instructions up in the instructions sysvar).  None of
+/// the offsets are validated.
+///
+/// Stops at the first error returned by `f` and returns it.  Returns
+/// [`Error::BadData`] if the header of the data is malformed.
+pub fn for_each_offsets(
+    data: &[u8],
+    mut f: impl FnMut(SignatureOffsets) -> Result<(), Error>,
+) -> Result<(), Error> {
+    split_offsets(data)?
+        .iter()
+        .try_for_each(|entry| f(SignatureOffsets::from_le_bytes(entry)))
+}
+
+/// Returns human-readable description of the instruction data.
+///
+/// The returned object implements [`Display`](core::fmt::Display) and lists
+/// number of signatures followed by offsets and sizes of each entry (one per
+/// line) noting whether the entry is inline or references other instructions
+/// and whether it points outside of the data.  For example:
+///
+/// ```text
+/// 2 entries
+/// entry 0: sig@33 key@97 msg@30(len 3) inline
+/// entry 1: sig@0 key@97 msg@30(len 3) cross-instruction (sig ix 0, key ix 65535, msg ix 65535)
+/// ```
+///
+/// This is meant for debugging malformed instructions.  The format isn’t
+/// stable and shouldn’t be parsed.
+pub fn describe(data: &[u8]) -> Describe<'_> { Describe(data) }
+
+/// Human-readable description of native program instruction data.
+///
+/// See [`describe`].
+#[derive(Clone, Copy, Debug)]
+pub struct Describe<'a>(&'a [u8]);
+
+impl core::fmt::Display for Describe<'_> {
+    fn fmt(&self, fmtr: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let entries = match split_offsets(self.0) {
+            Ok(entries) => entries,
+            Err(BadData) => {
+                return write!(
+                    fmtr,
+                    "malformed header ({} bytes)",
+                    self.0.len()
+                )
+            }
+        };
+        write!(fmtr, "{} entries", entries.len())?;
+        for (idx, entry) in entries.iter().enumerate() {
+            let offsets = SignatureOffsets::from_le_bytes(entry);
+            write!(
+                fmtr,
+                "\nentry {idx}: sig@{} key@{} msg@{}(len {})",
+                offsets.signature_offset,
+                offsets.pubkey_offset,
+                offsets.message_offset,
+                offsets.message_size,
+            )?;
+            match decode_entry(self.0, entry) {
+                Ok(_) => fmtr.write_str(" inline")?,
+                Err(Error::BadData) => {
+                    fmtr.write_str(" inline: out of bounds")?
+                }
+                Err(Error::UnsupportedFeature) => write!(
+                    fmtr,
+                    " cross-instruction (sig ix {}, key ix {}, msg ix {})",
+                    offsets.signature_instruction_index,
+                    offsets.pubkey_instruction_index,
+                    offsets.message_instruction_index,
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
 /// Iterator over signatures present in native signature verification program
 /// instruction data.
 #[derive(Clone, Debug)]
@@ -258,6 +1068,40 @@ pub struct Iter<'a> {
     data: &'a [u8],
 }
 
+impl<'a> Iter<'a> {
+    /// Consumes the iterator and counts entries by their outcome.
+    ///
+    /// Returns a `(ok, unsupported, bad)` tuple with number of entries which
+    /// were successfully decoded, number of entries which use unsupported
+    /// features (i.e. reference other instructions; see
+    /// [`Error::UnsupportedFeature`]) and number of malformed entries (see
+    /// [`Error::BadData`]).  This avoids collecting the entries when only the
+    /// statistics are of interest.
+    pub fn counts(self) -> (usize, usize, usize) {
+        self.fold((0, 0, 0), |(ok, unsupported, bad), entry| match entry {
+            Ok(_) => (ok + 1, unsupported, bad),
+            Err(Error::UnsupportedFeature) => (ok, unsupported + 1, bad),
+            Err(Error::BadData) => (ok, unsupported, bad + 1),
+        })
+    }
+
+    /// Returns an iterator over distinct public keys of the signatures.
+    ///
+    /// Each public key is yielded once, in order of its first occurrence, even
+    /// if multiple signatures were made with it.  This is useful to present
+    /// set of signers of an instruction.  Errors are passed through as
+    /// reported by this iterator.
+    pub fn unique_pubkeys(
+        self,
+    ) -> impl Iterator<Item = Result<&'a [u8; 32], Error>> {
+        let mut seen = alloc::collections::BTreeSet::new();
+        self.filter_map(move |entry| match entry {
+            Ok(entry) => seen.insert(entry.pubkey).then_some(Ok(entry.pubkey)),
+            Err(err) => Some(Err(err)),
+        })
+    }
+}
+
 impl<'a> core::iter::Iterator for Iter<'a> {
     type Item = Result<Entry<'a>, Error>;
 
@@ -297,6 +1141,206 @@ impl core::iter::DoubleEndedIterator for Iter<'_> {
 }
 
 
+/// Creates a new iterator over signatures in given native signature
+/// verification program instruction data resolving references to other
+/// instructions.
+///
+/// This is like [`parse_data`] except that it supports entries which reference
+/// signatures, public keys or messages stored in other instructions of the
+/// transaction.  `resolver` maps an instruction index into data of that
+/// instruction and returns `None` if there’s no such instruction.  References
+/// to the instruction itself (i.e. index `u16::MAX`) are resolved to `data`
+/// without calling `resolver`.
+///
+/// On-chain, the resolver can be implemented with the instructions sysvar by
+/// loading the referenced instructions beforehand (the data needs to outlive
+/// the iterator).
+///
+/// Returns [`BadData`] if the data is malformed.  Entries which reference
+/// instructions for which `resolver` returns `None` are reported as
+/// [`Error::BadData`] errors.
+pub fn parse_data_with_resolver<'a, F>(
+    data: &'a [u8],
+    resolver: F,
+) -> Result<ResolvingIter<'a, F>, BadData>
+where
+    F: Fn(u16) -> Option<&'a [u8]>,
+{
+    let Iter { entries, data } = parse_data(data)?;
+    Ok(ResolvingIter { entries, data, resolver })
+}
+
+/// Iterator over signatures present in native signature verification program
+/// instruction data which resolves references to other instructions.
+///
+/// See [`parse_data_with_resolver`].
+#[derive(Clone)]
+pub struct ResolvingIter<'a, F> {
+    entries: core::slice::Iter<'a, [u8; 14]>,
+    data: &'a [u8],
+    resolver: F,
+}
+
+impl<'a, F: Fn(u16) -> Option<&'a [u8]>> ResolvingIter<'a, F> {
+    fn resolve(&self, entry: &[u8; 14]) -> Result<Entry<'a>, Error> {
+        resolve_entry(SignatureOffsets::from_le_bytes(entry), |index| {
+            if index == u16::MAX {
+                Some(self.data)
+            } else {
+                (self.resolver)(index)
+            }
+        })
+    }
+}
+
+impl<'a, F: Fn(u16) -> Option<&'a [u8]>> core::iter::Iterator
+    for ResolvingIter<'a, F>
+{
+    type Item = Result<Entry<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.entries.next()?;
+        Some(self.resolve(entry))
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        let entry = self.entries.nth(n)?;
+        Some(self.resolve(entry))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) { self.entries.size_hint() }
+    fn count(self) -> usize { self.entries.count() }
+}
+
+impl<'a, F: Fn(u16) -> Option<&'a [u8]>> core::iter::ExactSizeIterator
+    for ResolvingIter<'a, F>
+{
+    fn len(&self) -> usize { self.entries.len() }
+}
+
+impl<'a, F: Fn(u16) -> Option<&'a [u8]>> core::iter::DoubleEndedIterator
+    for ResolvingIter<'a, F>
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let entry = self.entries.next_back()?;
+        Some(self.resolve(entry))
+    }
+
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        let entry = self.entries.nth_back(n)?;
+        Some(self.resolve(entry))
+    }
+}
+
+
+/// Parses all signatures verified by native program calls in a transaction.
+///
+/// `instructions` is a list of all instructions in a transaction.  Every
+/// instruction calling `program_id` is parsed (see [`parse_data`]) and all
+/// signatures it verifies are returned.  Unlike `parse_data`, this function
+/// supports entries which reference data in other instructions of the
+/// transaction.  `program_id` is typically [`ED25519_PROGRAM_ID`].
+///
+/// This is intended for clients analysing executed transactions (e.g. fetched
+/// from an RPC node) which need to know all the signatures verified in them.
+///
+/// Returns [`BadData`] if any of the native program calls is malformed
+/// (including if it references a non-existent instruction).  This should never
+/// happen for transactions which have been successfully executed.
+pub fn parse_instructions<'a>(
+    program_id: &Pubkey,
+    instructions: &'a [Instruction],
+) -> Result<Vec<Entry<'a>>, BadData> {
+    let mut entries = Vec::new();
+    for instruction in instructions {
+        if instruction.program_id != *program_id {
+            continue;
+        }
+        let iter = parse_data_with_resolver(&instruction.data, |index| {
+            let ix = instructions.get(usize::from(index))?;
+            Some(ix.data.as_slice())
+        })?;
+        for entry in iter {
+            entries.push(entry?);
+        }
+    }
+    Ok(entries)
+}
+
+/// Checks whether given instruction data verifies signatures of all the
+/// expected public key and message pairs.
+///
+/// `data` is native signature verification program instruction data (see
+/// [`parse_data`]).  Returns `true` if for each pair in `expected` there’s
+/// a signature in the data made with that public key over that message.  This
+/// can be used to check that an instruction pulled from the instructions
+/// sysvar checks signatures the program expects rather than some unrelated
+/// ones.  Signature entries which reference other instructions (see
+/// [`Error::UnsupportedFeature`]) are ignored.
+///
+/// Returns [`Error::BadData`] if the data is malformed.
+pub fn contains_all(
+    data: &[u8],
+    expected: &[(&[u8; 32], &[u8])],
+) -> Result<bool, Error> {
+    let mut entries = Vec::new();
+    for entry in parse_data(data)? {
+        match entry {
+            Ok(entry) => entries.push((entry.pubkey, entry.message)),
+            Err(Error::UnsupportedFeature) => (),
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(expected.iter().all(|want| entries.contains(want)))
+}
+
+
+/// Error when creating native signature verification program instruction.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BuildError {
+    /// There are more entries than fit in a single instruction (the limit is
+    /// 255).  Holds number of entries given.
+    TooManyEntries(usize),
+
+    /// Message of the entry at given index is longer than 65535 bytes.
+    MessageTooLong { index: usize, len: usize },
+
+    /// Total length of the instruction data would exceed 65535 bytes making
+    /// parts of it unaddressable with 16-bit offsets.  Holds length of the
+    /// data.
+    DataTooLong(usize),
+
+    /// Buffer passed to [`new_instruction_data_into`] is too small.  Holds
+    /// length of the instruction data.
+    BufferTooSmall(usize),
+
+    /// Instruction data doesn’t fit in requested budget.  Holds the smallest
+    /// length of the data that could be achieved.
+    BudgetExceeded(usize),
+
+    /// Auxiliary data instruction index passed to [`new_split_instructions`]
+    /// is `u16::MAX` which native programs interpret as the current
+    /// instruction rather than an index.
+    BadInstructionIndex,
+}
+
+impl From<BuildError> for solana_program::program_error::ProgramError {
+    fn from(_: BuildError) -> Self { Self::InvalidArgument }
+}
+
+
+/// Error when constructing an [`Entry`] with [`Entry::new`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum EntryError {
+    /// Message is longer than 65535 bytes.  Holds length of the message.
+    MessageTooLong(usize),
+}
+
+impl From<EntryError> for solana_program::program_error::ProgramError {
+    fn from(_: EntryError) -> Self { Self::InvalidArgument }
+}
+
+
 /// Error when parsing a signature.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Error {
@@ -327,19 +1371,43 @@ impl From<Error> for solana_program::program_error::ProgramError {
     fn from(_: Error) -> Self { Self::InvalidInstructionData }
 }
 
+impl From<Error> for BadData {
+    fn from(_: Error) -> Self { Self }
+}
+
 
 /// Decodes signature entry from the instruction data.
 ///
 /// `data` is the entire instruction data for the native signature verification
 /// program call and `entry` is one of the signature offsets entry from that
-/// instruction data.
-fn decode_entry<'a>(
+/// instruction data (though it may come from anywhere).  This is what
+/// [`parse_data`] iterator does for each entry and can be used to examine
+/// single entries, e.g. to find out why one of them fails to decode.
+///
+/// Returns [`Error::UnsupportedFeature`] if the entry references data in other
+/// instructions or [`Error::BadData`] if the offsets point outside of `data`.
+/// Like `parse_data`, never panics.
+pub fn decode_entry<'a>(
     data: &'a [u8],
     entry: &'a [u8; 14],
 ) -> Result<Entry<'a>, Error> {
-    let entry: &[[u8; 2]; 7] = bytemuck::must_cast_ref(entry);
-    let entry = entry.map(u16::from_le_bytes);
-    let entry: SignatureOffsets = bytemuck::must_cast(entry);
+    let (signature, pubkey, message) = decode_parts(data, entry)?;
+    Ok(Entry { signature, pubkey, message })
+}
+
+/// Signature, public key of length `K` and message of a signature entry.
+type Parts<'a, const K: usize> = (&'a [u8; 64], &'a [u8; K], &'a [u8]);
+
+/// Decodes signature, public key of length `K` and message from the
+/// instruction data.
+///
+/// This is like [`decode_entry`] but supports programs whose public keys
+/// aren’t 32-byte long.
+fn decode_parts<'a, const K: usize>(
+    data: &'a [u8],
+    entry: &'a [u8; 14],
+) -> Result<Parts<'a, K>, Error> {
+    let entry = SignatureOffsets::from_le_bytes(entry);
 
     if entry.signature_instruction_index != u16::MAX ||
         entry.pubkey_instruction_index != u16::MAX ||
@@ -348,17 +1416,41 @@ fn decode_entry<'a>(
         return Err(Error::UnsupportedFeature);
     }
 
+    resolve_parts(entry, |_| Some(data))
+}
+
+/// Reads signature, public key and message pointed by given offsets.
+///
+/// `get_data` maps instruction index (as stored in the offsets) into data of
+/// that instruction.  Returns [`Error::BadData`] if `get_data` returns `None` or
+/// offsets point outside of the instruction data.
+fn resolve_entry<'a>(
+    entry: SignatureOffsets,
+    get_data: impl Fn(u16) -> Option<&'a [u8]>,
+) -> Result<Entry<'a>, Error> {
+    let (signature, pubkey, message) = resolve_parts(entry, get_data)?;
+    Ok(Entry { signature, pubkey, message })
+}
+
+/// Reads signature, public key of length `K` and message pointed by given
+/// offsets; see [`resolve_entry`].
+fn resolve_parts<'a, const K: usize>(
+    entry: SignatureOffsets,
+    get_data: impl Fn(u16) -> Option<&'a [u8]>,
+) -> Result<Parts<'a, K>, Error> {
     fn get_array<const N: usize>(data: &[u8], offset: u16) -> Option<&[u8; N]> {
         Some(stdx::split_at::<N, u8>(data.get(usize::from(offset)..)?)?.0)
     }
 
     (|| {
+        let data = get_data(entry.signature_instruction_index)?;
         let signature = get_array::<64>(data, entry.signature_offset)?;
-        let pubkey = get_array::<32>(data, entry.pubkey_offset)?;
-        let message = data
+        let data = get_data(entry.pubkey_instruction_index)?;
+        let pubkey = get_array::<K>(data, entry.pubkey_offset)?;
+        let message = get_data(entry.message_instruction_index)?
             .get(usize::from(entry.message_offset)..)?
             .get(..usize::from(entry.message_size))?;
-        Some(Entry { signature, pubkey, message })
+        Some((signature, pubkey, message))
     })()
     .ok_or(Error::BadData)
 }
@@ -465,6 +1557,7 @@ mod test {
         Entry { signature: &ctx.0, pubkey: &ctx.1, message: b"message" }
     }
 
+    #[allow(clippy::type_complexity)]
     fn prepare_two_signatures_test(
         msg1: &[u8],
         msg2: &[u8],
@@ -539,6 +1632,22 @@ mod test {
         Entry { signature: &ctx.2, pubkey: &ctx.3, message: b"fo" }
     }
 
+    make_test! {
+        two_signatures_suffix_message;
+        let ctx = prepare_two_signatures_test(b"foo", b"oo", &SECRETKEY1);
+        ctx.4;
+        Entry { signature: &ctx.0, pubkey: &ctx.1, message: b"foo" },
+        Entry { signature: &ctx.2, pubkey: &ctx.3, message: b"oo" }
+    }
+
+    make_test! {
+        two_signatures_substring_message;
+        let ctx = prepare_two_signatures_test(b"foobar", b"oba", &SECRETKEY1);
+        ctx.4;
+        Entry { signature: &ctx.0, pubkey: &ctx.1, message: b"foobar" },
+        Entry { signature: &ctx.2, pubkey: &ctx.3, message: b"oba" }
+    }
+
     const SECRETKEY2: [u8; 32] = [
         157, 97, 177, 157, 239, 253, 90, 96, 186, 132, 74, 244, 146, 236, 44,
         196, 68, 73, 197, 105, 123, 50, 105, 25, 112, 59, 172, 3, 28, 174, 127,
@@ -568,4 +1677,728 @@ mod test {
         Entry { signature: &ctx.0, pubkey: &ctx.1, message: b"foo" },
         Entry { signature: &ctx.2, pubkey: &ctx.3, message: b"fo" }
     }
+
+    make_test! {
+        two_signatures_suffix_message_diff_keys;
+        let ctx = prepare_two_signatures_test(b"foo", b"oo", &SECRETKEY2);
+        ctx.4;
+        Entry { signature: &ctx.0, pubkey: &ctx.1, message: b"foo" },
+        Entry { signature: &ctx.2, pubkey: &ctx.3, message: b"oo" }
+    }
+
+    make_test! {
+        two_signatures_substring_message_diff_keys;
+        let ctx = prepare_two_signatures_test(b"foobar", b"oba", &SECRETKEY2);
+        ctx.4;
+        Entry { signature: &ctx.0, pubkey: &ctx.1, message: b"foobar" },
+        Entry { signature: &ctx.2, pubkey: &ctx.3, message: b"oba" }
+    }
+
+    #[test]
+    fn test_parse_instructions() {
+        let (sig1, key1) = make_signature(b"foo", &SECRETKEY1);
+        let (sig2, key2) = make_signature(b"bar", &SECRETKEY2);
+        let entry1 = Entry { signature: &sig1, pubkey: &key1, message: b"foo" };
+        let entry2 = Entry { signature: &sig2, pubkey: &key2, message: b"bar" };
+
+        // Instruction #0 holds message and signature of the second entry.
+        let other = Instruction {
+            program_id: Pubkey::new_unique(),
+            accounts: Vec::new(),
+            data: [&b"xbar"[..], &sig2[..]].concat(),
+        };
+
+        // Instruction #1 verifies first entry inline and second entry with
+        // message and signature from instruction #0.
+        let mut native =
+            new_instruction(ED25519_PROGRAM_ID, &[entry1, entry2]).unwrap();
+        let offsets = SignatureOffsets {
+            signature_offset: 4,
+            signature_instruction_index: 0,
+            pubkey_offset: 2 + 2 * 14 + 3 + 64 + 32 + 3 + 64,
+            pubkey_instruction_index: u16::MAX,
+            message_offset: 1,
+            message_size: 3,
+            message_instruction_index: 0,
+        };
+        native.data[16..30].copy_from_slice(&offsets.to_le_bytes());
+
+        let mut iter = parse_data(&native.data).unwrap();
+        assert_eq!(Some(Ok(entry1)), iter.next());
+        assert_eq!(Some(Err(Error::UnsupportedFeature)), iter.next());
+        assert_eq!(None, iter.next());
+
+        let mut iter = parse_data_with_resolver(&native.data, |index| {
+            (index == 0).then_some(other.data.as_slice())
+        })
+        .unwrap();
+        assert_eq!(2, iter.len());
+        assert_eq!(Some(Ok(entry1)), iter.next());
+        assert_eq!(Some(Ok(entry2)), iter.next());
+        assert_eq!(None, iter.next());
+        let mut iter =
+            parse_data_with_resolver(&native.data, |_| None).unwrap();
+        assert_eq!(Some(Err(Error::BadData)), iter.next_back());
+
+        let instructions = [other, native];
+        assert_eq!(
+            Ok(vec![entry1, entry2]),
+            parse_instructions(&ED25519_PROGRAM_ID, &instructions)
+        );
+
+        // Reference to non-existent instruction.
+        let mut instructions = instructions;
+        instructions[1].data[17] = 2;
+        assert_eq!(
+            Err(BadData),
+            parse_instructions(&ED25519_PROGRAM_ID, &instructions)
+        );
+    }
+
+    #[test]
+    fn test_new_instruction_data_errors() {
+        let sig = [0; 64];
+        let key = [0; 32];
+        let entry = Entry { signature: &sig, pubkey: &key, message: b"" };
+        assert_eq!(
+            Some(BuildError::TooManyEntries(256)),
+            new_instruction_data(&[entry; 256]).err()
+        );
+        assert!(new_instruction_data(&[entry; 255]).is_ok());
+
+        let long = vec![0; 65536];
+        let entries = [entry, Entry { message: &long, ..entry }];
+        assert_eq!(
+            Some(BuildError::MessageTooLong { index: 1, len: 65536 }),
+            new_instruction_data(&entries).err()
+        );
+
+        let long = vec![0; 65400];
+        let entries = [entry, Entry { message: &long, ..entry }];
+        assert_eq!(
+            Some(BuildError::DataTooLong(2 + 2 * 78 + 32 + 65400)),
+            new_instruction_data(&entries).err()
+        );
+    }
+
+    #[test]
+    fn test_new_split_instructions() {
+        let (sig1, key1) = make_signature(b"foo", &SECRETKEY1);
+        let (sig2, key2) = make_signature(b"fo", &SECRETKEY2);
+        let (sig3, key3) = make_signature(&[42; 100], &SECRETKEY1);
+        let entries = [
+            Entry { signature: &sig1, pubkey: &key1, message: b"foo" },
+            Entry { signature: &sig2, pubkey: &key2, message: b"fo" },
+            Entry { signature: &sig3, pubkey: &key3, message: &[42; 100] },
+        ];
+        let data_program_id = Pubkey::new_unique();
+        let split_at = |index, budget| {
+            new_split_instructions(
+                ED25519_PROGRAM_ID,
+                data_program_id,
+                index,
+                &entries,
+                budget,
+            )
+        };
+        let split = |budget| split_at(0, budget);
+
+        // Everything fits.
+        let inline = new_instruction_data(&entries).unwrap();
+        let (native, aux) = split(inline.len()).unwrap();
+        assert_eq!(inline, native.data);
+        assert_eq!((data_program_id, 0), (aux.program_id, aux.data.len()));
+
+        // Longest message needs to be moved.
+        let (native, aux) = split(inline.len() - 1).unwrap();
+        assert_eq!(inline.len() - 100, native.data.len());
+        assert_eq!(&[42; 100][..], aux.data.as_slice());
+        let mut iter = parse_data(&native.data).unwrap();
+        assert_eq!(Some(Ok(entries[0])), iter.next());
+        assert_eq!(Some(Ok(entries[1])), iter.next());
+        assert_eq!(Some(Err(Error::UnsupportedFeature)), iter.next());
+        assert_eq!(
+            Ok(entries.to_vec()),
+            parse_instructions(&ED25519_PROGRAM_ID, &[aux, native])
+        );
+
+        // All messages need to be moved.  Second message is a prefix of the
+        // first one so it follows the first message.
+        let (native, aux) = split(inline.len() - 101).unwrap();
+        assert_eq!(inline.len() - 103, native.data.len());
+        assert_eq!(103, aux.data.len());
+        assert_eq!(
+            Ok(entries.to_vec()),
+            parse_instructions(&ED25519_PROGRAM_ID, &[aux, native])
+        );
+
+        assert_eq!(
+            Some(BuildError::BudgetExceeded(inline.len() - 103)),
+            split(inline.len() - 104).err()
+        );
+
+        // u16::MAX means the current instruction so it can’t be used as index
+        // of the auxiliary instruction.
+        assert_eq!(
+            Some(BuildError::BadInstructionIndex),
+            split_at(u16::MAX, inline.len()).err()
+        );
+        assert!(split_at(u16::MAX - 1, inline.len()).is_ok());
+    }
+
+    #[test]
+    fn test_instruction_data_len() {
+        let (sig1, key1) = make_signature(b"foo", &SECRETKEY1);
+        let (sig2, key2) = make_signature(b"bar", &SECRETKEY2);
+        let entries = [
+            Entry { signature: &sig1, pubkey: &key1, message: b"foo" },
+            Entry { signature: &sig2, pubkey: &key2, message: b"bar" },
+            Entry { signature: &sig1, pubkey: &key1, message: b"fo" },
+            Entry { signature: &sig2, pubkey: &key1, message: b"ba" },
+        ];
+        for n in 0..=entries.len() {
+            let want = new_instruction_data(&entries[..n]).unwrap().len();
+            assert_eq!(Ok(want), instruction_data_len(&entries[..n]));
+        }
+        assert_eq!(Ok(2 + 4 * 78 + 2 * 32 + 6), instruction_data_len(&entries));
+    }
+
+    #[test]
+    fn test_fits_in_transaction() {
+        assert_eq!(1062, MAX_INSTRUCTION_DATA_LEN);
+
+        let sig = [0; 64];
+        let key = [0; 32];
+        let message = [0; MAX_INSTRUCTION_DATA_LEN - 2 - 78 - 32];
+        let entry = Entry { signature: &sig, pubkey: &key, message: &message };
+        assert!(fits_in_transaction(&[]));
+        assert!(fits_in_transaction(&[entry]));
+        assert!(!fits_in_transaction(&[entry, entry]));
+        let entry =
+            Entry { message: &[0; MAX_INSTRUCTION_DATA_LEN - 111], ..entry };
+        assert!(!fits_in_transaction(&[entry]));
+    }
+
+    #[test]
+    fn test_dedup_maps() {
+        let sigs = (0..200u8).map(|n| [n; 64]).collect::<Vec<_>>();
+        let keys = (0..7u8).map(|n| [n; 32]).collect::<Vec<_>>();
+        let messages = (0..11u8)
+            .map(|n| (0..n % 5 + 1).map(|k| (n + k) % 3).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let entries = sigs
+            .iter()
+            .enumerate()
+            .map(|(idx, signature)| Entry {
+                signature,
+                pubkey: &keys[idx % keys.len()],
+                message: &messages[idx * 5 % messages.len()],
+            })
+            .collect::<Vec<_>>();
+
+        let write = |use_maps| {
+            let dedup = Dedup::with_options(
+                &entries,
+                MessageDedup::default(),
+                use_maps,
+            );
+            let mut data = Vec::with_capacity(dedup.len());
+            let len = write_instruction_data(data.spare_capacity_mut(), &dedup);
+            assert_eq!(dedup.len(), len);
+            // SAFETY: write_instruction_data initialised len bytes.
+            unsafe { data.set_len(len) };
+            let report = (0..entries.len())
+                .map(|idx| (dedup.message(idx), dedup.pubkey(idx)))
+                .collect::<Vec<_>>();
+            (data, report)
+        };
+        let (data, report) = write(false);
+        assert_eq!((data.clone(), report.clone()), write(true));
+        assert_eq!(Ok(data), new_instruction_data(&entries));
+
+        // dedup_report uses maps for this many entries and must mirror the
+        // decisions of the linear scan.
+        let want = report
+            .into_iter()
+            .map(|(message, pubkey)| DedupInfo { message, pubkey })
+            .collect::<Vec<_>>();
+        assert_eq!(Ok(want), dedup_report(&entries));
+    }
+
+    #[test]
+    fn test_new_instruction_data_into() {
+        let (sig1, key1) = make_signature(b"foo", &SECRETKEY1);
+        let (sig2, key2) = make_signature(b"bar", &SECRETKEY2);
+        let entries = [
+            Entry { signature: &sig1, pubkey: &key1, message: b"foo" },
+            Entry { signature: &sig2, pubkey: &key2, message: b"bar" },
+        ];
+        let want = new_instruction_data(&entries).unwrap();
+
+        let mut buf = [0xAA; 512];
+        assert_eq!(
+            Ok(want.len()),
+            new_instruction_data_into(&mut buf, &entries)
+        );
+        assert_eq!(want.as_slice(), &buf[..want.len()]);
+        assert!(buf[want.len()..].iter().all(|&byte| byte == 0xAA));
+
+        let mut buf = [0; 512];
+        assert_eq!(
+            Ok(want.len()),
+            new_instruction_data_into(&mut buf[..want.len()], &entries)
+        );
+        assert_eq!(
+            Err(BuildError::BufferTooSmall(want.len())),
+            new_instruction_data_into(&mut buf[..want.len() - 1], &entries)
+        );
+    }
+
+    #[test]
+    fn test_new_instruction_data_reuse() {
+        let (sig1, key1) = make_signature(b"foo", &SECRETKEY1);
+        let (sig2, key2) = make_signature(b"bar", &SECRETKEY2);
+        let entries = [
+            Entry { signature: &sig1, pubkey: &key1, message: b"foo" },
+            Entry { signature: &sig2, pubkey: &key2, message: b"bar" },
+            Entry { signature: &sig2, pubkey: &key2, message: b"fo" },
+        ];
+
+        let mut buf = vec![42; 1024];
+        let ptr = buf.as_ptr();
+        for count in [3, 1, 2, 0] {
+            let entries = &entries[..count];
+            new_instruction_data_reuse(&mut buf, entries).unwrap();
+            assert_eq!(new_instruction_data(entries).unwrap(), buf);
+            // The buffer was large enough so it hasn’t been reallocated.
+            assert_eq!(ptr, buf.as_ptr());
+        }
+
+        let entries = [entries[0]; 256];
+        assert_eq!(
+            Err(BuildError::TooManyEntries(256)),
+            new_instruction_data_reuse(&mut buf, &entries)
+        );
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_new_instruction_data_iter() {
+        let (sig1, key1) = make_signature(b"foo", &SECRETKEY1);
+        let (sig2, key2) = make_signature(b"bar", &SECRETKEY2);
+        let entries = [
+            Entry { signature: &sig1, pubkey: &key1, message: b"foo" },
+            Entry { signature: &sig2, pubkey: &key2, message: b"bar" },
+        ];
+        let want = new_instruction_data(&entries);
+        assert_eq!(want, new_instruction_data_iter(entries));
+        assert_eq!(want, new_instruction_data_iter(entries.iter().copied()));
+        let owned = entries.map(OwnedEntry::from);
+        let iter = owned.iter().map(OwnedEntry::as_entry);
+        assert_eq!(want, new_instruction_data_iter(iter));
+
+        let entries = core::iter::repeat_n(entries[0], 256);
+        assert_eq!(
+            Err(BuildError::TooManyEntries(256)),
+            new_instruction_data_iter(entries)
+        );
+    }
+
+    #[test]
+    fn test_new_instruction_data_sorted() {
+        let sig = [0; 64];
+        let key = [0; 32];
+        let entry = |message| Entry { signature: &sig, pubkey: &key, message };
+        let entries =
+            [entry(&b"fo"[..]), entry(b"bar"), entry(b"oo"), entry(b"foo")];
+
+        let plain = new_instruction_data(&entries).unwrap();
+        let sorted = new_instruction_data_sorted(&entries).unwrap();
+        assert_eq!(2 + 4 * 78 + 32 + 2 + 3 + 2 + 3, plain.len());
+        assert_eq!(2 + 4 * 78 + 32 + 3 + 3, sorted.len());
+
+        let parsed = parse_data(&sorted)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let want = [entries[1], entries[3], entries[0], entries[2]];
+        assert_eq!(&want[..], parsed.as_slice());
+    }
+
+    #[test]
+    fn test_dedup_report() {
+        let sig = [0; 64];
+        let (key1, key2) = ([1; 32], [2; 32]);
+        let entry =
+            |pubkey, message| Entry { signature: &sig, pubkey, message };
+        let entries = [
+            entry(&key1, &b"foo"[..]),
+            entry(&key2, b"bar"),
+            entry(&key2, b"oo"),
+            entry(&key1, b"bar"),
+        ];
+        let info = |message, pubkey| DedupInfo { message, pubkey };
+        let want = [
+            info(None, None),
+            info(None, None),
+            info(Some((0, 1)), Some(1)),
+            info(Some((1, 0)), Some(0)),
+        ];
+        assert_eq!(Ok(want.to_vec()), dedup_report(&entries));
+
+        let entries = [entries[0]; 256];
+        assert_eq!(
+            Err(BuildError::TooManyEntries(256)),
+            dedup_report(&entries)
+        );
+    }
+
+    #[test]
+    fn test_sort_for_dedup() {
+        let sig = [0; 64];
+        let key = [0; 32];
+        let entry = |message| Entry { signature: &sig, pubkey: &key, message };
+        let mut entries =
+            [entry(&b"fo"[..]), entry(b"bar"), entry(b"oo"), entry(b"foo")];
+        let want = [entries[1], entries[3], entries[0], entries[2]];
+        sort_for_dedup(&mut entries);
+        assert_eq!(want, entries);
+    }
+
+    #[test]
+    fn test_parse_adversarial() {
+        fn parse(
+            data: &[u8],
+        ) -> Result<Vec<Result<Entry<'_>, Error>>, BadData> {
+            let iter = parse_data(data)?;
+            assert_eq!(iter.len(), iter.clone().rev().count());
+            Ok(iter.collect())
+        }
+
+        // Count larger than number of available offsets entries.
+        assert_eq!(Err(BadData), parse(&[]));
+        assert_eq!(Err(BadData), parse(&[1, 0]));
+        assert_eq!(
+            Err(BadData),
+            parse(&[2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0])
+        );
+        assert_eq!(Err(BadData), parse(&[255; 300]));
+        assert_eq!(Err(BadData), parse(&[0, 1]));
+
+        // Offsets and sizes which point past the data or overflow u16 when
+        // added together.
+        let mut data = vec![1, 0];
+        data.extend_from_slice(
+            &SignatureOffsets {
+                signature_offset: u16::MAX,
+                signature_instruction_index: u16::MAX,
+                pubkey_offset: u16::MAX - 1,
+                pubkey_instruction_index: u16::MAX,
+                message_offset: u16::MAX,
+                message_size: u16::MAX,
+                message_instruction_index: u16::MAX,
+            }
+            .to_le_bytes(),
+        );
+        data.resize(usize::from(u16::MAX), 0);
+        assert_eq!(Ok(vec![Err(Error::BadData)]), parse(&data));
+
+        // Message range which would wrap around.
+        let offsets = SignatureOffsets::inline(16, 16, 200, u16::MAX - 100);
+        let mut data = vec![1, 0];
+        data.extend_from_slice(&offsets.to_le_bytes());
+        data.resize(300, 0);
+        assert_eq!(Ok(vec![Err(Error::BadData)]), parse(&data));
+
+        // Random mutations of valid data.  The exact result doesn’t matter as
+        // long as parsing doesn’t panic.
+        let (sig1, key1) = make_signature(b"foo", &SECRETKEY1);
+        let (sig2, key2) = make_signature(b"bar", &SECRETKEY2);
+        let valid = new_instruction_data(&[
+            Entry { signature: &sig1, pubkey: &key1, message: b"foo" },
+            Entry { signature: &sig2, pubkey: &key2, message: b"bar" },
+        ])
+        .unwrap();
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut rand = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        for _ in 0..10_000 {
+            let mut data = valid.clone();
+            for _ in 0..rand() % 8 + 1 {
+                let idx = (rand() % data.len() as u64) as usize;
+                data[idx] = rand() as u8;
+            }
+            data.truncate((rand() % (data.len() as u64 + 1)) as usize);
+            let _ = parse(&data);
+        }
+    }
+
+    #[test]
+    fn test_prefix_message_length() {
+        let (sig1, key1) = make_signature(b"foo", &SECRETKEY1);
+        let (sig2, key2) = make_signature(b"fo", &SECRETKEY2);
+        let data = new_instruction_data(&[
+            Entry { signature: &sig1, pubkey: &key1, message: b"foo" },
+            Entry { signature: &sig2, pubkey: &key2, message: b"fo" },
+        ])
+        .unwrap();
+        let got =
+            parse_data(&data).unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(b"foo", got[0].message);
+        assert_eq!(b"fo", got[1].message);
+        // The second message is stored in the same bytes as the first one.
+        assert_eq!(got[0].message.as_ptr(), got[1].message.as_ptr());
+    }
+
+    #[test]
+    fn test_contains_all() {
+        let (sig1, key1) = make_signature(b"foo", &SECRETKEY1);
+        let (sig2, key2) = make_signature(b"bar", &SECRETKEY2);
+        let data = new_instruction_data(&[
+            Entry { signature: &sig1, pubkey: &key1, message: b"foo" },
+            Entry { signature: &sig2, pubkey: &key2, message: b"bar" },
+        ])
+        .unwrap();
+        let foo: (&[u8; 32], &[u8]) = (&key1, b"foo");
+        let bar: (&[u8; 32], &[u8]) = (&key2, b"bar");
+        let baz: (&[u8; 32], &[u8]) = (&key2, b"foo");
+
+        assert_eq!(Ok(true), contains_all(&data, &[]));
+        assert_eq!(Ok(true), contains_all(&data, &[foo]));
+        assert_eq!(Ok(true), contains_all(&data, &[bar, foo]));
+        assert_eq!(Ok(false), contains_all(&data, &[foo, baz]));
+        assert_eq!(Err(Error::BadData), contains_all(&data[..20], &[foo]));
+    }
+
+    #[test]
+    fn test_for_each_offsets() {
+        let (sig1, key1) = make_signature(b"foo", &SECRETKEY1);
+        let (sig2, key2) = make_signature(b"bar", &SECRETKEY2);
+        let mut data = new_instruction_data(&[
+            Entry { signature: &sig1, pubkey: &key1, message: b"foo" },
+            Entry { signature: &sig2, pubkey: &key2, message: b"bar" },
+        ])
+        .unwrap();
+        let mut offsets = SignatureOffsets::from_le_bytes(
+            data[2 + OFF_SIZE..2 + 2 * OFF_SIZE].try_into().unwrap(),
+        );
+        offsets.message_instruction_index = 3;
+        data[2 + OFF_SIZE..2 + 2 * OFF_SIZE]
+            .copy_from_slice(&offsets.to_le_bytes());
+
+        let mut got = Vec::new();
+        for_each_offsets(&data, |offsets| {
+            got.push(offsets);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(2, got.len());
+        assert_eq!(u16::MAX, got[0].message_instruction_index);
+        assert_eq!(offsets, got[1]);
+
+        // Errors from the callback stop the iteration.
+        let mut calls = 0;
+        let res = for_each_offsets(&data, |_| {
+            calls += 1;
+            Err(Error::UnsupportedFeature)
+        });
+        assert_eq!((Err(Error::UnsupportedFeature), 1), (res, calls));
+
+        // Malformed header.
+        assert_eq!(
+            Err(Error::BadData),
+            for_each_offsets(&data[..20], |_| Ok(()))
+        );
+    }
+
+    #[test]
+    fn test_decode_entry() {
+        let (sig1, key1) = make_signature(b"foo", &SECRETKEY1);
+        let entry = Entry { signature: &sig1, pubkey: &key1, message: b"foo" };
+        let data = new_instruction_data(&[entry]).unwrap();
+        let offsets = SignatureOffsets::from_le_bytes(
+            data[2..2 + OFF_SIZE].try_into().unwrap(),
+        );
+        assert_eq!(Ok(entry), decode_entry(&data, &offsets.to_le_bytes()));
+
+        let mut other = offsets;
+        other.pubkey_instruction_index = 0;
+        assert_eq!(
+            Err(Error::UnsupportedFeature),
+            decode_entry(&data, &other.to_le_bytes())
+        );
+
+        let mut other = offsets;
+        other.message_offset = data.len() as u16 - 2;
+        assert_eq!(
+            Err(Error::BadData),
+            decode_entry(&data, &other.to_le_bytes())
+        );
+        assert_eq!(
+            Err(Error::BadData),
+            decode_entry(&data[..data.len() - 1], &offsets.to_le_bytes())
+        );
+    }
+
+    #[test]
+    fn test_describe() {
+        let (sig1, key1) = make_signature(b"foo", &SECRETKEY1);
+        let entry = Entry { signature: &sig1, pubkey: &key1, message: b"foo" };
+        let mut data = new_instruction_data(&[entry, entry, entry]).unwrap();
+        let second = 2 + OFF_SIZE;
+        data[second..][SignatureOffsets::SIGNATURE_INSTRUCTION_INDEX_RANGE]
+            .copy_from_slice(&0u16.to_le_bytes());
+        let third = 2 + 2 * OFF_SIZE;
+        data[third..][SignatureOffsets::MESSAGE_OFFSET_RANGE]
+            .copy_from_slice(&u16::MAX.to_le_bytes());
+
+        let want = "3 entries\nentry 0: sig@47 key@111 msg@44(len 3) \
+                    inline\nentry 1: sig@143 key@111 msg@44(len 3) \
+                    cross-instruction (sig ix 0, key ix 65535, msg ix \
+                    65535)\nentry 2: sig@207 key@111 msg@65535(len 3) inline: \
+                    out of bounds";
+        assert_eq!(want, describe(&data).to_string());
+        assert_eq!("malformed header (1 bytes)", describe(&[1]).to_string());
+    }
+
+    #[test]
+    fn test_counts() {
+        let (sig1, key1) = make_signature(b"foo", &SECRETKEY1);
+        let mut data = new_instruction_data(&[
+            Entry { signature: &sig1, pubkey: &key1, message: b"foo" },
+            Entry { signature: &sig1, pubkey: &key1, message: b"foo" },
+            Entry { signature: &sig1, pubkey: &key1, message: b"foo" },
+        ])
+        .unwrap();
+        assert_eq!((3, 0, 0), parse_data(&data).unwrap().counts());
+
+        // Make the second entry reference another instruction and the third
+        // point outside of the data.
+        let second = 2 + OFF_SIZE;
+        let third = 2 + 2 * OFF_SIZE;
+        data[second..][SignatureOffsets::SIGNATURE_INSTRUCTION_INDEX_RANGE]
+            .copy_from_slice(&0u16.to_le_bytes());
+        data[third..][SignatureOffsets::SIGNATURE_OFFSET_RANGE]
+            .copy_from_slice(&u16::MAX.to_le_bytes());
+        assert_eq!((1, 1, 1), parse_data(&data).unwrap().counts());
+    }
+
+    #[test]
+    fn test_unique_pubkeys() {
+        let (sig1, key1) = make_signature(b"foo", &SECRETKEY1);
+        let (sig2, key2) = make_signature(b"bar", &SECRETKEY2);
+        let (sig3, _) = make_signature(b"bar", &SECRETKEY1);
+        let data = new_instruction_data(&[
+            Entry { signature: &sig1, pubkey: &key1, message: b"foo" },
+            Entry { signature: &sig2, pubkey: &key2, message: b"bar" },
+            Entry { signature: &sig3, pubkey: &key1, message: b"bar" },
+        ])
+        .unwrap();
+        let got = parse_data(&data)
+            .unwrap()
+            .unique_pubkeys()
+            .collect::<Result<Vec<_>, _>>();
+        assert_eq!(Ok(vec![&key1, &key2]), got);
+    }
+
+    #[test]
+    fn test_signature_offsets_le_bytes() {
+        let offsets = SignatureOffsets {
+            signature_offset: 0x0102,
+            signature_instruction_index: 0x0304,
+            pubkey_offset: 0x0506,
+            pubkey_instruction_index: 0x0708,
+            message_offset: 0x090A,
+            message_size: 0x0B0C,
+            message_instruction_index: 0x0D0E,
+        };
+        let bytes = [2, 1, 4, 3, 6, 5, 8, 7, 10, 9, 12, 11, 14, 13];
+        assert_eq!(bytes, offsets.to_le_bytes());
+        assert_eq!(offsets, SignatureOffsets::from_le_bytes(&bytes));
+
+        let field = |range: core::ops::Range<usize>| {
+            u16::from_le_bytes(bytes[range].try_into().unwrap())
+        };
+        assert_eq!(offsets, SignatureOffsets {
+            signature_offset: field(SignatureOffsets::SIGNATURE_OFFSET_RANGE),
+            signature_instruction_index: field(
+                SignatureOffsets::SIGNATURE_INSTRUCTION_INDEX_RANGE
+            ),
+            pubkey_offset: field(SignatureOffsets::PUBKEY_OFFSET_RANGE),
+            pubkey_instruction_index: field(
+                SignatureOffsets::PUBKEY_INSTRUCTION_INDEX_RANGE
+            ),
+            message_offset: field(SignatureOffsets::MESSAGE_OFFSET_RANGE),
+            message_size: field(SignatureOffsets::MESSAGE_SIZE_RANGE),
+            message_instruction_index: field(
+                SignatureOffsets::MESSAGE_INSTRUCTION_INDEX_RANGE
+            ),
+        });
+
+        let offsets = SignatureOffsets::inline(0x0102, 0x0506, 0x090A, 0x0B0C);
+        let bytes = [2, 1, 255, 255, 6, 5, 255, 255, 10, 9, 12, 11, 255, 255];
+        assert_eq!(bytes, offsets.to_le_bytes());
+    }
+
+    #[test]
+    fn test_entry_new() {
+        let message = vec![42; 65536];
+        let entry = Entry::new(&[1; 64], &[2; 32], &message[..65535]);
+        assert_eq!(
+            Ok(Entry {
+                signature: &[1; 64],
+                pubkey: &[2; 32],
+                message: &message[..65535]
+            }),
+            entry
+        );
+        assert_eq!(
+            Err(EntryError::MessageTooLong(65536)),
+            Entry::new(&[1; 64], &[2; 32], &message)
+        );
+    }
+
+    #[test]
+    fn test_owned_entry() {
+        let (sig1, key1) = make_signature(b"foo", &SECRETKEY1);
+        let (sig2, key2) = make_signature(b"bar", &SECRETKEY2);
+        let entries = [
+            Entry { signature: &sig1, pubkey: &key1, message: b"foo" },
+            Entry { signature: &sig2, pubkey: &key2, message: b"bar" },
+        ];
+        let owned = entries.map(OwnedEntry::from);
+        assert_eq!(entries[0], owned[0].as_entry());
+        assert_eq!(entries[1], owned[1].as_entry());
+
+        let want = new_instruction_data(&entries).unwrap();
+        assert_eq!(Ok(want.clone()), new_instruction_data(&owned));
+        let refs = [&owned[0], &owned[1]];
+        assert_eq!(Ok(want.clone()), new_instruction_data(&refs));
+        let instruction = new_instruction(ED25519_PROGRAM_ID, &owned).unwrap();
+        assert_eq!(want, instruction.data);
+    }
+
+    #[test]
+    #[cfg(feature = "borsh")]
+    fn test_borsh() {
+        let entry = OwnedEntry {
+            signature: [1; 64],
+            pubkey: [2; 32],
+            message: b"foo".to_vec(),
+        };
+        let mut want = vec![1; 64];
+        want.extend_from_slice(&[2; 32]);
+        want.extend_from_slice(&[3, 0, 0, 0, b'f', b'o', b'o']);
+
+        let data = borsh::to_vec(&entry).unwrap();
+        assert_eq!(want, data);
+        assert_eq!(entry, borsh::from_slice::<OwnedEntry>(&data).unwrap());
+        assert!(
+            borsh::from_slice::<OwnedEntry>(&data[..data.len() - 1]).is_err()
+        );
+    }
 }
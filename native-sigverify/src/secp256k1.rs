@@ -0,0 +1,430 @@
+//! Support for the Secp256k1 native program.
+//!
+//! Secp256k1 program uses different instruction data layout than Ed25519
+//! program.  Its offsets structure is 11 bytes long with 8-bit instruction
+//! indexes, signatures are followed by a one-byte recovery id and instead of
+//! public keys the program takes 20-byte Ethereum addresses.  Furthermore, the
+//! program hashes messages with Keccak-256 before verifying the signatures.
+//!
+//! Secp256k1 program also has no way to refer to the instruction being
+//! executed.  Instead, offsets always hold absolute index of an instruction in
+//! the transaction.  Because of that, functions in this module take index of
+//! the Secp256k1 program instruction within the transaction.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use solana_program::instruction::Instruction;
+
+use crate::{checked_len, stdx, BadData, BuildError, Error};
+
+/// Offsets used in instruction data of the Secp256k1 native program.
+///
+/// This is a low-level structure.  Typically you’d want to use
+/// [`new_secp256k1_instruction`] or [`parse_secp256k1_data`] instead.
+///
+/// All integers are stored as little-endian.
+// See https://github.com/anza-xyz/solana-sdk/blob/master/secp256k1-program/src/lib.rs
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SecpSignatureOffsets {
+    /// Offset to 64-byte signature followed by 1-byte recovery id.
+    pub signature_offset: u16,
+    /// Index of the instruction holding the signature.
+    pub signature_instruction_index: u8,
+    /// Offset to 20-byte Ethereum address.
+    pub eth_address_offset: u16,
+    /// Index of the instruction holding the Ethereum address.
+    pub eth_address_instruction_index: u8,
+    /// Offset to start of message data.
+    pub message_offset: u16,
+    /// Size of message data.
+    pub message_size: u16,
+    /// Index of the instruction holding the message.
+    pub message_instruction_index: u8,
+}
+
+const SECP_OFF_SIZE: usize = 11;
+
+impl SecpSignatureOffsets {
+    /// Decodes signature offsets from their little-endian representation as
+    /// stored in the instruction data.
+    pub fn from_le_bytes(bytes: &[u8; SECP_OFF_SIZE]) -> Self {
+        let u16 = |idx: usize| u16::from_le_bytes([bytes[idx], bytes[idx + 1]]);
+        Self {
+            signature_offset: u16(0),
+            signature_instruction_index: bytes[2],
+            eth_address_offset: u16(3),
+            eth_address_instruction_index: bytes[5],
+            message_offset: u16(6),
+            message_size: u16(8),
+            message_instruction_index: bytes[10],
+        }
+    }
+
+    /// Encodes signature offsets into their little-endian representation as
+    /// stored in the instruction data.
+    pub fn to_le_bytes(&self) -> [u8; SECP_OFF_SIZE] {
+        let [s0, s1] = self.signature_offset.to_le_bytes();
+        let [e0, e1] = self.eth_address_offset.to_le_bytes();
+        let [m0, m1] = self.message_offset.to_le_bytes();
+        let [l0, l1] = self.message_size.to_le_bytes();
+        [
+            s0,
+            s1,
+            self.signature_instruction_index,
+            e0,
+            e1,
+            self.eth_address_instruction_index,
+            m0,
+            m1,
+            l0,
+            l1,
+            self.message_instruction_index,
+        ]
+    }
+}
+
+
+/// A signature verified by the Secp256k1 native program.
+///
+/// Together, `signature`, `recovery_id` and Keccak-256 hash of the `message`
+/// are sufficient to recover public key of the signer.  Ethereum address is
+/// the last 20 bytes of Keccak-256 hash of the uncompressed public key (without
+/// the leading `0x04` byte).  The native program does exactly that and checks
+/// that the result matches `eth_address`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Secp256k1Entry<'a> {
+    /// The signature in compact (r, s) form.
+    pub signature: &'a [u8; 64],
+    /// Recovery id which follows the signature in the instruction data.
+    pub recovery_id: u8,
+    /// Ethereum address of the signer.
+    pub eth_address: &'a [u8; 20],
+    /// The message.  Note that the program hashes it with Keccak-256 and the
+    /// signature is of that hash.
+    pub message: &'a [u8],
+}
+
+
+/// Creates an instruction calling the Secp256k1 native program.
+///
+/// `instruction_index` is the index the instruction will have in the
+/// transaction.  See [`new_secp256k1_instruction_data`] for details.
+pub fn new_secp256k1_instruction(
+    instruction_index: u8,
+    entries: &[Secp256k1Entry],
+) -> Result<Instruction, BuildError> {
+    let data = new_secp256k1_instruction_data(instruction_index, entries)?;
+    Ok(Instruction {
+        program_id: crate::SECP256K1_PROGRAM_ID,
+        accounts: Vec::new(),
+        data,
+    })
+}
+
+/// Creates instruction data for a call of the Secp256k1 native program.
+///
+/// `instruction_index` is the index the instruction will have in the
+/// transaction.  The offsets in the data refer to that instruction so the
+/// call fails if the instruction is placed at a different index.
+///
+/// Returns the same errors as [`new_instruction_data`](crate::new_instruction_data).
+/// Ethereum addresses and messages used by multiple signatures are included
+/// in the data only once.
+pub fn new_secp256k1_instruction_data(
+    instruction_index: u8,
+    entries: &[Secp256k1Entry],
+) -> Result<Vec<u8>, BuildError> {
+    let count = u8::try_from(entries.len())
+        .map_err(|_| BuildError::TooManyEntries(entries.len()))?;
+    for (index, entry) in entries.iter().enumerate() {
+        let len = entry.message.len();
+        if u16::try_from(len).is_err() {
+            return Err(BuildError::MessageTooLong { index, len });
+        }
+    }
+
+    // The structure of the instruction data is:
+    //   count:   u8
+    //   entries: [SecpSignatureOffsets; count]
+    //   data:    [u8]
+    let mut data = vec![0; 1 + entries.len() * SECP_OFF_SIZE];
+    data[0] = count;
+    let mut offsets = Vec::<SecpSignatureOffsets>::with_capacity(entries.len());
+    for (idx, entry) in entries.iter().enumerate() {
+        let earlier = &entries[..idx];
+
+        let message_offset =
+            match earlier.iter().position(|ent| ent.message == entry.message) {
+                Some(pos) => offsets[pos].message_offset,
+                None => append(&mut data, entry.message),
+            };
+
+        let signature_offset = append(&mut data, entry.signature);
+        data.push(entry.recovery_id);
+
+        let eth_address_offset = match earlier
+            .iter()
+            .position(|ent| ent.eth_address == entry.eth_address)
+        {
+            Some(pos) => offsets[pos].eth_address_offset,
+            None => append(&mut data, entry.eth_address),
+        };
+
+        offsets.push(SecpSignatureOffsets {
+            signature_offset,
+            signature_instruction_index: instruction_index,
+            eth_address_offset,
+            eth_address_instruction_index: instruction_index,
+            message_offset,
+            message_size: entry.message.len() as u16,
+            message_instruction_index: instruction_index,
+        });
+    }
+    checked_len(data.len())?;
+
+    for (dst, offsets) in data[1..].chunks_exact_mut(SECP_OFF_SIZE).zip(offsets)
+    {
+        dst.copy_from_slice(&offsets.to_le_bytes());
+    }
+    Ok(data)
+}
+
+/// Appends bytes to the data returning offset they were written at.
+///
+/// The offset is truncated to 16 bits.  Caller must check length of the data
+/// once it’s complete.
+fn append(dst: &mut Vec<u8>, bytes: &[u8]) -> u16 {
+    let offset = dst.len() as u16;
+    dst.extend_from_slice(bytes);
+    offset
+}
+
+
+/// Creates a new iterator over signatures in given Secp256k1 native program
+/// instruction data.
+///
+/// `instruction_index` is the index of the instruction in the transaction.
+/// The format of the data is:
+///
+/// ```ignore
+/// count:   u8
+/// offsets: [SecpSignatureOffsets; count]
+/// rest:    [u8]
+/// ```
+///
+/// Entries which reference data in other instructions than
+/// `instruction_index` are reported as [`Error::UnsupportedFeature`] errors.
+///
+/// Returns [`BadData`] if the data is malformed.
+pub fn parse_secp256k1_data(
+    data: &[u8],
+    instruction_index: u8,
+) -> Result<Secp256k1Iter<'_>, BadData> {
+    let (count, rest) = data.split_first().ok_or(BadData)?;
+    let entries = stdx::as_chunks::<SECP_OFF_SIZE, u8>(rest)
+        .0
+        .get(..usize::from(*count))
+        .ok_or(BadData)?;
+    Ok(Secp256k1Iter { entries: entries.iter(), data, instruction_index })
+}
+
+/// Iterator over signatures present in Secp256k1 native program instruction
+/// data.
+///
+/// See [`parse_secp256k1_data`].
+#[derive(Clone, Debug)]
+pub struct Secp256k1Iter<'a> {
+    entries: core::slice::Iter<'a, [u8; SECP_OFF_SIZE]>,
+    data: &'a [u8],
+    instruction_index: u8,
+}
+
+impl<'a> Secp256k1Iter<'a> {
+    fn decode(
+        &self,
+        entry: &[u8; SECP_OFF_SIZE],
+    ) -> Result<Secp256k1Entry<'a>, Error> {
+        let entry = SecpSignatureOffsets::from_le_bytes(entry);
+        let index = self.instruction_index;
+        if entry.signature_instruction_index != index ||
+            entry.eth_address_instruction_index != index ||
+            entry.message_instruction_index != index
+        {
+            return Err(Error::UnsupportedFeature);
+        }
+
+        fn get_array<const N: usize>(
+            data: &[u8],
+            offset: u16,
+        ) -> Option<&[u8; N]> {
+            Some(stdx::split_at::<N, u8>(data.get(usize::from(offset)..)?)?.0)
+        }
+
+        let data = self.data;
+        (|| {
+            let signature = get_array::<65>(data, entry.signature_offset)?;
+            let (signature, recovery_id) =
+                stdx::split_array_ref::<64, 1, 65>(signature);
+            let eth_address = get_array::<20>(data, entry.eth_address_offset)?;
+            let message = data
+                .get(usize::from(entry.message_offset)..)?
+                .get(..usize::from(entry.message_size))?;
+            Some(Secp256k1Entry {
+                signature,
+                recovery_id: recovery_id[0],
+                eth_address,
+                message,
+            })
+        })()
+        .ok_or(Error::BadData)
+    }
+}
+
+impl<'a> core::iter::Iterator for Secp256k1Iter<'a> {
+    type Item = Result<Secp256k1Entry<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.entries.next()?;
+        Some(self.decode(entry))
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        let entry = self.entries.nth(n)?;
+        Some(self.decode(entry))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) { self.entries.size_hint() }
+    fn count(self) -> usize { self.entries.count() }
+}
+
+impl core::iter::ExactSizeIterator for Secp256k1Iter<'_> {
+    fn len(&self) -> usize { self.entries.len() }
+}
+
+impl core::iter::DoubleEndedIterator for Secp256k1Iter<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let entry = self.entries.next_back()?;
+        Some(self.decode(entry))
+    }
+
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        let entry = self.entries.nth_back(n)?;
+        Some(self.decode(entry))
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn make_signature(
+        message: &[u8],
+        secretkey: u8,
+    ) -> ([u8; 64], u8, [u8; 20]) {
+        let key = libsecp256k1::SecretKey::parse(&[secretkey; 32]).unwrap();
+        let pubkey = libsecp256k1::PublicKey::from_secret_key(&key);
+        let pubkey = pubkey.serialize()[1..].try_into().unwrap();
+        let address =
+            solana_secp256k1_program::eth_address_from_pubkey(&pubkey);
+        let (signature, recovery_id) =
+            solana_secp256k1_program::sign_message(&key.serialize(), message)
+                .unwrap();
+        (signature, recovery_id, address)
+    }
+
+    #[test]
+    fn test_recover_eth_address() {
+        let (signature, recovery_id, address) = make_signature(b"foo", 1);
+        let entry = Secp256k1Entry {
+            signature: &signature,
+            recovery_id,
+            eth_address: &address,
+            message: b"foo",
+        };
+        let data = new_secp256k1_instruction_data(0, &[entry]).unwrap();
+        let entry = parse_secp256k1_data(&data, 0).unwrap().next();
+        let entry = entry.unwrap().unwrap();
+
+        let hash = solana_program::keccak::hash(entry.message).to_bytes();
+        let pubkey = libsecp256k1::recover(
+            &libsecp256k1::Message::parse(&hash),
+            &libsecp256k1::Signature::parse_standard(entry.signature).unwrap(),
+            &libsecp256k1::RecoveryId::parse(entry.recovery_id).unwrap(),
+        )
+        .unwrap();
+        let pubkey = pubkey.serialize()[1..].try_into().unwrap();
+        let got = solana_secp256k1_program::eth_address_from_pubkey(&pubkey);
+        assert_eq!(entry.eth_address, &got);
+    }
+
+    #[test]
+    fn test_secp256k1() {
+        let (sig1, rec1, addr1) = make_signature(b"foo", 1);
+        let (sig2, rec2, addr2) = make_signature(b"bar", 2);
+        let (sig3, rec3, _) = make_signature(b"foo", 2);
+        let entries = [
+            Secp256k1Entry {
+                signature: &sig1,
+                recovery_id: rec1,
+                eth_address: &addr1,
+                message: b"foo",
+            },
+            Secp256k1Entry {
+                signature: &sig2,
+                recovery_id: rec2,
+                eth_address: &addr2,
+                message: b"bar",
+            },
+            Secp256k1Entry {
+                signature: &sig3,
+                recovery_id: rec3,
+                eth_address: &addr2,
+                message: b"foo",
+            },
+        ];
+
+        for index in [0, 1] {
+            let instruction =
+                new_secp256k1_instruction(index, &entries).unwrap();
+            assert_eq!(crate::SECP256K1_PROGRAM_ID, instruction.program_id);
+            let data = instruction.data.as_slice();
+            assert_eq!(1 + 3 * 11 + 2 * 20 + 3 * 65 + 2 * 3, data.len());
+
+            // Verify with the native program.
+            let datas: &[&[u8]] =
+                if index == 0 { &[data] } else { &[b"unrelated", data] };
+            #[allow(deprecated)]
+            solana_secp256k1_program::verify(data, datas, &Default::default())
+                .unwrap();
+
+            let got = parse_secp256k1_data(data, index)
+                .unwrap()
+                .collect::<Result<Vec<_>, _>>();
+            assert_eq!(Ok(entries.to_vec()), got);
+
+            let mut iter = parse_secp256k1_data(data, index ^ 1).unwrap();
+            assert_eq!(Some(Err(Error::UnsupportedFeature)), iter.next());
+        }
+
+        assert_eq!(Err(BadData), parse_secp256k1_data(&[], 0).map(|_| ()));
+        assert_eq!(Err(BadData), parse_secp256k1_data(&[1, 0], 0).map(|_| ()));
+    }
+
+    #[test]
+    fn test_secp_offsets_le_bytes() {
+        let offsets = SecpSignatureOffsets {
+            signature_offset: 0x0201,
+            signature_instruction_index: 3,
+            eth_address_offset: 0x0504,
+            eth_address_instruction_index: 6,
+            message_offset: 0x0807,
+            message_size: 0x0a09,
+            message_instruction_index: 11,
+        };
+        let bytes = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11];
+        assert_eq!(bytes, offsets.to_le_bytes());
+        assert_eq!(offsets, SecpSignatureOffsets::from_le_bytes(&bytes));
+    }
+}
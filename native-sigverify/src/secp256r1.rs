@@ -0,0 +1,276 @@
+//! Support for the Secp256r1 native program.
+//!
+//! Secp256r1 program uses the same instruction data layout as Ed25519 program
+//! (see [`SignatureOffsets`]) except that public keys are 33-byte compressed
+//! points rather than 32-byte Ed25519 keys.  The program also limits number of
+//! signatures in a single instruction to [`MAX_SECP256R1_SIGNATURES`].
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use solana_program::instruction::Instruction;
+
+use crate::{
+    checked_len, decode_parts, split_offsets, BadData, BuildError, Error,
+    SignatureOffsets, OFF_SIZE,
+};
+
+/// Maximum number of signatures Secp256r1 program verifies in a single
+/// instruction.
+pub const MAX_SECP256R1_SIGNATURES: usize = 8;
+
+/// A signature verified by the Secp256r1 native program.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Secp256r1Entry<'a> {
+    pub signature: &'a [u8; 64],
+    /// Compressed public key.
+    pub pubkey: &'a [u8; 33],
+    pub message: &'a [u8],
+}
+
+
+/// Creates an instruction calling the Secp256r1 native program.
+///
+/// See [`new_secp256r1_instruction_data`] for details.
+pub fn new_secp256r1_instruction(
+    entries: &[Secp256r1Entry],
+) -> Result<Instruction, BuildError> {
+    let data = new_secp256r1_instruction_data(entries)?;
+    Ok(Instruction {
+        program_id: crate::SECP256R1_PROGRAM_ID,
+        accounts: Vec::new(),
+        data,
+    })
+}
+
+/// Creates instruction data for a call of the Secp256r1 native program.
+///
+/// Returns [`BuildError::TooManyEntries`] if there are more than
+/// [`MAX_SECP256R1_SIGNATURES`] entries.  Other errors are the same as for
+/// [`new_instruction_data`](crate::new_instruction_data).  Public keys and
+/// messages used by multiple signatures are included in the data only once.
+pub fn new_secp256r1_instruction_data(
+    entries: &[Secp256r1Entry],
+) -> Result<Vec<u8>, BuildError> {
+    if entries.len() > MAX_SECP256R1_SIGNATURES {
+        return Err(BuildError::TooManyEntries(entries.len()));
+    }
+    for (index, entry) in entries.iter().enumerate() {
+        let len = entry.message.len();
+        if u16::try_from(len).is_err() {
+            return Err(BuildError::MessageTooLong { index, len });
+        }
+    }
+
+    fn append(dst: &mut Vec<u8>, bytes: &[u8]) -> u16 {
+        let offset = dst.len() as u16;
+        dst.extend_from_slice(bytes);
+        offset
+    }
+
+    // The structure of the instruction data is the same as for Ed25519
+    // program; see write_instruction_data.
+    let mut data = vec![0; 2 + entries.len() * OFF_SIZE];
+    data[0] = entries.len() as u8;
+    let mut offsets = Vec::<SignatureOffsets>::with_capacity(entries.len());
+    for (idx, entry) in entries.iter().enumerate() {
+        let earlier = &entries[..idx];
+
+        let message_offset =
+            match earlier.iter().position(|ent| ent.message == entry.message) {
+                Some(pos) => offsets[pos].message_offset,
+                None => append(&mut data, entry.message),
+            };
+
+        let signature_offset = append(&mut data, entry.signature);
+
+        let pubkey_offset =
+            match earlier.iter().position(|ent| ent.pubkey == entry.pubkey) {
+                Some(pos) => offsets[pos].pubkey_offset,
+                None => append(&mut data, entry.pubkey),
+            };
+
+        offsets.push(SignatureOffsets::inline(
+            signature_offset,
+            pubkey_offset,
+            message_offset,
+            entry.message.len() as u16,
+        ));
+    }
+    // All offsets are smaller than length of the data so if it fits in 16
+    // bits, none of them has been truncated.
+    checked_len(data.len())?;
+
+    for (dst, offsets) in data[2..].chunks_exact_mut(OFF_SIZE).zip(offsets) {
+        dst.copy_from_slice(&offsets.to_le_bytes());
+    }
+    Ok(data)
+}
+
+
+/// Creates a new iterator over signatures in given Secp256r1 native program
+/// instruction data.
+///
+/// This is like [`parse_data`](crate::parse_data) except that it reads 33-byte
+/// compressed public keys.  Entries which reference data in other
+/// instructions are reported as [`Error::UnsupportedFeature`] errors.
+///
+/// Returns [`BadData`] if the data is malformed.
+pub fn parse_secp256r1_data(data: &[u8]) -> Result<Secp256r1Iter<'_>, BadData> {
+    let entries = split_offsets(data)?;
+    Ok(Secp256r1Iter { entries: entries.iter(), data })
+}
+
+/// Iterator over signatures present in Secp256r1 native program instruction
+/// data.
+///
+/// See [`parse_secp256r1_data`].
+#[derive(Clone, Debug)]
+pub struct Secp256r1Iter<'a> {
+    entries: core::slice::Iter<'a, [u8; OFF_SIZE]>,
+    data: &'a [u8],
+}
+
+fn decode_entry<'a>(
+    data: &'a [u8],
+    entry: &'a [u8; OFF_SIZE],
+) -> Result<Secp256r1Entry<'a>, Error> {
+    let (signature, pubkey, message) = decode_parts(data, entry)?;
+    Ok(Secp256r1Entry { signature, pubkey, message })
+}
+
+impl<'a> core::iter::Iterator for Secp256r1Iter<'a> {
+    type Item = Result<Secp256r1Entry<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.entries.next()?;
+        Some(decode_entry(self.data, entry))
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        let entry = self.entries.nth(n)?;
+        Some(decode_entry(self.data, entry))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) { self.entries.size_hint() }
+    fn count(self) -> usize { self.entries.count() }
+}
+
+impl core::iter::ExactSizeIterator for Secp256r1Iter<'_> {
+    fn len(&self) -> usize { self.entries.len() }
+}
+
+impl core::iter::DoubleEndedIterator for Secp256r1Iter<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let entry = self.entries.next_back()?;
+        Some(decode_entry(self.data, entry))
+    }
+
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        let entry = self.entries.nth_back(n)?;
+        Some(decode_entry(self.data, entry))
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use openssl::bn::BigNumContext;
+    use openssl::ec::{EcGroup, EcKey, PointConversionForm};
+    use openssl::nid::Nid;
+    use solana_secp256r1_program as r1;
+
+    use super::*;
+
+    fn make_signature(
+        message: &[u8],
+        key: &EcKey<openssl::pkey::Private>,
+    ) -> ([u8; 64], [u8; 33]) {
+        let der = key.private_key_to_der().unwrap();
+        let signature = r1::sign_message(message, &der).unwrap();
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let mut ctx = BigNumContext::new().unwrap();
+        let pubkey = key
+            .public_key()
+            .to_bytes(&group, PointConversionForm::COMPRESSED, &mut ctx)
+            .unwrap();
+        (signature, pubkey.try_into().unwrap())
+    }
+
+    fn new_key() -> EcKey<openssl::pkey::Private> {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        EcKey::generate(&group).unwrap()
+    }
+
+    /// Verifies instruction data with the Secp256r1 native program.
+    fn verify(data: &[u8]) {
+        // The program requires data to be aligned to two bytes.
+        let mut buf = vec![0u16; data.len().div_ceil(2)];
+        let aligned =
+            &mut bytemuck::cast_slice_mut::<u16, u8>(&mut buf)[..data.len()];
+        aligned.copy_from_slice(data);
+        #[allow(deprecated)]
+        r1::verify(aligned, &[aligned], &Default::default()).unwrap();
+    }
+
+    #[test]
+    fn test_parse_sdk_instruction() {
+        let (signature, pubkey) = make_signature(b"foo", &new_key());
+        let instruction = r1::new_secp256r1_instruction_with_signature(
+            b"foo", &signature, &pubkey,
+        );
+        assert_eq!(crate::SECP256R1_PROGRAM_ID, instruction.program_id);
+        verify(&instruction.data);
+
+        let got = parse_secp256r1_data(&instruction.data)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>();
+        let want = Secp256r1Entry {
+            signature: &signature,
+            pubkey: &pubkey,
+            message: b"foo",
+        };
+        assert_eq!(Ok(vec![want]), got);
+    }
+
+    #[test]
+    fn test_new_instruction() {
+        let (key1, key2) = (new_key(), new_key());
+        let (sig1, pubkey1) = make_signature(b"foo", &key1);
+        let (sig2, pubkey2) = make_signature(b"bar", &key2);
+        let (sig3, _) = make_signature(b"bar", &key1);
+        let entries = [
+            Secp256r1Entry {
+                signature: &sig1,
+                pubkey: &pubkey1,
+                message: b"foo",
+            },
+            Secp256r1Entry {
+                signature: &sig2,
+                pubkey: &pubkey2,
+                message: b"bar",
+            },
+            Secp256r1Entry {
+                signature: &sig3,
+                pubkey: &pubkey1,
+                message: b"bar",
+            },
+        ];
+
+        let instruction = new_secp256r1_instruction(&entries).unwrap();
+        assert_eq!(crate::SECP256R1_PROGRAM_ID, instruction.program_id);
+        let data = instruction.data.as_slice();
+        assert_eq!(2 + 3 * 14 + 3 * 64 + 2 * 33 + 2 * 3, data.len());
+        verify(data);
+
+        let got =
+            parse_secp256r1_data(data).unwrap().collect::<Result<Vec<_>, _>>();
+        assert_eq!(Ok(entries.to_vec()), got);
+
+        let entries = [entries[0]; MAX_SECP256R1_SIGNATURES + 1];
+        assert_eq!(
+            Err(BuildError::TooManyEntries(MAX_SECP256R1_SIGNATURES + 1)),
+            new_secp256r1_instruction_data(&entries)
+        );
+    }
+}
@@ -0,0 +1,239 @@
+//! Serde support for [`Entry`] and [`OwnedEntry`].
+//!
+//! Both types are serialised as a structure with `signature`, `pubkey` and
+//! `message` fields.  In human-readable formats (such as JSON) signature and
+//! public key are encoded as base58 strings.  In other formats they are
+//! encoded as bytes.  Message is always encoded as bytes.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use serde::de::{self, Deserializer, SeqAccess, Visitor};
+use serde::ser::{SerializeStruct, Serializer};
+
+use crate::{Entry, OwnedEntry};
+
+const FIELDS: &[&str] = &["signature", "pubkey", "message"];
+
+impl serde::Serialize for Entry<'_> {
+    fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        let mut st = ser.serialize_struct("Entry", 3)?;
+        st.serialize_field("signature", &Key(self.signature))?;
+        st.serialize_field("pubkey", &Key(self.pubkey))?;
+        st.serialize_field("message", &Bytes(self.message))?;
+        st.end()
+    }
+}
+
+impl serde::Serialize for OwnedEntry {
+    fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        self.as_entry().serialize(ser)
+    }
+}
+
+/// Deserialises an [`OwnedEntry`].
+///
+/// Note that there’s no `Deserialize` implementation for [`Entry`] since
+/// base58-encoded fields cannot be borrowed from the input.  Deserialise
+/// `OwnedEntry` and use [`OwnedEntry::as_entry`] instead.
+impl<'de> serde::Deserialize<'de> for OwnedEntry {
+    fn deserialize<D: Deserializer<'de>>(de: D) -> Result<Self, D::Error> {
+        let human_readable = de.is_human_readable();
+        de.deserialize_struct("Entry", FIELDS, EntryVisitor { human_readable })
+    }
+}
+
+
+/// Serialises a fixed-size array as base58 string or bytes.
+struct Key<'a, const N: usize>(&'a [u8; N]);
+
+impl<const N: usize> serde::Serialize for Key<'_, N> {
+    fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        if ser.is_human_readable() {
+            ser.serialize_str(&bs58::encode(self.0).into_string())
+        } else {
+            ser.serialize_bytes(self.0)
+        }
+    }
+}
+
+/// Serialises a slice as bytes.
+struct Bytes<'a>(&'a [u8]);
+
+impl serde::Serialize for Bytes<'_> {
+    fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        ser.serialize_bytes(self.0)
+    }
+}
+
+
+struct EntryVisitor {
+    human_readable: bool,
+}
+
+impl<'de> Visitor<'de> for EntryVisitor {
+    type Value = OwnedEntry;
+
+    fn expecting(&self, fmt: &mut core::fmt::Formatter) -> core::fmt::Result {
+        fmt.write_str("a signature entry")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(
+        self,
+        mut seq: A,
+    ) -> Result<Self::Value, A::Error> {
+        let hr = self.human_readable;
+        let mut next = |idx| seq.next_element_seed(FieldSeed(idx, hr));
+        let missing = |idx| de::Error::invalid_length(idx, &"3 fields");
+        let signature = next(0)?.ok_or_else(|| missing(0))?;
+        let pubkey = next(1)?.ok_or_else(|| missing(1))?;
+        let message = next(2)?.ok_or_else(|| missing(2))?;
+        Ok(OwnedEntry {
+            signature: signature.try_into().unwrap(),
+            pubkey: pubkey.try_into().unwrap(),
+            message,
+        })
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(
+        self,
+        mut map: A,
+    ) -> Result<Self::Value, A::Error> {
+        let mut fields: [Option<Vec<u8>>; 3] = Default::default();
+        while let Some(key) = map.next_key::<String>()? {
+            let idx = FIELDS
+                .iter()
+                .position(|field| *field == key)
+                .ok_or_else(|| de::Error::unknown_field(&key, FIELDS))?;
+            if fields[idx].is_some() {
+                return Err(de::Error::duplicate_field(FIELDS[idx]));
+            }
+            fields[idx] =
+                Some(map.next_value_seed(FieldSeed(idx, self.human_readable))?);
+        }
+        let [signature, pubkey, message] = fields;
+        let missing = |idx| de::Error::missing_field(FIELDS[idx]);
+        Ok(OwnedEntry {
+            signature: signature.ok_or_else(|| missing(0))?.try_into().unwrap(),
+            pubkey: pubkey.ok_or_else(|| missing(1))?.try_into().unwrap(),
+            message: message.ok_or_else(|| missing(2))?,
+        })
+    }
+}
+
+/// Deserialises field with given index.
+///
+/// Signature and public key are deserialised from base58 strings (if
+/// `human_readable` is set) or bytes and their length is checked.
+struct FieldSeed(usize, bool);
+
+impl<'de> de::DeserializeSeed<'de> for FieldSeed {
+    type Value = Vec<u8>;
+
+    fn deserialize<D: Deserializer<'de>>(
+        self,
+        de: D,
+    ) -> Result<Self::Value, D::Error> {
+        let len = match self.0 {
+            0 => Some(64),
+            1 => Some(32),
+            _ => None,
+        };
+        let bytes = match (len, self.1) {
+            (Some(_), true) => de.deserialize_str(Base58Visitor)?,
+            _ => de.deserialize_bytes(BytesVisitor)?,
+        };
+        match len {
+            Some(len) if bytes.len() != len => {
+                Err(de::Error::invalid_length(bytes.len(), &FIELDS[self.0]))
+            }
+            _ => Ok(bytes),
+        }
+    }
+}
+
+struct Base58Visitor;
+
+impl Visitor<'_> for Base58Visitor {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, fmt: &mut core::fmt::Formatter) -> core::fmt::Result {
+        fmt.write_str("a base58 string")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+        bs58::decode(value).into_vec().map_err(E::custom)
+    }
+}
+
+struct BytesVisitor;
+
+impl<'de> Visitor<'de> for BytesVisitor {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, fmt: &mut core::fmt::Formatter) -> core::fmt::Result {
+        fmt.write_str("bytes")
+    }
+
+    fn visit_bytes<E: de::Error>(self, value: &[u8]) -> Result<Self::Value, E> {
+        Ok(value.to_vec())
+    }
+
+    fn visit_byte_buf<E: de::Error>(
+        self,
+        value: Vec<u8>,
+    ) -> Result<Self::Value, E> {
+        Ok(value)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(
+        self,
+        mut seq: A,
+    ) -> Result<Self::Value, A::Error> {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+        Ok(bytes)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_json() {
+        let entry = OwnedEntry {
+            signature: [1; 64],
+            pubkey: [2; 32],
+            message: b"foo\0bar".to_vec(),
+        };
+        let json = serde_json::to_string(&entry).unwrap();
+        let want = format!(
+            r#"{{"signature":"{}","pubkey":"{}","message":{}}}"#,
+            bs58::encode([1; 64]).into_string(),
+            bs58::encode([2; 32]).into_string(),
+            "[102,111,111,0,98,97,114]",
+        );
+        assert_eq!(want, json);
+        assert_eq!(
+            Ok(json.clone()),
+            serde_json::to_string(&entry.as_entry()).map_err(|_| ())
+        );
+        assert_eq!(entry, serde_json::from_str::<OwnedEntry>(&json).unwrap());
+
+        // Wrong length of the public key.
+        let json = format!(
+            r#"{{"signature":"{}","pubkey":"{}","message":[]}}"#,
+            bs58::encode([1; 64]).into_string(),
+            bs58::encode([2; 31]).into_string(),
+        );
+        assert!(serde_json::from_str::<OwnedEntry>(&json).is_err());
+
+        // Missing field.
+        let json = r#"{"signature":"1","pubkey":"1"}"#;
+        assert!(serde_json::from_str::<OwnedEntry>(json).is_err());
+    }
+}
@@ -47,7 +47,8 @@ pub(crate) fn as_chunks<const N: usize, T>(slice: &[T]) -> (&[[T; N]], &[T]) {
 
     // SAFETY: We cast a slice of `len * N` elements into a slice of `len` many
     // `N` elements chunks.
-    let head = unsafe { std::slice::from_raw_parts(head.as_ptr().cast(), len) };
+    let head =
+        unsafe { core::slice::from_raw_parts(head.as_ptr().cast(), len) };
     (head, tail)
 }
 
@@ -63,7 +64,7 @@ pub(crate) fn as_chunks_mut<const N: usize, T>(
     // SAFETY: We cast a slice of `len * N` elements into a slice of `len` many
     // `N` elements chunks.
     let head = unsafe {
-        std::slice::from_raw_parts_mut(head.as_mut_ptr().cast(), len)
+        core::slice::from_raw_parts_mut(head.as_mut_ptr().cast(), len)
     };
     (head, tail)
 }
@@ -122,3 +123,18 @@ pub(crate) fn write_slice(dst: &mut [MaybeUninit<u8>], src: &[u8]) {
     let src: &[MaybeUninit<u8>] = unsafe { core::mem::transmute(src) };
     dst.copy_from_slice(src);
 }
+
+/// Views an initialised slice as a slice of possibly uninitialised elements.
+///
+/// This allows functions writing into `&mut [MaybeUninit<u8>]` to be used with
+/// initialised buffers.
+///
+/// # Safety
+///
+/// Caller must not write uninitialised values into the returned slice (e.g.
+/// by assigning `MaybeUninit::uninit()` to its elements).
+pub(crate) unsafe fn as_uninit_mut(slice: &mut [u8]) -> &mut [MaybeUninit<u8>] {
+    // SAFETY: &mut [T] and &mut [MaybeUninit<T>] have the same layout.  Caller
+    // promises to keep the slice initialised.
+    unsafe { &mut *(slice as *mut [u8] as *mut [MaybeUninit<u8>]) }
+}
@@ -0,0 +1,82 @@
+//! WebAssembly bindings for building and parsing instruction data.
+//!
+//! Entries are represented in JavaScript as objects with `signature`, `pubkey`
+//! and `message` fields holding `Uint8Array`s.  Errors are thrown as `Error`
+//! exceptions whose message is `BadData` or `UnsupportedFeature` (see
+//! [`crate::Error`]) when parsing and the [`crate::BuildError`] debug
+//! representation when building.
+
+use alloc::format;
+use alloc::vec::Vec;
+
+use js_sys::{Array, Object, Reflect, Uint8Array};
+use wasm_bindgen::prelude::*;
+
+use crate::{Entry, Error, OwnedEntry};
+
+/// Creates instruction data for a call of a native signature verification
+/// program.
+///
+/// `entries` is an array of `{signature, pubkey, message}` objects.  Returns
+/// the instruction data as `Uint8Array`.  See [`crate::new_instruction_data`].
+#[wasm_bindgen(js_name = newInstructionData)]
+pub fn new_instruction_data(entries: Array) -> Result<Vec<u8>, JsError> {
+    let entries = entries
+        .iter()
+        .map(|entry| entry_from_js(&entry))
+        .collect::<Result<Vec<_>, _>>()?;
+    crate::new_instruction_data(&entries)
+        .map_err(|err| JsError::new(&format!("{err:?}")))
+}
+
+/// Parses instruction data of a native signature verification program call.
+///
+/// Returns an array of `{signature, pubkey, message}` objects.  See
+/// [`crate::parse_data`].  Unlike that function, fails on entries which use
+/// unsupported features rather than skipping them.
+#[wasm_bindgen(js_name = parseData)]
+pub fn parse_data(data: &[u8]) -> Result<Array, JsError> {
+    let iter = crate::parse_data(data).map_err(|_| error(Error::BadData))?;
+    iter.map(|entry| entry.map_err(error).and_then(entry_to_js))
+        .collect::<Result<Array, _>>()
+}
+
+/// Converts an `{signature, pubkey, message}` object into an entry.
+fn entry_from_js(value: &JsValue) -> Result<OwnedEntry, JsError> {
+    let field = |name: &str| {
+        Reflect::get(value, &JsValue::from_str(name))
+            .ok()
+            .and_then(|value| value.dyn_into::<Uint8Array>().ok())
+            .map(|array| array.to_vec())
+            .ok_or_else(|| error(Error::BadData))
+    };
+    let bad = |_| error(Error::BadData);
+    Ok(OwnedEntry {
+        signature: field("signature")?.try_into().map_err(bad)?,
+        pubkey: field("pubkey")?.try_into().map_err(bad)?,
+        message: field("message")?,
+    })
+}
+
+/// Converts an entry into an `{signature, pubkey, message}` object.
+fn entry_to_js(entry: Entry) -> Result<JsValue, JsError> {
+    let object = Object::new();
+    for (name, bytes) in [
+        ("signature", &entry.signature[..]),
+        ("pubkey", &entry.pubkey[..]),
+        ("message", entry.message),
+    ] {
+        let bytes = Uint8Array::from(bytes);
+        Reflect::set(&object, &JsValue::from_str(name), &bytes)
+            .map_err(|_| JsError::new("failed to set field"))?;
+    }
+    Ok(object.into())
+}
+
+/// Converts parsing error into a JavaScript exception.
+fn error(err: Error) -> JsError {
+    JsError::new(match err {
+        Error::BadData => "BadData",
+        Error::UnsupportedFeature => "UnsupportedFeature",
+    })
+}
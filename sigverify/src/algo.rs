@@ -10,11 +10,99 @@ use crate::SigHash;
 pub struct Magic(core::num::NonZeroU32);
 
 impl Magic {
-    pub(crate) fn to_bytes(self) -> [u8; 4] { self.0.get().to_le_bytes() }
+    /// Returns the 4-byte token as used when constructing [`SigHash`].
+    pub fn to_bytes(self) -> [u8; 4] { self.0.get().to_le_bytes() }
+
+    /// Constructs magic from its 4-byte token; see [`Self::to_bytes`].
+    ///
+    /// Returns `None` if all bytes are zero.  Note that the function doesn’t
+    /// check whether the magic corresponds to any known algorithm.
+    pub fn from_bytes(bytes: [u8; 4]) -> Option<Self> {
+        NonZeroU32::new(u32::from_le_bytes(bytes)).map(Self)
+    }
+
+    /// Constructs magic for a custom signature algorithm.
+    ///
+    /// This allows implementing [`Algorithm`] for signature verification
+    /// programs other than the ones supported by this crate.  Returns `None`
+    /// if `magic` is zero or is the same as magic of one of the built-in
+    /// algorithms.  Users should make sure that custom magics they use don’t
+    /// collide with each other.
+    ///
+    /// See [`Algorithm`] for an example.
+    pub const fn new_custom(magic: u32) -> Option<Self> {
+        let magic = match NonZeroU32::new(magic) {
+            Some(magic) => magic,
+            None => return None,
+        };
+        let mut idx = 0;
+        while idx < BUILTIN_MAGICS.len() {
+            if BUILTIN_MAGICS[idx].0.get() == magic.get() {
+                return None;
+            }
+            idx += 1;
+        }
+        Some(Self(magic))
+    }
+
+    /// Returns the magic as an integer.
+    ///
+    /// The integer is the 4-byte token interpreted as little-endian number.
+    pub fn get(&self) -> u32 { self.0.get() }
+}
+
+#[test]
+fn test_magic_bytes() {
+    let magic = Ed25519::MAGIC;
+    assert_eq!(*b"ed\xff\x13", magic.to_bytes());
+    assert_eq!(u32::from_le_bytes(*b"ed\xff\x13"), magic.get());
+    assert_eq!(Some(magic), Magic::from_bytes(magic.to_bytes()));
+    assert_eq!(None, Magic::from_bytes([0; 4]));
+}
+
+#[test]
+fn test_magic_new_custom() {
+    let magic = u32::from_le_bytes(*b"cust");
+    assert_eq!(Some(magic), Magic::new_custom(magic).map(|magic| magic.get()));
+    assert_eq!(None, Magic::new_custom(0));
+    assert_eq!(None, Magic::new_custom(Ed25519::MAGIC.get()));
+    assert_eq!(None, Magic::new_custom(Secp256k1::MAGIC.get()));
+    assert_eq!(None, Magic::new_custom(Secp256r1::MAGIC.get()));
 }
 
 
 /// Specifies a signature algorithm.
+///
+/// The crate implements this trait for [`Ed25519`], [`Secp256k1`] and
+/// [`Secp256r1`] algorithms.  It’s possible to implement it for other
+/// signature verification programs which use the same calling convention as
+/// the native programs.  Note however that the solana-sigverify program
+/// aggregates only signatures of the built-in algorithms.
+///
+/// # Example
+///
+/// ```
+/// use solana_program::pubkey::Pubkey;
+/// use solana_sigverify::algo::{Algorithm, Magic};
+///
+/// struct Custom;
+///
+/// impl Custom {
+///     const MAGIC: Magic =
+///         match Magic::new_custom(u32::from_le_bytes(*b"cust")) {
+///             Some(magic) => magic,
+///             None => panic!(),
+///         };
+/// }
+///
+/// impl Algorithm for Custom {
+///     fn magic() -> Magic { Self::MAGIC }
+///     fn program_id() -> Pubkey { Pubkey::new_from_array([42; 32]) }
+/// }
+///
+/// let instruction = Custom::new_instruction(&[]).unwrap();
+/// assert_eq!(Custom::program_id(), instruction.program_id);
+/// ```
 pub trait Algorithm {
     /// Magic used for this algorithm when constructing [`SigHash`].
     fn magic() -> Magic;
@@ -26,18 +114,39 @@ pub trait Algorithm {
     /// verifying signatures of this type.
     fn check_id(id: Pubkey) -> bool { id == Self::program_id() }
 
+    /// Returns hash of concatenation of all the `parts`.
+    ///
+    /// This is the hash function used by [`Self::sighash`] and
+    /// [`Self::sighash_entry`].  It defaults to SHA-256 (see
+    /// [`SolanaHashBackend`](crate::SolanaHashBackend)).  Built-in algorithms
+    /// use hash function given by their `HASH_FUNCTION` constant, i.e.
+    /// Keccak-256 for [`Secp256k1`] (to align with EVM conventions) and
+    /// SHA-256 for the others.  [`SigHash::for_algorithm`] uses this function
+    /// while [`SigHash::new`], which knows only the magic, always uses
+    /// SHA-256.
+    ///
+    /// Custom algorithms may override it.  **Note** however that the sigverify
+    /// program aggregates only signatures of the built-in algorithms so
+    /// [`SigHash`]es of custom algorithms will never be found in signatures
+    /// accounts it fills.
+    fn hashv(parts: &[&[u8]]) -> [u8; 32] { HashFunction::Sha256.hashv(parts) }
+
     /// Calculates a [`SigHash`] for signature of this algorithm.
+    ///
+    /// The hash is computed with [`Self::hashv`].
     fn sighash(
         pubkey: &[u8; 32],
         signature: &[u8; 64],
         message: &[u8],
     ) -> SigHash {
-        SigHash::new(Self::magic(), pubkey, signature, message)
+        SigHash::for_algorithm::<Self>(pubkey, signature, message)
     }
 
     /// Calculates a [`SigHash`] for signature of this algorithm.
+    ///
+    /// The hash is computed with [`Self::hashv`].
     fn sighash_entry(entry: solana_native_sigverify::Entry) -> SigHash {
-        SigHash::from_entry(Self::magic(), entry)
+        Self::sighash(entry.pubkey, entry.signature, entry.message)
     }
 
     /// Creates an instruction calling a native signature verification program.
@@ -45,14 +154,134 @@ pub trait Algorithm {
     /// This is a wrapper around [`solana_native_sigverify::new_instruction`].
     fn new_instruction(
         entries: &[solana_native_sigverify::Entry],
-    ) -> Option<solana_program::instruction::Instruction> {
+    ) -> Result<
+        solana_program::instruction::Instruction,
+        solana_native_sigverify::BuildError,
+    > {
         solana_native_sigverify::new_instruction(Self::program_id(), entries)
     }
+
+    /// Creates an instruction calling a native signature verification program
+    /// together with [`SigHash`]es of all the entries.
+    ///
+    /// The hashes are what the sigverify program stores in the signatures
+    /// account once it aggregates signatures verified by the instruction.
+    /// They are returned in the same order as `entries`.
+    fn new_instruction_with_sighashes(
+        entries: &[solana_native_sigverify::Entry],
+    ) -> Result<
+        (solana_program::instruction::Instruction, Vec<SigHash>),
+        solana_native_sigverify::BuildError,
+    > {
+        let instruction = Self::new_instruction(entries)?;
+        let sighashes =
+            entries.iter().map(|entry| Self::sighash_entry(*entry)).collect();
+        Ok((instruction, sighashes))
+    }
+}
+
+
+/// Hash function used to calculate [`SigHash`]es of an algorithm.
+///
+/// See [`Algorithm::hashv`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HashFunction {
+    /// SHA-256.  Used by all built-in algorithms other than [`Secp256k1`].
+    Sha256,
+    /// Keccak-256.  Used by [`Secp256k1`].
+    Keccak256,
+}
+
+impl HashFunction {
+    /// Returns hash of concatenation of all the `parts`.
+    ///
+    /// On-chain this uses `sol_sha256` or `sol_keccak256` syscall.
+    pub fn hashv(self, parts: &[&[u8]]) -> [u8; 32] {
+        match self {
+            Self::Sha256 => {
+                <crate::SolanaHashBackend as crate::HashBackend>::hashv(parts)
+            }
+            Self::Keccak256 => solana_keccak_hasher::hashv(parts).to_bytes(),
+        }
+    }
+}
+
+/// [`HashBackend`](crate::HashBackend) using hash function of given
+/// algorithm; see [`Algorithm::hashv`].
+pub(crate) struct Hasher<A: ?Sized>(core::marker::PhantomData<A>);
+
+impl<A: Algorithm + ?Sized> crate::HashBackend for Hasher<A> {
+    #[inline]
+    fn hashv(parts: &[&[u8]]) -> [u8; 32] { A::hashv(parts) }
+}
+
+#[test]
+fn test_custom_hash() {
+    struct Keccak;
+
+    impl Algorithm for Keccak {
+        fn magic() -> Magic {
+            Magic::new_custom(u32::from_le_bytes(*b"kecc")).unwrap()
+        }
+        fn program_id() -> Pubkey { Pubkey::new_from_array([42; 32]) }
+        fn hashv(parts: &[&[u8]]) -> [u8; 32] {
+            HashFunction::Keccak256.hashv(parts)
+        }
+    }
+
+    let magic = Keccak::magic().to_bytes();
+    let want =
+        solana_keccak_hasher::hashv(&[&magic[..], &[1; 32], &[2; 64], b"foo"]);
+    let got = Keccak::sighash(&[1; 32], &[2; 64], b"foo");
+    assert_eq!(SigHash::from(want.to_bytes()), got);
+    assert_eq!(
+        got,
+        SigHash::for_algorithm::<Keccak>(&[1; 32], &[2; 64], b"foo")
+    );
+
+    // Knowing only the magic, SigHash::new uses SHA-256.
+    let want =
+        HashFunction::Sha256.hashv(&[&magic[..], &[1; 32], &[2; 64], b"foo"]);
+    assert_eq!(
+        SigHash::from(want),
+        SigHash::new(Keccak::magic(), &[1; 32], &[2; 64], b"foo")
+    );
+
+    let entry = solana_native_sigverify::Entry {
+        signature: &[2; 64],
+        pubkey: &[1; 32],
+        message: b"foo",
+    };
+    assert_eq!(got, Keccak::sighash_entry(entry));
+}
+
+#[test]
+fn test_secp256k1_keccak() {
+    let magic = Secp256k1::MAGIC.to_bytes();
+    let want =
+        solana_keccak_hasher::hashv(&[&magic[..], &[1; 32], &[2; 64], b"foo"]);
+    let want = SigHash::from(want.to_bytes());
+    assert_eq!(want, Secp256k1::sighash(&[1; 32], &[2; 64], b"foo"));
+    assert_eq!(
+        want,
+        SigHash::for_algorithm::<Secp256k1>(&[1; 32], &[2; 64], b"foo")
+    );
+    assert_eq!(want, Algo::Secp256k1.sighash(&[1; 32], &[2; 64], b"foo"));
+
+    // SigHash::new keeps using SHA-256 so existing hashes don’t change.
+    let sha =
+        HashFunction::Sha256.hashv(&[&magic[..], &[1; 32], &[2; 64], b"foo"]);
+    assert_eq!(
+        SigHash::from(sha),
+        SigHash::new(Secp256k1::MAGIC, &[1; 32], &[2; 64], b"foo")
+    );
+    assert_eq!(HashFunction::Keccak256, Algo::Secp256k1.hash_function());
+    assert_eq!(HashFunction::Sha256, Algo::Ed25519.hash_function());
 }
 
 
 macro_rules! define {
-    ($($name:ident, $magic:expr, $id:ident;)*) => {
+    ($($name:ident, $magic:expr, $id:ident, $pubkey_len:expr, $hash:ident;)*) => {
         $(
             #[doc = concat!("Specification for the ", stringify!($name), " algorithm.")]
             pub struct $name;
@@ -66,14 +295,31 @@ macro_rules! define {
 
                 /// Address of the native program verifying signatures of this type.
                 pub const ID: Pubkey = solana_native_sigverify::$id;
+
+                /// Length of the public key as stored in the native program
+                /// instruction data.
+                pub const PUBKEY_LEN: usize = $pubkey_len;
+
+                /// Length of the signature as stored in the native program
+                /// instruction data.
+                pub const SIGNATURE_LEN: usize = 64;
+
+                /// Hash function used when constructing [`SigHash`].
+                pub const HASH_FUNCTION: HashFunction = HashFunction::$hash;
             }
 
             impl Algorithm for $name {
                 fn magic() -> Magic { Self::MAGIC }
                 fn program_id() -> Pubkey { Self::ID }
+                fn hashv(parts: &[&[u8]]) -> [u8; 32] {
+                    Self::HASH_FUNCTION.hashv(parts)
+                }
             }
         )*
 
+        /// Magics of all the built-in algorithms.
+        const BUILTIN_MAGICS: &[Magic] = &[$($name::MAGIC),*];
+
         #[test]
         fn test_unique_magic() {
             let magic = [
@@ -86,11 +332,37 @@ macro_rules! define {
             }
         }
 
+        #[test]
+        fn test_unique_sighash() {
+            // Built-in algorithms must produce different hashes for the same
+            // inputs.  Those using SHA-256 must agree with SigHash::new which
+            // is what the sigverify program stores.
+            let sighash = [$((
+                stringify!($name),
+                $name::sighash(&[1; 32], &[2; 64], b"foo"),
+                SigHash::for_algorithm::<$name>(&[1; 32], &[2; 64], b"foo"),
+                $name::HASH_FUNCTION,
+                SigHash::new($name::MAGIC, &[1; 32], &[2; 64], b"foo"),
+            ),)*];
+            for (i, this) in sighash.iter().enumerate() {
+                assert_eq!(this.2, this.1, "{}", this.0);
+                if this.3 == HashFunction::Sha256 {
+                    assert_eq!(this.4, this.1, "{}", this.0);
+                } else {
+                    assert_ne!(this.4, this.1, "{}", this.0);
+                }
+                for other in sighash[..i].iter() {
+                    assert_ne!(other.1, this.1, "{} same as {}", other.0, this.0);
+                }
+            }
+        }
+
         /// Identifies algorithm from ID of the native program verifying
         /// signatures of that algorithm.
         ///
         /// Returns a magic token used in [`SigHash`] or `None` if the algorithm
-        /// cannot be identified.
+        /// cannot be identified.  Use [`algorithm_from_id`] to get the
+        /// algorithm itself.
         ///
         /// # Example
         ///
@@ -114,16 +386,185 @@ macro_rules! define {
             $( assert_eq!(Some($name::MAGIC), from_id($name::ID)); )*
             assert_eq!(None, from_id(solana_program::system_program::ID));
         }
+
+        /// A signature algorithm selected at run time.
+        ///
+        /// This is a run-time counterpart of types implementing [`Algorithm`]
+        /// trait.  It’s useful when the algorithm is known only at run time
+        /// (e.g. from a program ID of an instruction) and monomorphising code
+        /// over all algorithms is undesired.
+        ///
+        /// # Example
+        ///
+        /// ```
+        /// use solana_sigverify::algo;
+        ///
+        /// let algo = algo::Algo::from_id(algo::Secp256k1::ID).unwrap();
+        /// assert_eq!(algo::Algo::Secp256k1, algo);
+        /// assert_eq!(algo::Secp256k1::MAGIC, algo.magic());
+        /// ```
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+        pub enum Algo {
+            $(
+                #[doc = concat!("The [`", stringify!($name), "`] algorithm.")]
+                $name,
+            )*
+        }
+
+        impl Algo {
+            /// Returns magic used for this algorithm when constructing
+            /// [`SigHash`].
+            pub fn magic(self) -> Magic {
+                match self {
+                    $( Self::$name => $name::MAGIC, )*
+                }
+            }
+
+            /// Returns address of the native program verifying signatures of
+            /// this type.
+            pub fn program_id(self) -> Pubkey {
+                match self {
+                    $( Self::$name => $name::ID, )*
+                }
+            }
+
+            /// Returns length of the public key as stored in the native program
+            /// instruction data.
+            ///
+            /// Note that for Secp256k1 this is length of an Ethereum address
+            /// since that’s what the Secp256k1 program takes instead of public
+            /// keys.
+            pub fn pubkey_len(self) -> usize {
+                match self {
+                    $( Self::$name => $name::PUBKEY_LEN, )*
+                }
+            }
+
+            /// Returns length of the signature as stored in the native program
+            /// instruction data.
+            ///
+            /// Note that in Secp256k1 program instruction data, signatures are
+            /// followed by a one-byte recovery id which isn’t included in this
+            /// length.
+            pub fn signature_len(self) -> usize {
+                match self {
+                    $( Self::$name => $name::SIGNATURE_LEN, )*
+                }
+            }
+
+            /// Returns hash function used when constructing [`SigHash`].
+            pub fn hash_function(self) -> HashFunction {
+                match self {
+                    $( Self::$name => $name::HASH_FUNCTION, )*
+                }
+            }
+
+            /// Identifies algorithm from its magic.
+            ///
+            /// Returns `None` if the magic doesn’t belong to any built-in
+            /// algorithm.
+            pub fn from_magic(magic: Magic) -> Option<Self> {
+                $(
+                    if $name::MAGIC == magic {
+                        return Some(Self::$name)
+                    }
+                )*
+                None
+            }
+
+            /// Identifies algorithm from ID of the native program verifying
+            /// signatures of that algorithm.
+            ///
+            /// Returns `None` if the algorithm cannot be identified.  See also
+            /// [`algorithm_from_id`].
+            pub fn from_id(id: Pubkey) -> Option<Self> {
+                $(
+                    if $name::ID == id {
+                        return Some(Self::$name)
+                    }
+                )*
+                None
+            }
+
+            /// Calculates a [`SigHash`] for signature of this algorithm.
+            ///
+            /// The result is the same as that of [`Algorithm::sighash`] of the
+            /// algorithm, i.e. it uses the algorithm’s hash function.
+            pub fn sighash(
+                self,
+                pubkey: &[u8; 32],
+                signature: &[u8; 64],
+                message: &[u8],
+            ) -> SigHash {
+                match self {
+                    $( Self::$name => $name::sighash(pubkey, signature, message), )*
+                }
+            }
+
+            /// Calculates a [`SigHash`] for signature of this algorithm.
+            ///
+            /// See [`Self::sighash`].
+            pub fn sighash_entry(
+                self,
+                entry: solana_native_sigverify::Entry,
+            ) -> SigHash {
+                self.sighash(entry.pubkey, entry.signature, entry.message)
+            }
+        }
+
+        /// Identifies algorithm from ID of the native program verifying
+        /// signatures of that algorithm.
+        ///
+        /// Unlike [`from_id`], returns the [`Algo`] which can be queried for
+        /// other properties of the algorithm such as lengths of public keys
+        /// and signatures.  Returns `None` if the algorithm cannot be
+        /// identified.
+        ///
+        /// # Example
+        ///
+        /// ```
+        /// use solana_sigverify::algo;
+        ///
+        /// let algo = algo::algorithm_from_id(algo::Secp256r1::ID).unwrap();
+        /// assert_eq!(33, algo.pubkey_len());
+        /// ```
+        pub fn algorithm_from_id(id: Pubkey) -> Option<Algo> { Algo::from_id(id) }
+
+        #[test]
+        fn test_algo() {
+            $(
+                let algo = Algo::$name;
+                assert_eq!(Some(algo), Algo::from_id($name::ID));
+                assert_eq!(Some(algo), algorithm_from_id($name::ID));
+                assert_eq!($name::PUBKEY_LEN, algo.pubkey_len());
+                assert_eq!($name::SIGNATURE_LEN, algo.signature_len());
+                assert_eq!($name::MAGIC, algo.magic());
+                assert_eq!($name::ID, algo.program_id());
+                assert_eq!($name::HASH_FUNCTION, algo.hash_function());
+                assert_eq!(Some(algo), Algo::from_magic($name::MAGIC));
+                assert_eq!(
+                    $name::sighash(&[1; 32], &[2; 64], b"foo"),
+                    algo.sighash(&[1; 32], &[2; 64], b"foo"),
+                );
+            )*
+            assert_eq!(None, Algo::from_id(solana_program::system_program::ID));
+        }
     }
 }
 
 define! {
-    Ed25519, b"ed\xff\x13", ED25519_PROGRAM_ID;
+    Ed25519, b"ed\xff\x13", ED25519_PROGRAM_ID, 32, Sha256;
 
     // See https://www.secg.org/sec2-v2.pdf for different sec algorithms.  The
     // magic format chosen is 's', followed by number in the algorithm mod 256
     // and then 'k#' or 'r#'.  Most of the algorithms won’t be supported by
     // Solana but this scheme allows for all of them to be used.
-    Secp256k1, b"s\x00k1", SECP256K1_PROGRAM_ID;
-    Secp256r1, b"s\x00r1", SECP256R1_PROGRAM_ID;
+    //
+    // Secp256k1 program takes 20-byte Ethereum addresses rather than public
+    // keys while Secp256r1 program takes 33-byte compressed public keys.
+    //
+    // Secp256k1 signatures are hashed with Keccak-256 to align with EVM
+    // conventions.
+    Secp256k1, b"s\x00k1", SECP256K1_PROGRAM_ID, 20, Keccak256;
+    Secp256r1, b"s\x00r1", SECP256R1_PROGRAM_ID, 33, Sha256;
 }
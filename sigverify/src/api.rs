@@ -2,6 +2,7 @@ use solana_native_sigverify::Entry;
 use solana_program::account_info::AccountInfo;
 use solana_program::program_error::ProgramError;
 use solana_program::pubkey::Pubkey;
+use solana_program::rent::Rent;
 
 use crate::{algo, stdx};
 
@@ -40,13 +41,29 @@ impl SigHash {
     /// Magic token used to identify Secp256r1 signatures.
     pub const SECP256R1_MAGIC: algo::Magic = algo::Secp256r1::MAGIC;
 
-    const SIZE: usize = core::mem::size_of::<SigHash>();
+    pub(crate) const SIZE: usize = core::mem::size_of::<SigHash>();
+
+    /// Constructs SigHash from raw bytes of the hash.
+    ///
+    /// Unlike the [`From`] implementation, this can be used in constant
+    /// contexts, e.g. to embed precomputed hashes in a program.
+    #[inline]
+    pub const fn from_bytes(bytes: [u8; 32]) -> Self { Self(bytes) }
+
+    /// Returns raw bytes of the hash.
+    ///
+    /// Unlike the [`Into`] implementation, this can be used in constant
+    /// contexts.
+    #[inline]
+    pub const fn to_bytes(&self) -> [u8; 32] { self.0 }
 
     /// Constructs a new SigHash for given signature.
     ///
     /// `magic` identifies type of signature and is typically one of
     /// [`Self::ED25519_MAGIC`], [`Self::SECP256K1_MAGIC`] or
-    /// [`Self::SECP256R1_MAGIC`].
+    /// [`Self::SECP256R1_MAGIC`].  The hash is always calculated with SHA-256
+    /// regardless of the magic.  To use hash function of a particular
+    /// algorithm (see [`algo::Algorithm::hashv`]) use [`Self::for_algorithm`].
     #[inline]
     pub fn new(
         magic: algo::Magic,
@@ -54,13 +71,49 @@ impl SigHash {
         signature: &[u8; 64],
         message: &[u8],
     ) -> Self {
-        let hash = solana_program::hash::hashv(&[
+        Self::new_with_backend::<SolanaHashBackend>(
+            magic, pubkey, signature, message,
+        )
+    }
+
+    /// Constructs a new SigHash for given signature using given SHA-256
+    /// implementation.
+    ///
+    /// The result is the same as that of [`Self::new`].  This allows off-chain
+    /// code to pick a faster hash implementation (see [`HashBackend`]).
+    #[inline]
+    pub fn new_with_backend<B: HashBackend>(
+        magic: algo::Magic,
+        pubkey: &[u8; 32],
+        signature: &[u8; 64],
+        message: &[u8],
+    ) -> Self {
+        Self(B::hashv(&[
             &magic.to_bytes(),
             &pubkey[..],
             &signature[..],
             message,
-        ]);
-        Self(hash.to_bytes())
+        ]))
+    }
+
+    /// Constructs a new SigHash for given signature of algorithm `A`.
+    ///
+    /// Uses `A`’s magic and hashes the preimage with `A`’s hash function (see
+    /// [`algo::Algorithm::hashv`]).  This is the same as
+    /// [`algo::Algorithm::sighash`].  For algorithms which use SHA-256 the
+    /// result is the same as that of [`Self::new`] with `A`’s magic.
+    #[inline]
+    pub fn for_algorithm<A: algo::Algorithm + ?Sized>(
+        pubkey: &[u8; 32],
+        signature: &[u8; 64],
+        message: &[u8],
+    ) -> Self {
+        Self::new_with_backend::<algo::Hasher<A>>(
+            A::magic(),
+            pubkey,
+            signature,
+            message,
+        )
     }
 
     /// Constructs a new SigHash from an [`Entry`].
@@ -70,45 +123,654 @@ impl SigHash {
     pub fn from_entry(magic: algo::Magic, entry: Entry) -> Self {
         Self::new(magic, entry.pubkey, entry.signature, entry.message)
     }
+
+    /// Constructs SigHashes for all given entries.
+    ///
+    /// The result is the same as calling [`Self::from_entry`] for each entry.
+    /// See [`Self::batch_iter`] for a lazy variant.
+    pub fn new_batch(magic: algo::Magic, entries: &[Entry]) -> Vec<Self> {
+        Self::batch_iter(magic, entries).collect()
+    }
+
+    /// Returns an iterator calculating SigHashes of given entries.
+    ///
+    /// The iterator yields the same values as calling [`Self::from_entry`] for
+    /// each entry.  See also [`Self::new_batch`].
+    pub fn batch_iter<'a>(
+        magic: algo::Magic,
+        entries: &'a [Entry<'a>],
+    ) -> impl ExactSizeIterator<Item = Self> + DoubleEndedIterator + 'a {
+        entries.iter().map(move |entry| Self::from_entry(magic, *entry))
+    }
+
+    /// Constructs a new SigHash for given Secp256k1 signature.
+    ///
+    /// Secp256k1 native program doesn’t verify signatures against public keys.
+    /// Instead, it recovers the public key from the signature and compares its
+    /// Ethereum address with the address in the instruction data.  This
+    /// constructor hashes what the program actually verified: the 20-byte
+    /// Ethereum address and the 65-byte signature (64-byte signature followed
+    /// by one-byte recovery id).  `magic` is typically
+    /// [`Self::SECP256K1_MAGIC`].
+    ///
+    /// **Note** that hashes created by this function are different from ones
+    /// created by [`Self::new`] and the two schemes cannot be mixed.  The
+    /// preimage starts with a tag which no magic can take, so a hash created
+    /// by this function never equals a hash created by `new` even with the
+    /// same magic and even if the bytes of Ethereum address, signature and
+    /// message happen to concatenate to the same string as public key,
+    /// signature and message passed to `new`.
+    #[inline]
+    pub fn new_secp256k1(
+        magic: algo::Magic,
+        eth_address: &[u8; 20],
+        signature: &[u8; 65],
+        message: &[u8],
+    ) -> Self {
+        Self(SolanaHashBackend::hashv(&[
+            &Scheme::Secp256k1.prefix(),
+            &magic.to_bytes(),
+            &eth_address[..],
+            &signature[..],
+            message,
+        ]))
+    }
+
+    /// Constructs a new SigHash from a Secp256k1 entry.
+    ///
+    /// See [`Self::new_secp256k1`].
+    #[inline]
+    pub fn from_secp256k1_entry(
+        magic: algo::Magic,
+        entry: solana_native_sigverify::Secp256k1Entry,
+    ) -> Self {
+        let mut signature = [0; 65];
+        signature[..64].copy_from_slice(entry.signature);
+        signature[64] = entry.recovery_id;
+        Self::new_secp256k1(magic, entry.eth_address, &signature, entry.message)
+    }
+
+    /// Constructs a new SigHash for given signature over hash of a message.
+    ///
+    /// This is for signatures created over hash of the message (see
+    /// [`solana_native_sigverify::Entry::with_message_hash`]) rather than the
+    /// message itself.  The message is hashed with
+    /// [`solana_native_sigverify::hash_message`] and the result is the same as
+    /// calling [`Self::from_message_hash`] with that hash.  This matches the
+    /// hashes stored by the sigverify program when Update operation is
+    /// invoked in hashed-message mode.
+    ///
+    /// **Note** that such SigHash commits to the hash of the message rather
+    /// than to the message itself.  The hashes live in a separate domain so
+    /// a signature of a raw 32-byte message never results in the same SigHash
+    /// as a signature of a message with that hash.
+    #[inline]
+    pub fn new_hashed(
+        magic: algo::Magic,
+        pubkey: &[u8; 32],
+        signature: &[u8; 64],
+        message: &[u8],
+    ) -> Self {
+        let hash = solana_native_sigverify::hash_message(message);
+        Self::from_message_hash(magic, pubkey, signature, &hash)
+    }
+
+    /// Constructs a new SigHash for given signature over given hash of
+    /// a message.
+    ///
+    /// This is like [`Self::new_hashed`] but takes the hash of the message
+    /// (see [`solana_native_sigverify::hash_message`]) rather than the message
+    /// itself.  It’s what the sigverify program stores for each signature in
+    /// hashed-message mode where the message in the native instruction data is
+    /// the hash of the actual message.
+    ///
+    /// The preimage starts with a tag which no magic can take so the result is
+    /// different from calling [`Self::new`] with `hash` as the message.  Both
+    /// sides creating and checking the hashes must agree on whether messages
+    /// are hashed.
+    #[inline]
+    pub fn from_message_hash(
+        magic: algo::Magic,
+        pubkey: &[u8; 32],
+        signature: &[u8; 64],
+        hash: &[u8; 32],
+    ) -> Self {
+        Self(SolanaHashBackend::hashv(&[
+            &Scheme::HashedMessage.prefix(),
+            &magic.to_bytes(),
+            &pubkey[..],
+            &signature[..],
+            &hash[..],
+        ]))
+    }
+
+    /// Constructs a new SigHash for given signature with length of the message
+    /// included in the hash.
+    ///
+    /// This differs from [`Self::new`] in that the length of the message (as
+    /// 64-bit little-endian integer) is hashed before the message.  Note that
+    /// hashes created by `new` are already unambiguous since the message is the
+    /// only variable-length part and is hashed last.  For example, signatures
+    /// of messages `foo` and `foobar` never result in the same hash even if
+    /// their bytes are shared in the native program instruction data.  This
+    /// constructor offers a separate domain for applications which want the
+    /// length to be explicitly committed to.
+    ///
+    /// The preimage starts with a tag which no magic can take so hashes
+    /// created by this function never equal ones created by `new` (or any
+    /// other constructor).  In particular, it’s not the same as calling `new`
+    /// with the length prepended to the message.  Both sides creating and
+    /// checking the hashes must agree on the scheme.  The sigverify program
+    /// stores hashes created by this function when Update operation is invoked
+    /// in length-prefixed mode.
+    #[inline]
+    pub fn new_length_prefixed(
+        magic: algo::Magic,
+        pubkey: &[u8; 32],
+        signature: &[u8; 64],
+        message: &[u8],
+    ) -> Self {
+        let len = u64::try_from(message.len()).unwrap_or(u64::MAX);
+        Self(SolanaHashBackend::hashv(&[
+            &Scheme::LengthPrefixed.prefix(),
+            &magic.to_bytes(),
+            &pubkey[..],
+            &signature[..],
+            &len.to_le_bytes(),
+            message,
+        ]))
+    }
+
+    /// Constructs a new length-prefixed SigHash from an [`Entry`].
+    ///
+    /// See [`Self::new_length_prefixed`].
+    #[inline]
+    pub fn from_entry_length_prefixed(
+        magic: algo::Magic,
+        entry: Entry,
+    ) -> Self {
+        Self::new_length_prefixed(
+            magic,
+            entry.pubkey,
+            entry.signature,
+            entry.message,
+        )
+    }
+}
+
+/// Tags identifying SigHash schemes other than the plain one.
+///
+/// Preimage of a plain SigHash (see [`SigHash::new`]) starts with the magic
+/// which is never zero.  Preimages of other schemes start with four zero bytes
+/// followed by the scheme’s tag and only then the magic.  This way preimages of
+/// different schemes never coincide even if the same magic is used.
+#[derive(Clone, Copy)]
+#[repr(u8)]
+enum Scheme {
+    /// See [`SigHash::new_secp256k1`].
+    Secp256k1 = 1,
+    /// See [`SigHash::new_length_prefixed`].
+    LengthPrefixed = 2,
+    /// See [`SigHash::from_message_hash`].
+    HashedMessage = 3,
+}
+
+impl Scheme {
+    /// Returns the prefix of preimages of SigHashes of this scheme.
+    fn prefix(self) -> [u8; 5] { [0, 0, 0, 0, self as u8] }
+}
+
+/// A SHA-256 implementation used to compute [`SigHash`]es.
+///
+/// On-chain, signature hashes are computed with the `sol_sha256` syscall (see
+/// [`SolanaHashBackend`]).  Off-chain, clients may prefer a different
+/// implementation for performance.  Any implementation must produce output
+/// byte-for-byte identical to SHA-256 since otherwise hashes computed by
+/// clients won’t match ones stored by the sigverify program.
+pub trait HashBackend {
+    /// Returns SHA-256 hash of concatenation of all the `parts`.
+    fn hashv(parts: &[&[u8]]) -> [u8; 32];
+}
+
+/// Hash backend using [`solana_program::hash::hashv`].
+///
+/// On-chain this uses the `sol_sha256` syscall.  This is the backend used by
+/// [`SigHash::new`].
+pub enum SolanaHashBackend {}
+
+impl HashBackend for SolanaHashBackend {
+    #[inline]
+    fn hashv(parts: &[&[u8]]) -> [u8; 32] {
+        solana_program::hash::hashv(parts).to_bytes()
+    }
+}
+
+/// Hash backend using the `sha2` crate.
+///
+/// Available with the `sha2` feature.  Features of the `sha2` crate (such as
+/// `asm`) can be enabled by adding it as a dependency with those features.
+#[cfg(feature = "sha2")]
+pub enum Sha2HashBackend {}
+
+#[cfg(feature = "sha2")]
+impl HashBackend for Sha2HashBackend {
+    #[inline]
+    fn hashv(parts: &[&[u8]]) -> [u8; 32] {
+        use sha2::Digest;
+        let mut hasher = sha2::Sha256::new();
+        for part in parts {
+            hasher.update(part);
+        }
+        hasher.finalize().into()
+    }
+}
+
+
+impl From<solana_program::hash::Hash> for SigHash {
+    #[inline]
+    fn from(hash: solana_program::hash::Hash) -> Self { Self(hash.to_bytes()) }
+}
+
+impl From<SigHash> for solana_program::hash::Hash {
+    #[inline]
+    fn from(hash: SigHash) -> Self { Self::new_from_array(hash.0) }
+}
+
+/// Formats the hash as 64 lowercase hexadecimal digits.
+impl core::fmt::Display for SigHash {
+    fn fmt(&self, fmtr: &mut core::fmt::Formatter) -> core::fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(fmtr, "{byte:02x}"))
+    }
+}
+
+/// Parses the hash from 64 hexadecimal digits.
+///
+/// Both lowercase and uppercase digits are accepted.
+impl core::str::FromStr for SigHash {
+    type Err = ParseSigHashError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        fn digit(chr: u8) -> Option<u8> {
+            (chr as char).to_digit(16).map(|digit| digit as u8)
+        }
+
+        let value = value.as_bytes();
+        if value.len() != 64 {
+            return Err(ParseSigHashError);
+        }
+        let mut hash = [0; 32];
+        for (dst, pair) in hash.iter_mut().zip(value.chunks_exact(2)) {
+            let (hi, lo) = (digit(pair[0]), digit(pair[1]));
+            *dst = hi
+                .zip(lo)
+                .map(|(hi, lo)| hi << 4 | lo)
+                .ok_or(ParseSigHashError)?;
+        }
+        Ok(Self(hash))
+    }
+}
+
+/// Error when parsing a [`SigHash`] from a string.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ParseSigHashError;
+
+impl core::fmt::Display for ParseSigHashError {
+    fn fmt(&self, fmtr: &mut core::fmt::Formatter) -> core::fmt::Result {
+        fmtr.write_str("expected 64 hexadecimal digits")
+    }
+}
+
+
+/// Errors specific to the signatures account.
+///
+/// When converted into [`ProgramError`], the errors are represented as
+/// [`ProgramError::Custom`] with the code being the discriminant of the enum.
+/// The codes start at `0x1000` rather than zero so they aren’t mistaken for
+/// `Custom(0)` which is commonly used as a generic error.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum AccountError {
+    /// The signatures account uses unsupported layout version.
+    ///
+    /// This may happen if the account has been created by a newer version of
+    /// the sigverify program.  See [`account_version`].
+    UnsupportedVersion = 0x1000,
+
+    /// The signatures account is full.
+    ///
+    /// The account already holds [`SignaturesAccount::MAX_SIGNATURES`]
+    /// signatures and cannot be enlarged any further.  Remaining signatures
+    /// need to be aggregated in a new account (i.e. one with a different
+    /// seed).
+    AccountFull = 0x1001,
+
+    /// The signatures account doesn’t store magic of the signatures.
+    ///
+    /// Only full-entry accounts store magic next to each signature hash (see
+    /// [`SignaturesAccount`]) so only in those can signatures of a single
+    /// algorithm be looked up or cleared.
+    MagicNotStored = 0x1002,
+}
+
+impl From<AccountError> for ProgramError {
+    fn from(err: AccountError) -> Self { Self::Custom(err as u32) }
+}
+
+
+/// Current version of the signatures account layout.
+///
+/// Accounts created before versioning was introduced have version zero.  They
+/// are still supported and keep their layout; the program writes the current
+/// version only when it creates an account.  See [`account_version`] for
+/// description of the layouts.
+pub const ACCOUNT_VERSION: u8 = 1;
+
+/// Version of accounts created before versioning was introduced.
+const LEGACY_ACCOUNT_VERSION: u8 = 0;
+
+/// Discriminator following the header of versioned signatures accounts.
+const ACCOUNT_DISCRIMINATOR: [u8; 4] = *b"sigv";
+
+/// Discriminator following the header of full-entry signatures accounts.
+const FULL_ENTRY_DISCRIMINATOR: [u8; 4] = *b"sigf";
+
+/// Size of a record in full-entry signatures account, i.e. signature hash
+/// followed by magic.
+const FULL_ENTRY_RECORD_SIZE: usize = SigHash::SIZE + 4;
+
+/// Returns the layout version of the signatures account with given data.
+///
+/// Returns `None` if the data is too short to contain the account header.  No
+/// other verification is performed, in particular, the returned version may
+/// be one which isn’t supported (i.e. one different than [`ACCOUNT_VERSION`]
+/// or zero).
+///
+/// This can be used by clients to query the format of the account (e.g. one
+/// fetched through RPC) without fully parsing it.
+///
+/// # Layout
+///
+/// Account data starts with a 12-byte header: little-endian 64-bit epoch,
+/// little-endian 24-bit number of signatures and one-byte version.  In
+/// version [`ACCOUNT_VERSION`] the header is followed by a four-byte
+/// discriminator and then the records sorted by signature hash.  With `sigv`
+/// discriminator the records are 32-byte [`SigHash`]es.  With `sigf`
+/// discriminator the account is a full-entry account whose 36-byte records
+/// are [`SigHash`]es each followed by the little-endian magic of the
+/// signature’s algorithm.  In legacy version zero the 32-byte hashes follow
+/// the header directly.
+///
+/// Readers from before versioning was introduced treat the version byte as
+/// the most significant byte of a 32-bit count.  For a versioned account they
+/// read a count of at least 2²⁴ which exceeds what any account can hold so
+/// they fail with [`ProgramError::InvalidAccountData`] rather than misreading
+/// the data.  In particular, they never report a signature as present.
+/// Accounts which already exist keep legacy layout and remain readable by
+/// them.
+pub fn account_version(data: &[u8]) -> Option<u8> {
+    stdx::split_at::<{ LEGACY_HEAD_SIZE }, u8>(data)
+        .map(|(head, _)| bytemuck::must_cast_ref::<_, Header>(head).version)
+}
+
+/// Returns number of signatures stored in the signatures account with given
+/// data.
+///
+/// Returns an error if the data is too short to contain the account header or
+/// the account uses unsupported layout version.  Note that the count is
+/// returned regardless of the epoch stored in the account.
+///
+/// This can be used by clients to inspect the account (e.g. one fetched
+/// through RPC) without fully parsing it.  See also
+/// [`SignaturesAccount::count`].
+pub fn count_in_data(data: &[u8]) -> Result<u32> {
+    split_data(data).map(|(header, ..)| header.count())
+}
+
+/// Returns epoch of the signatures stored in the signatures account with given
+/// data.
+///
+/// Returns an error if the data is too short to contain the account header or
+/// the account uses unsupported layout version.  Together with
+/// [`count_in_data`] this describes the state of the account, e.g. a client
+/// can compare the epoch with the one it’s currently using to detect stale
+/// accounts.  See also [`SignaturesAccount::epoch`].
+pub fn epoch_in_data(data: &[u8]) -> Result<u64> {
+    split_data(data).map(|(header, ..)| header.epoch())
+}
+
+/// Splits account data into header, layout of the records and the data
+/// holding the records.
+///
+/// Returns an error if the data is too short to contain the header, the
+/// account uses unsupported layout version or, for versioned accounts, the
+/// discriminator isn’t recognised.
+fn split_data(data: &[u8]) -> Result<(&Header, Layout, &[u8])> {
+    let (head, tail) = stdx::split_at::<{ LEGACY_HEAD_SIZE }, u8>(data)
+        .ok_or(ProgramError::AccountDataTooSmall)?;
+    let header = Header::from_bytes(head)?;
+    let (layout, len) = header.layout(tail)?;
+    Ok((header, layout, &tail[len..]))
+}
+
+/// Splits mutable account data into header, layout of the records and the
+/// data holding the records.
+///
+/// See [`split_data`].
+#[cfg(any(test, not(any(feature = "lib", feature = "client"))))]
+fn split_data_mut(data: &mut [u8]) -> Result<(&mut Header, Layout, &mut [u8])> {
+    let (head, tail) = stdx::split_at_mut::<{ LEGACY_HEAD_SIZE }, u8>(data)
+        .ok_or(ProgramError::AccountDataTooSmall)?;
+    let header = Header::from_bytes_mut(head)?;
+    let (layout, len) = header.layout(tail)?;
+    Ok((header, layout, &mut tail[len..]))
+}
+
+/// Layout of the records stored in a signatures account.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Layout {
+    /// Records are just the signature hashes.
+    Plain,
+    /// Records are signature hashes each followed by magic.
+    FullEntry,
+}
+
+impl Layout {
+    /// Returns size of a single record.
+    const fn record_size(self) -> usize {
+        match self {
+            Self::Plain => SigHash::SIZE,
+            Self::FullEntry => FULL_ENTRY_RECORD_SIZE,
+        }
+    }
+
+    /// Returns maximum number of records an account can hold.
+    const fn max_records(self) -> u32 {
+        let len = solana_system_interface::MAX_PERMITTED_DATA_LENGTH as usize;
+        ((len - HEAD_SIZE) / self.record_size()) as u32
+    }
+
+    /// Returns size of the account data needed to hold `count` records.
+    fn size_for(self, count: u32) -> usize {
+        usize::try_from(count)
+            .unwrap_or(usize::MAX)
+            .saturating_mul(self.record_size())
+            .saturating_add(HEAD_SIZE)
+    }
 }
 
 
 /// Header of the signatures account.
+///
+/// The version is stored in what used to be the most significant byte of the
+/// count.  Since account can hold fewer than 2²⁴ signatures, the byte has
+/// always been zero so accounts created before versioning was introduced are
+/// treated as version zero.  The program writes [`ACCOUNT_VERSION`] when it
+/// creates the account and rejects accounts with unknown version.
+///
+/// In versioned accounts the header is followed by [`ACCOUNT_DISCRIMINATOR`]
+/// or, in full-entry accounts, [`FULL_ENTRY_DISCRIMINATOR`].  Legacy accounts
+/// have no discriminator and keep their layout.  See [`account_version`] for
+/// how readers from before versioning was introduced handle versioned
+/// accounts.
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 #[repr(C)]
 struct Header {
     epoch_le: [u8; 8],
-    count_le: [u8; 4],
+    count_le: [u8; 3],
+    version: u8,
 }
 
 impl Header {
-    fn count(&self) -> u32 { u32::from_le_bytes(self.count_le) }
+    /// Interprets the head of account data as a header.
+    ///
+    /// Returns an error if the header uses unsupported version.
+    fn from_bytes(head: &[u8; LEGACY_HEAD_SIZE]) -> Result<&Self> {
+        let header = bytemuck::must_cast_ref::<_, Self>(head);
+        if header.is_supported() {
+            Ok(header)
+        } else {
+            Err(AccountError::UnsupportedVersion.into())
+        }
+    }
+
+    /// Interprets the head of account data as a mutable header.
+    ///
+    /// Returns an error if the header uses unsupported version.
+    #[cfg(any(test, not(any(feature = "lib", feature = "client"))))]
+    fn from_bytes_mut(head: &mut [u8; LEGACY_HEAD_SIZE]) -> Result<&mut Self> {
+        let header = bytemuck::must_cast_mut::<_, Self>(head);
+        if header.is_supported() {
+            Ok(header)
+        } else {
+            Err(AccountError::UnsupportedVersion.into())
+        }
+    }
+
+    /// Returns whether the header uses a supported version.
+    fn is_supported(&self) -> bool {
+        self.version == ACCOUNT_VERSION ||
+            self.version == LEGACY_ACCOUNT_VERSION
+    }
+
+    /// Checks the discriminator at the start of `tail`, i.e. data following
+    /// the header, and returns layout of the records and length of the
+    /// discriminator.
+    ///
+    /// Legacy accounts have no discriminator so zero is returned for them.
+    fn layout(&self, tail: &[u8]) -> Result<(Layout, usize)> {
+        if self.version == LEGACY_ACCOUNT_VERSION {
+            return Ok((Layout::Plain, 0));
+        }
+        let (got, _) =
+            stdx::split_at::<{ ACCOUNT_DISCRIMINATOR.len() }, u8>(tail)
+                .ok_or(ProgramError::AccountDataTooSmall)?;
+        let layout = match *got {
+            ACCOUNT_DISCRIMINATOR => Layout::Plain,
+            FULL_ENTRY_DISCRIMINATOR => Layout::FullEntry,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        Ok((layout, got.len()))
+    }
+
+    fn count(&self) -> u32 {
+        let [a, b, c] = self.count_le;
+        u32::from_le_bytes([a, b, c, 0])
+    }
+
+    fn epoch(&self) -> u64 { u64::from_le_bytes(self.epoch_le) }
 
     #[cfg(any(test, not(any(feature = "lib", feature = "client"))))]
     fn get_count(&self, want_epoch: Option<u64>) -> u32 {
         match want_epoch {
-            Some(want) if want != u64::from_le_bytes(self.epoch_le) => 0,
+            Some(want) if want != self.epoch() => 0,
             _ => self.count(),
         }
     }
 
+    /// Sets epoch (if given) and count.
+    ///
+    /// The version is left unchanged, i.e. legacy accounts keep their layout.
     #[cfg(any(test, not(any(feature = "lib", feature = "client"))))]
-    fn set(&mut self, epoch: Option<u64>, count: u32) {
+    fn set(&mut self, epoch: Option<u64>, count: u32) -> Result {
+        let [a, b, c, 0] = count.to_le_bytes() else {
+            return Err(ProgramError::ArithmeticOverflow);
+        };
         if let Some(epoch) = epoch {
             self.epoch_le = epoch.to_le_bytes();
         }
-        self.count_le = count.to_le_bytes();
+        self.count_le = [a, b, c];
+        Ok(())
     }
 }
 
-const HEAD_SIZE: usize = core::mem::size_of::<Header>();
+/// Size of the header of legacy accounts.
+const LEGACY_HEAD_SIZE: usize = core::mem::size_of::<Header>();
+
+/// Size of the header, including the discriminator, of accounts created by the
+/// program.
+const HEAD_SIZE: usize = LEGACY_HEAD_SIZE + ACCOUNT_DISCRIMINATOR.len();
+
+/// Maximum number of signatures a single signatures account can hold.
+///
+/// This is limited by the maximum size of account data (i.e.
+/// [`MAX_PERMITTED_DATA_LENGTH`]) less the account header.  Clients aggregating
+/// more signatures need to split them across multiple accounts (i.e. use
+/// different seeds).  Same as [`SignaturesAccount::MAX_SIGNATURES`].
+///
+/// Full-entry accounts have larger records and can hold at most
+/// [`MAX_FULL_ENTRY_SIGNATURES_PER_ACCOUNT`] signatures.
+///
+/// [`MAX_PERMITTED_DATA_LENGTH`]: solana_system_interface::MAX_PERMITTED_DATA_LENGTH
+pub const MAX_SIGNATURES_PER_ACCOUNT: u32 = Layout::Plain.max_records();
+
+/// Maximum number of signatures a single full-entry signatures account can
+/// hold.
+///
+/// See [`MAX_SIGNATURES_PER_ACCOUNT`] and [`SignaturesAccount`].
+pub const MAX_FULL_ENTRY_SIGNATURES_PER_ACCOUNT: u32 =
+    Layout::FullEntry.max_records();
+
+
+/// A signature to look for with [`SignaturesAccount::find_many`].
+///
+/// The tuple holds magic, public key, signature and message, i.e. arguments
+/// of [`SignaturesAccount::find`].
+pub type SignatureQuery<'a> =
+    (algo::Magic, &'a [u8; 32], &'a [u8; 64], &'a [u8]);
+
+/// A Secp256k1 signature to look for with
+/// [`SignaturesAccount::find_many_secp256k1`].
+///
+/// The tuple holds Ethereum address, 65-byte signature and message, i.e.
+/// arguments of [`SignaturesAccount::find_secp256k1`].
+pub type Secp256k1Query<'a> = (&'a [u8; 20], &'a [u8; 65], &'a [u8]);
 
 
 /// Wrapper around signatures account created by the verifier program.
+///
+/// A single account may hold signatures of different algorithms.  The
+/// algorithm’s magic is hashed into each [`SigHash`] so in a regular account
+/// a stored record cannot be attributed to an algorithm.  A full-entry
+/// account (created by the Update operation with the full-entry flag) stores
+/// the magic next to each [`SigHash`] instead.  Such account can report the
+/// magic of a stored signature (see [`Self::magic_of`]) and the program can
+/// clear signatures of a single algorithm from it (see the Clear operation of
+/// the program).  The price is four more bytes per signature.  Both kinds of
+/// accounts are searched the same way.
+///
+/// An account can hold at most [`MAX_SIGNATURES_PER_ACCOUNT`] signatures (or
+/// [`MAX_FULL_ENTRY_SIGNATURES_PER_ACCOUNT`] for full-entry account).  Once
+/// that limit is reached, the program fails with [`AccountError::AccountFull`]
+/// when asked to add more signatures to the account.
 #[derive(Clone, Copy, derive_more::Deref, derive_more::DerefMut)]
 pub struct SignaturesAccount<'a, 'info>(pub(crate) &'a AccountInfo<'info>);
 
 impl<'a, 'info> SignaturesAccount<'a, 'info> {
+    /// Maximum number of signatures an account can hold.
+    ///
+    /// See [`MAX_SIGNATURES_PER_ACCOUNT`].
+    pub const MAX_SIGNATURES: u32 = MAX_SIGNATURES_PER_ACCOUNT;
+
     /// Constructs new object checking that the wrapped account is owned by
     /// given signature verifier program.
     ///
@@ -126,7 +788,45 @@ impl<'a, 'info> SignaturesAccount<'a, 'info> {
         }
     }
 
+    /// Returns size of the account data needed to hold `count` signatures.
+    ///
+    /// Note that the sigverify program grows the account in increments of 10
+    /// KiB (i.e. [`MAX_PERMITTED_DATA_INCREASE`]) so the actual account it
+    /// creates may be larger.
+    ///
+    /// [`MAX_PERMITTED_DATA_INCREASE`]: solana_program::entrypoint::MAX_PERMITTED_DATA_INCREASE
+    pub fn size_for(count: u32) -> usize { Layout::Plain.size_for(count) }
+
+    /// Returns size of full-entry account data needed to hold `count`
+    /// signatures.
+    ///
+    /// This is like [`Self::size_for`] but for accounts which store magic
+    /// next to each signature.
+    pub fn size_for_full_entry(count: u32) -> usize {
+        Layout::FullEntry.size_for(count)
+    }
+
+    /// Returns size of the account data needed to hold signatures from all the
+    /// given entries.
+    ///
+    /// This is a shorthand for [`Self::size_for`] with number of entries as the
+    /// argument.
+    pub fn required_account_size(entries: &[Entry]) -> usize {
+        Self::size_for(u32::try_from(entries.len()).unwrap_or(u32::MAX))
+    }
+
+    /// Returns minimum balance needed for the account to hold signatures from
+    /// all the given entries and be rent-exempt.
+    pub fn required_rent(entries: &[Entry], rent: &Rent) -> u64 {
+        rent.minimum_balance(Self::required_account_size(entries))
+    }
+
     /// Looks for given signature in the account data.
+    ///
+    /// This computes the signature’s hash with [`SigHash::new`], which is what
+    /// the sigverify program stores, and then calls [`Self::find_hash`].
+    /// Signatures aggregated from Secp256k1 program calls are hashed
+    /// differently; use [`Self::find_secp256k1`] to look for those.
     pub fn find(
         &self,
         magic: algo::Magic,
@@ -134,59 +834,420 @@ impl<'a, 'info> SignaturesAccount<'a, 'info> {
         signature: &[u8; 64],
         message: &[u8],
     ) -> Result<bool> {
-        let data = self.0.try_borrow_data()?;
-        let signature = SigHash::new(magic, pubkey, signature, message);
-        find_sighash(*data, signature)
+        self.find_hash(&SigHash::new(magic, pubkey, signature, message))
     }
 
-    /// Reads number of signatures saved in the account.
+    /// Looks for given Secp256k1 signature in the account data.
     ///
-    /// If `want_epoch` is `Some` and epoch stored in the account doesn’t match
-    /// the one given, returns zero.
-    #[cfg(any(test, not(any(feature = "lib", feature = "client"))))]
-    pub(crate) fn read_count(&self, want_epoch: Option<u64>) -> Result<u32> {
+    /// `signature` is the 64-byte signature followed by one-byte recovery id.
+    /// This computes the signature’s hash with [`SigHash::new_secp256k1`]
+    /// (i.e. the hash the sigverify program stores for Secp256k1 program
+    /// calls) and then calls [`Self::find_hash`].
+    pub fn find_secp256k1(
+        &self,
+        eth_address: &[u8; 20],
+        signature: &[u8; 65],
+        message: &[u8],
+    ) -> Result<bool> {
+        self.find_hash(&SigHash::new_secp256k1(
+            SigHash::SECP256K1_MAGIC,
+            eth_address,
+            signature,
+            message,
+        ))
+    }
+
+    /// Looks for given signature hash in the account data.
+    ///
+    /// This is like [`Self::find`] but takes an already computed hash which
+    /// avoids hashing the signature again, e.g. if the hash has been passed to
+    /// the program in instruction data.
+    pub fn find_hash(&self, sighash: &SigHash) -> Result<bool> {
         let data = self.0.try_borrow_data()?;
-        let (head, _) = stdx::split_at::<{ HEAD_SIZE }, u8>(&data)
-            .ok_or(ProgramError::AccountDataTooSmall)?;
-        Ok(bytemuck::must_cast_ref::<_, Header>(head).get_count(want_epoch))
+        find_sighash(*data, *sighash)
     }
 
-    /// Sets number of signatures saved in the account and sort the entries.
-    #[cfg(any(test, not(any(feature = "lib", feature = "client"))))]
-    pub(crate) fn write_count_and_sort(
+    /// Returns index of given signature in the account data.
+    ///
+    /// Signature hashes are stored sorted so the index is the position of the
+    /// signature’s hash in sorted list of all stored hashes.  It doesn’t change
+    /// until new signatures are added to the account or the account is reset.
+    /// This can be used with [`crate::merkle::prove`] to create proof of
+    /// inclusion of the signature.  Returns `None` if the signature isn’t
+    /// stored in the account.
+    pub fn position(
         &self,
-        epoch: Option<u64>,
-        count: u32,
-    ) -> Result {
+        magic: algo::Magic,
+        pubkey: &[u8; 32],
+        signature: &[u8; 64],
+        message: &[u8],
+    ) -> Result<Option<u32>> {
+        let data = self.0.try_borrow_data()?;
+        let signature = SigHash::new(magic, pubkey, signature, message);
+        position_sighash(*data, signature)
+    }
+
+    /// Returns index of given Secp256k1 signature in the account data.
+    ///
+    /// This is like [`Self::position`] but for signatures aggregated from
+    /// Secp256k1 program calls.  See [`Self::find_secp256k1`].
+    pub fn position_secp256k1(
+        &self,
+        eth_address: &[u8; 20],
+        signature: &[u8; 65],
+        message: &[u8],
+    ) -> Result<Option<u32>> {
+        let data = self.0.try_borrow_data()?;
+        let signature = SigHash::new_secp256k1(
+            SigHash::SECP256K1_MAGIC,
+            eth_address,
+            signature,
+            message,
+        );
+        position_sighash(*data, signature)
+    }
+
+    /// Looks for multiple signatures in the account data.
+    ///
+    /// Each query holds arguments to [`Self::find`].  Returns whether
+    /// each of the signatures has been found, in the same order as `queries`.
+    /// Compared to calling `find` for each signature, this borrows and parses
+    /// the account data only once.
+    pub fn find_many(&self, queries: &[SignatureQuery]) -> Result<Vec<bool>> {
+        let data = self.0.try_borrow_data()?;
+        let records = read_records(*data)?;
+        Ok(queries
+            .iter()
+            .map(|&(magic, pubkey, signature, message)| {
+                let sighash = SigHash::new(magic, pubkey, signature, message);
+                records.binary_search(&sighash).is_ok()
+            })
+            .collect())
+    }
+
+    /// Looks for multiple Secp256k1 signatures in the account data.
+    ///
+    /// This is like [`Self::find_many`] but for signatures aggregated from
+    /// Secp256k1 program calls.  Each query holds arguments to
+    /// [`Self::find_secp256k1`].
+    pub fn find_many_secp256k1(
+        &self,
+        queries: &[Secp256k1Query],
+    ) -> Result<Vec<bool>> {
+        let data = self.0.try_borrow_data()?;
+        let records = read_records(*data)?;
+        Ok(queries
+            .iter()
+            .map(|&(eth_address, signature, message)| {
+                let sighash = SigHash::new_secp256k1(
+                    SigHash::SECP256K1_MAGIC,
+                    eth_address,
+                    signature,
+                    message,
+                );
+                records.binary_search(&sighash).is_ok()
+            })
+            .collect())
+    }
+
+    /// Returns magic of the algorithm of given stored signature.
+    ///
+    /// Returns `None` if the signature isn’t stored in the account.  Returns
+    /// [`AccountError::MagicNotStored`] if the account isn’t a full-entry
+    /// account and thus doesn’t store magic of the signatures.
+    pub fn magic_of(&self, sighash: &SigHash) -> Result<Option<algo::Magic>> {
+        let data = self.0.try_borrow_data()?;
+        let Records::FullEntry(records) = read_records(*data)? else {
+            return Err(AccountError::MagicNotStored.into());
+        };
+        Ok(Records::FullEntry(records)
+            .binary_search(sighash)
+            .ok()
+            .and_then(|idx| record_magic(&records[idx])))
+    }
+
+    /// Returns whether the account is a full-entry account, i.e. one which
+    /// stores magic next to each signature.
+    pub fn is_full_entry(&self) -> Result<bool> {
+        let data = self.0.try_borrow_data()?;
+        Ok(split_data(&data)?.1 == Layout::FullEntry)
+    }
+
+    /// Returns number of signatures stored in the account.
+    ///
+    /// See [`count_in_data`].
+    pub fn count(&self) -> Result<u32> {
+        count_in_data(&self.0.try_borrow_data()?)
+    }
+
+    /// Returns epoch of the signatures stored in the account.
+    ///
+    /// See [`epoch_in_data`].
+    pub fn epoch(&self) -> Result<u64> {
+        epoch_in_data(&self.0.try_borrow_data()?)
+    }
+
+    /// Returns number of signatures the account can hold without being
+    /// resized.
+    ///
+    /// The capacity is determined from the size of the account data.  Returns
+    /// an error if the account is too small to hold the header or uses
+    /// unsupported layout version.
+    pub fn capacity(&self) -> Result<u32> {
+        let data = self.0.try_borrow_data()?;
+        let (_, layout, tail) = split_data(&data)?;
+        u32::try_from(tail.len() / layout.record_size())
+            .map_err(|_| ProgramError::InvalidAccountData)
+    }
+
+    /// Returns number of signatures which can be added to the account before
+    /// it needs to be resized.
+    ///
+    /// This is [`Self::capacity`] minus [`Self::count`].  Returns an error if
+    /// the account data is malformed.
+    pub fn remaining(&self) -> Result<u32> {
+        self.capacity()?
+            .checked_sub(self.count()?)
+            .ok_or(ProgramError::InvalidAccountData)
+    }
+
+    /// Returns an iterator over signature hashes stored in the account.
+    ///
+    /// The hashes are yielded in sorted order.  Only the stored entries are
+    /// returned, i.e. the iterator respects the count saved in the account
+    /// header rather than the size of the account.  The account data remains
+    /// borrowed until the iterator is dropped.
+    ///
+    /// Returns an error if the account data cannot be borrowed or is
+    /// malformed.
+    pub fn iter(&self) -> Result<SigHashIter<'a>> {
+        let data = self.0.try_borrow_data()?;
+        let records = read_records(*data)?;
+        let (count, size) = (records.len(), records.record_size());
+        // read_records has succeeded above so split_data will succeed again.
+        let data = core::cell::Ref::map(data, |data| {
+            split_data(data).map_or(&[][..], |(.., tail)| tail)
+        });
+        Ok(SigHashIter { data, size, range: 0..count })
+    }
+
+    /// Returns the smallest signature hash stored in the account.
+    ///
+    /// Signature hashes are stored sorted so this is the first entry.  Returns
+    /// `None` if the account holds no signatures.  This can be used together
+    /// with [`Self::max_sighash`] to partition queries across multiple accounts
+    /// by ranges of signature hashes.
+    pub fn min_sighash(&self) -> Result<Option<SigHash>> {
+        let data = self.0.try_borrow_data()?;
+        Ok(read_records(*data)?.get(0))
+    }
+
+    /// Returns the largest signature hash stored in the account.
+    ///
+    /// Signature hashes are stored sorted so this is the last entry.  Returns
+    /// `None` if the account holds no signatures.
+    pub fn max_sighash(&self) -> Result<Option<SigHash>> {
+        let data = self.0.try_borrow_data()?;
+        let records = read_records(*data)?;
+        Ok(records.len().checked_sub(1).and_then(|idx| records.get(idx)))
+    }
+
+    /// Returns Merkle root of signature hashes stored in the account.
+    ///
+    /// The tree is built over the sorted list of stored hashes so the root is
+    /// deterministic and doesn’t depend on order in which signatures have been
+    /// added.  See [`crate::merkle`] module for exact description of the tree
+    /// construction which lets off-chain verifiers reproduce the root.  Root of
+    /// an account with no signatures is all zeros.
+    pub fn merkle_root(&self) -> Result<[u8; 32]> {
+        use bytemuck::TransparentWrapper;
+
+        let data = self.0.try_borrow_data()?;
+        Ok(match read_records(*data)? {
+            Records::Plain(sighashes) => {
+                crate::merkle::root(SigHash::wrap_slice(sighashes))
+            }
+            records @ Records::FullEntry(_) => {
+                let sighashes = (0..records.len())
+                    .filter_map(|idx| records.get(idx))
+                    .collect::<Vec<_>>();
+                crate::merkle::root(&sighashes)
+            }
+        })
+    }
+
+    /// Writes header of a newly created account.
+    ///
+    /// Sets layout version of the account to [`ACCOUNT_VERSION`] and writes the
+    /// discriminator, [`FULL_ENTRY_DISCRIMINATOR`] if `full_entry` is true or
+    /// [`ACCOUNT_DISCRIMINATOR`] otherwise.  The rest of the header is left as
+    /// is, i.e. zeroed for a freshly created account.
+    #[cfg(any(test, not(any(feature = "lib", feature = "client"))))]
+    pub(crate) fn write_version(&self, full_entry: bool) -> Result {
         let mut data = self.0.try_borrow_mut_data()?;
-        let (head, tail) = stdx::split_at_mut::<{ HEAD_SIZE }, _>(*data)
+        let (head, _) = stdx::split_at_mut::<{ HEAD_SIZE }, u8>(*data)
+            .ok_or(ProgramError::AccountDataTooSmall)?;
+        let (head, disc) = stdx::split_at_mut::<{ LEGACY_HEAD_SIZE }, u8>(head)
             .ok_or(ProgramError::AccountDataTooSmall)?;
-        stdx::as_chunks_mut::<{ SigHash::SIZE }, _>(tail)
+        bytemuck::must_cast_mut::<_, Header>(head).version = ACCOUNT_VERSION;
+        disc.copy_from_slice(if full_entry {
+            &FULL_ENTRY_DISCRIMINATOR
+        } else {
+            &ACCOUNT_DISCRIMINATOR
+        });
+        Ok(())
+    }
+
+    /// Reads number of signatures saved in the account.
+    ///
+    /// If `want_epoch` is `Some` and epoch stored in the account doesn’t match
+    /// the one given, returns zero.
+    #[cfg(any(test, not(any(feature = "lib", feature = "client"))))]
+    pub(crate) fn read_count(&self, want_epoch: Option<u64>) -> Result<u32> {
+        let data = self.0.try_borrow_data()?;
+        Ok(split_data(&data)?.0.get_count(want_epoch))
+    }
+
+    /// Reads epoch and number of signatures saved in the account.
+    ///
+    /// Unlike [`Self::read_count`], the count is returned regardless of the
+    /// epoch.
+    #[cfg(any(test, not(any(feature = "lib", feature = "client"))))]
+    pub(crate) fn read_epoch_and_count(&self) -> Result<(u64, u32)> {
+        let data = self.0.try_borrow_data()?;
+        let (header, ..) = split_data(&data)?;
+        Ok((header.epoch(), header.count()))
+    }
+
+    /// Sorts first `count` entries, removes duplicates among them and sets
+    /// number of signatures saved in the account.
+    ///
+    /// The same signature may be verified multiple times (e.g. in separate
+    /// Update operations) so after sorting equal entries are collapsed into
+    /// one.  The count saved in the account is the number of unique entries
+    /// which may be smaller than `count`.
+    ///
+    /// Entries past `count` are neither sorted nor looked at.  They may hold
+    /// stale hashes from a previous epoch but since readers only ever consider
+    /// the first `count` entries (see [`read_records`]), those are never
+    /// reported as present.  Unstable sort is fine since equal entries are
+    /// indistinguishable.  In full-entry accounts the magic follows the
+    /// signature hash so the records are sorted by the hash.
+    ///
+    /// Returns the epoch and the number of unique entries now saved in the
+    /// account.
+    #[cfg(any(test, not(any(feature = "lib", feature = "client"))))]
+    pub(crate) fn write_count_and_sort(
+        &self,
+        epoch: Option<u64>,
+        count: u32,
+    ) -> Result<(u64, u32)> {
+        let mut data = self.0.try_borrow_mut_data()?;
+        let (header, layout, tail) = split_data_mut(*data)?;
+        let count = usize::try_from(count).unwrap();
+        let unique = match layout {
+            Layout::Plain => sort_and_dedup::<{ SigHash::SIZE }>(tail, count),
+            Layout::FullEntry => {
+                sort_and_dedup::<FULL_ENTRY_RECORD_SIZE>(tail, count)
+            }
+        }?;
+        // unique ≤ count so the conversion never fails.
+        let unique = unique as u32;
+        header.set(epoch, unique)?;
+        Ok((header.epoch(), unique))
+    }
+
+    /// Removes given signature hash from the account.
+    ///
+    /// Looks for the hash among stored entries and, if found, removes it by
+    /// shifting all following entries down and decrementing number of
+    /// signatures saved in the account.  The entries remain sorted.  Returns
+    /// whether the hash has been found and removed.
+    #[cfg(any(test, not(any(feature = "lib", feature = "client"))))]
+    pub(crate) fn remove_signature(&self, sighash: &SigHash) -> Result<bool> {
+        let mut data = self.0.try_borrow_mut_data()?;
+        let (header, layout, tail) = split_data_mut(*data)?;
+        let count = header.count();
+        let removed = match layout {
+            Layout::Plain => {
+                remove_record::<{ SigHash::SIZE }>(tail, count, sighash)
+            }
+            Layout::FullEntry => {
+                remove_record::<FULL_ENTRY_RECORD_SIZE>(tail, count, sighash)
+            }
+        }?;
+        if removed {
+            header.set(None, count - 1)?;
+        }
+        Ok(removed)
+    }
+
+    /// Removes all signatures with given magic from a full-entry account.
+    ///
+    /// Remaining entries are moved to the front keeping them sorted and number
+    /// of signatures saved in the account is updated.  Returns number of
+    /// removed signatures.  Returns [`AccountError::MagicNotStored`] if the
+    /// account isn’t a full-entry account.
+    #[cfg(any(test, not(any(feature = "lib", feature = "client"))))]
+    pub(crate) fn clear_magic(&self, magic: algo::Magic) -> Result<u32> {
+        let mut data = self.0.try_borrow_mut_data()?;
+        let (header, layout, tail) = split_data_mut(*data)?;
+        if layout != Layout::FullEntry {
+            return Err(AccountError::MagicNotStored.into());
+        }
+        let count = header.count();
+        let entries = stdx::as_chunks_mut::<FULL_ENTRY_RECORD_SIZE, _>(tail)
             .0
             .get_mut(..usize::try_from(count).unwrap())
-            .ok_or(ProgramError::AccountDataTooSmall)?
-            .sort_unstable();
-        bytemuck::must_cast_mut::<_, Header>(head).set(epoch, count);
-        Ok(())
+            .ok_or(ProgramError::InvalidAccountData)?;
+
+        // Move entries to keep to the front.  `kept` is the number of entries
+        // kept so far.
+        let mut kept = 0;
+        for idx in 0..entries.len() {
+            if record_magic(&entries[idx]) != Some(magic) {
+                entries[kept] = entries[idx];
+                kept += 1;
+            }
+        }
+        // kept ≤ count so the conversion never fails.
+        let kept = kept as u32;
+        header.set(None, kept)?;
+        Ok(count - kept)
     }
 
     /// Writes signature at given index.
     ///
-    /// If the account isn’t large enough to hold `index` entries, calls
-    /// `enlarge` to resize the account.
+    /// In full-entry accounts `magic` is written after the signature hash;
+    /// otherwise it’s ignored.  If the account isn’t large enough to hold
+    /// `index` entries, calls `enlarge` to resize the account.  Returns
+    /// [`AccountError::AccountFull`] if `index` is not less than
+    /// [`Self::MAX_SIGNATURES`] (or [`MAX_FULL_ENTRY_SIGNATURES_PER_ACCOUNT`]
+    /// for full-entry account) since such entry would never fit in an account.
+    /// Checking that upfront also guarantees the byte offsets never overflow,
+    /// even with 32-bit `usize`.
     #[cfg(any(test, not(any(feature = "lib", feature = "client"))))]
     pub(crate) fn write_signature(
         &self,
         index: u32,
+        magic: algo::Magic,
         signature: &SigHash,
         enlarge: impl FnOnce() -> Result,
     ) -> Result {
+        let (layout, offset) = {
+            let data = self.0.try_borrow_data()?;
+            let (_, layout, tail) = split_data(&data)?;
+            (layout, data.len() - tail.len())
+        };
+        if index >= layout.max_records() {
+            return Err(AccountError::AccountFull.into());
+        }
         let range = (|| {
             let start = usize::try_from(index)
                 .ok()?
-                .checked_mul(core::mem::size_of_val(signature))?
-                .checked_add(HEAD_SIZE)?;
-            let end = start.checked_add(core::mem::size_of_val(signature))?;
+                .checked_mul(layout.record_size())?
+                .checked_add(offset)?;
+            let end = start.checked_add(layout.record_size())?;
             Some(start..end)
         })()
         .ok_or(ProgramError::ArithmeticOverflow)?;
@@ -195,34 +1256,241 @@ impl<'a, 'info> SignaturesAccount<'a, 'info> {
             enlarge()?;
         }
 
-        self.0
-            .try_borrow_mut_data()?
-            .get_mut(range)
-            .ok_or(ProgramError::AccountDataTooSmall)?
-            .copy_from_slice(signature.as_ref());
+        let mut data = self.0.try_borrow_mut_data()?;
+        let record =
+            data.get_mut(range).ok_or(ProgramError::AccountDataTooSmall)?;
+        let (hash, tail) = record.split_at_mut(SigHash::SIZE);
+        hash.copy_from_slice(signature.as_ref());
+        if layout == Layout::FullEntry {
+            tail.copy_from_slice(&magic.to_bytes());
+        }
         Ok(())
     }
 }
 
+/// Iterator over signature hashes stored in a signatures account.
+///
+/// Created by [`SignaturesAccount::iter`].
+pub struct SigHashIter<'a> {
+    data: core::cell::Ref<'a, [u8]>,
+    size: usize,
+    range: core::ops::Range<usize>,
+}
+
+impl SigHashIter<'_> {
+    fn get(&self, idx: usize) -> SigHash {
+        let start = idx * self.size;
+        SigHash(self.data[start..start + SigHash::SIZE].try_into().unwrap())
+    }
+}
+
+impl core::iter::Iterator for SigHashIter<'_> {
+    type Item = SigHash;
+
+    fn next(&mut self) -> Option<SigHash> {
+        self.range.next().map(|idx| self.get(idx))
+    }
+
+    fn nth(&mut self, n: usize) -> Option<SigHash> {
+        self.range.nth(n).map(|idx| self.get(idx))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) { self.range.size_hint() }
+}
+
+impl core::iter::ExactSizeIterator for SigHashIter<'_> {
+    fn len(&self) -> usize { self.range.len() }
+}
+
+impl core::iter::DoubleEndedIterator for SigHashIter<'_> {
+    fn next_back(&mut self) -> Option<SigHash> {
+        self.range.next_back().map(|idx| self.get(idx))
+    }
+}
+
+/// Sorted records stored in a signatures account.
+///
+/// See [`read_records`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Records<'a> {
+    /// Signature hashes of a regular account.
+    Plain(&'a [[u8; SigHash::SIZE]]),
+    /// Signature hashes each followed by magic of a full-entry account.
+    FullEntry(&'a [[u8; FULL_ENTRY_RECORD_SIZE]]),
+}
+
+impl Records<'_> {
+    /// Returns number of records.
+    pub(crate) fn len(&self) -> usize {
+        match self {
+            Self::Plain(records) => records.len(),
+            Self::FullEntry(records) => records.len(),
+        }
+    }
+
+    /// Returns signature hash of record at given index.
+    pub(crate) fn get(&self, idx: usize) -> Option<SigHash> {
+        match self {
+            Self::Plain(records) => records.get(idx).copied(),
+            Self::FullEntry(records) => records.get(idx).map(record_sighash),
+        }
+        .map(SigHash)
+    }
+
+    /// Binary searches the records for given signature hash.
+    ///
+    /// See [`slice::binary_search`].
+    pub(crate) fn binary_search(
+        &self,
+        sighash: &SigHash,
+    ) -> core::result::Result<usize, usize> {
+        match self {
+            Self::Plain(records) => records.binary_search(&sighash.0),
+            Self::FullEntry(records) => records.binary_search_by(|record| {
+                record_sighash(record).cmp(&sighash.0)
+            }),
+        }
+    }
+
+    /// Returns size of a single record.
+    fn record_size(&self) -> usize {
+        match self {
+            Self::Plain(_) => Layout::Plain.record_size(),
+            Self::FullEntry(_) => Layout::FullEntry.record_size(),
+        }
+    }
+}
+
+/// Returns signature hash of a full-entry record.
+fn record_sighash(
+    record: &[u8; FULL_ENTRY_RECORD_SIZE],
+) -> [u8; SigHash::SIZE] {
+    *stdx::split_array_ref::<{ SigHash::SIZE }, 4, FULL_ENTRY_RECORD_SIZE>(
+        record,
+    )
+    .0
+}
+
+/// Returns magic of a full-entry record.
+///
+/// Returns `None` if the stored bytes aren’t a valid magic.
+fn record_magic(record: &[u8; FULL_ENTRY_RECORD_SIZE]) -> Option<algo::Magic> {
+    let (_, magic) =
+        stdx::split_array_ref::<{ SigHash::SIZE }, 4, FULL_ENTRY_RECORD_SIZE>(
+            record,
+        );
+    algo::Magic::from_bytes(*magic)
+}
+
+/// Sorts first `count` records in `tail`, moves unique ones to the front and
+/// returns their number.
+///
+/// See [`SignaturesAccount::write_count_and_sort`].
+#[cfg(any(test, not(any(feature = "lib", feature = "client"))))]
+fn sort_and_dedup<const N: usize>(
+    tail: &mut [u8],
+    count: usize,
+) -> Result<usize> {
+    let entries = stdx::as_chunks_mut::<N, _>(tail)
+        .0
+        .get_mut(..count)
+        .ok_or(ProgramError::AccountDataTooSmall)?;
+    entries.sort_unstable();
+
+    // Move unique entries to the front.  `unique` is the number of unique
+    // entries found so far.
+    let mut unique = entries.len().min(1);
+    for idx in 1..entries.len() {
+        if entries[idx] != entries[unique - 1] {
+            entries[unique] = entries[idx];
+            unique += 1;
+        }
+    }
+    Ok(unique)
+}
+
+/// Removes record with given signature hash from the first `count` records in
+/// `tail` and returns whether it has been found.
+///
+/// See [`SignaturesAccount::remove_signature`].
+#[cfg(any(test, not(any(feature = "lib", feature = "client"))))]
+fn remove_record<const N: usize>(
+    tail: &mut [u8],
+    count: u32,
+    sighash: &SigHash,
+) -> Result<bool> {
+    let entries = stdx::as_chunks_mut::<N, _>(tail)
+        .0
+        .get_mut(..usize::try_from(count).unwrap())
+        .ok_or(ProgramError::InvalidAccountData)?;
+    let Ok(index) = entries
+        .binary_search_by(|entry| entry[..SigHash::SIZE].cmp(&sighash.0))
+    else {
+        return Ok(false);
+    };
+    entries.copy_within(index + 1.., index);
+    Ok(true)
+}
+
 /// Searches given account data for provided signature hash.
 ///
 /// Returns whether the signature has been found.  Returns an error if the
 /// account data is malformed.
 pub(crate) fn find_sighash(data: &[u8], signature: SigHash) -> Result<bool> {
-    let (head, tail) = stdx::split_at::<{ HEAD_SIZE }, _>(data)
-        .ok_or(ProgramError::AccountDataTooSmall)?;
-    let count = bytemuck::must_cast_ref::<_, Header>(head)
+    Ok(read_records(data)?.binary_search(&signature).is_ok())
+}
+
+/// Searches given account data for provided signature hash and returns its
+/// index.
+///
+/// Returns `None` if the signature hasn’t been found.  Returns an error if the
+/// account data is malformed.
+pub(crate) fn position_sighash(
+    data: &[u8],
+    signature: SigHash,
+) -> Result<Option<u32>> {
+    let records = read_records(data)?;
+    // Number of entries is stored in 24 bits so the index always fits u32.
+    Ok(records.binary_search(&signature).ok().map(|idx| idx as u32))
+}
+
+/// Returns sorted records stored in given account data.
+///
+/// Returns an error if the account data is malformed.
+pub(crate) fn read_records(data: &[u8]) -> Result<Records<'_>> {
+    let (header, layout, tail) = split_data(data)?;
+    let count = header
         .count()
         .try_into()
         .map_err(|_| ProgramError::InvalidAccountData)?;
-    let entries = stdx::as_chunks::<{ SigHash::SIZE }, _>(tail)
-        .0
-        .get(..count)
-        .ok_or(ProgramError::InvalidAccountData)?;
-    Ok(entries.binary_search(signature.as_ref()).is_ok())
+    let records = match layout {
+        Layout::Plain => stdx::as_chunks::<{ SigHash::SIZE }, _>(tail)
+            .0
+            .get(..count)
+            .map(Records::Plain),
+        Layout::FullEntry => stdx::as_chunks::<FULL_ENTRY_RECORD_SIZE, _>(tail)
+            .0
+            .get(..count)
+            .map(Records::FullEntry),
+    };
+    records.ok_or(ProgramError::InvalidAccountData)
 }
 
 
+/// Creates an account over given data for use in tests.
+///
+/// The account is neither signer nor writable.  Tests which need different
+/// flags can override them with struct update syntax.
+#[cfg(test)]
+pub(crate) fn test_account<'a>(
+    key: &'a Pubkey,
+    owner: &'a Pubkey,
+    lamports: &'a mut u64,
+    data: &'a mut [u8],
+) -> AccountInfo<'a> {
+    AccountInfo::new(key, false, false, lamports, data, owner, false, 42)
+}
+
 #[test]
 fn test_ed25519() {
     use algo::Algorithm;
@@ -245,16 +1513,7 @@ fn test_ed25519() {
     let owner = Pubkey::new_unique();
     let mut lamports: u64 = 42;
 
-    let account = AccountInfo {
-        key: &key,
-        lamports: alloc::rc::Rc::new(core::cell::RefCell::new(&mut lamports)),
-        data: alloc::rc::Rc::new(core::cell::RefCell::new(&mut data[..])),
-        owner: &owner,
-        rent_epoch: 42,
-        is_signer: false,
-        is_writable: false,
-        executable: false,
-    };
+    let account = test_account(&key, &owner, &mut lamports, &mut data);
     let signatures =
         SignaturesAccount::new_checked_owner(&account, &owner).unwrap();
 
@@ -275,14 +1534,16 @@ fn test_ed25519() {
     assert_eq!(yes, signatures.find(MAGIC, &[11; 32], &[12; 64], b"FOO"));
     assert_eq!(yes, signatures.find(MAGIC, &[21; 32], &[22; 64], b"bar"));
 
-    signatures.write_signature(1, &sig3, || panic!()).unwrap();
+    signatures
+        .write_signature(1, algo::Ed25519::MAGIC, &sig3, || panic!())
+        .unwrap();
     assert_eq!(yes, signatures.find(MAGIC, &[11; 32], &[12; 64], b"FOO"));
     assert_eq!(nah, signatures.find(MAGIC, &[21; 32], &[22; 64], b"bar"));
     assert_eq!(yes, signatures.find(MAGIC, &[31; 32], &[32; 64], b"qux"));
 
     let mut new_data = [0u8; 108];
     signatures
-        .write_signature(2, &sig2, || {
+        .write_signature(2, algo::Ed25519::MAGIC, &sig2, || {
             let mut data = signatures.try_borrow_mut_data().unwrap();
             new_data[..data.len()].copy_from_slice(&data);
             *data = &mut new_data[..];
@@ -302,3 +1563,780 @@ fn test_ed25519() {
     assert_eq!(Ok(0), signatures.read_count(Some(0)));
     assert_eq!(Ok(3), signatures.read_count(Some(2)));
 }
+
+/// Returns three Ed25519 signature hashes in ascending order.
+#[cfg(test)]
+fn test_sighashes() -> [SigHash; 3] {
+    use algo::Algorithm;
+
+    let sig1 = algo::Ed25519::sighash(&[11; 32], &[12; 64], b"FOO");
+    let sig2 = algo::Ed25519::sighash(&[21; 32], &[22; 64], b"bar");
+    let sig3 = algo::Ed25519::sighash(&[31; 32], &[32; 64], b"qux");
+    assert!(sig1.0 < sig2.0);
+    assert!(sig2.0 < sig3.0);
+    [sig1, sig2, sig3]
+}
+
+/// Returns account data with space for two entries holding `sig1` and `sig2`
+/// and a zero count.
+#[cfg(test)]
+fn test_data(sig1: &SigHash, sig2: &SigHash) -> [u8; 76] {
+    let mut data = [0; 76];
+    data[12..44].copy_from_slice(&sig1.0);
+    data[44..].copy_from_slice(&sig2.0);
+    data
+}
+
+#[test]
+fn test_count_and_capacity() {
+    let [sig1, sig2, sig3] = test_sighashes();
+    let mut data = test_data(&sig1, &sig2);
+    let key = Pubkey::new_unique();
+    let mut lamports: u64 = 42;
+    let account = test_account(&key, &key, &mut lamports, &mut data);
+    let signatures = SignaturesAccount(&account);
+
+    assert_eq!(Ok(0), signatures.count());
+    assert_eq!(Ok(2), signatures.capacity());
+    assert_eq!(Ok(2), signatures.remaining());
+
+    assert_eq!(Ok((0, 2)), signatures.write_count_and_sort(None, 2));
+    assert_eq!(Ok(2), signatures.count());
+    assert_eq!(Ok(2), signatures.capacity());
+    assert_eq!(Ok(0), signatures.remaining());
+
+    let mut new_data = [0u8; 108];
+    signatures
+        .write_signature(2, algo::Ed25519::MAGIC, &sig3, || {
+            let mut data = signatures.try_borrow_mut_data().unwrap();
+            new_data[..data.len()].copy_from_slice(&data);
+            *data = &mut new_data[..];
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(Ok(3), signatures.capacity());
+    assert_eq!(Ok(1), signatures.remaining());
+    assert_eq!(Ok((0, 3)), signatures.write_count_and_sort(None, 3));
+    assert_eq!(Ok(3), signatures.count());
+    assert_eq!(Ok(0), signatures.remaining());
+}
+
+#[test]
+fn test_iter_and_bounds() {
+    let [sig1, sig2, _] = test_sighashes();
+    let mut data = test_data(&sig1, &sig2);
+    let key = Pubkey::new_unique();
+    let mut lamports: u64 = 42;
+    let account = test_account(&key, &key, &mut lamports, &mut data);
+    let signatures = SignaturesAccount(&account);
+
+    assert_eq!(0, signatures.iter().unwrap().len());
+    assert_eq!(Ok(None), signatures.min_sighash());
+    assert_eq!(Ok(None), signatures.max_sighash());
+    assert_eq!(Ok([0; 32]), signatures.merkle_root());
+
+    signatures.write_count_and_sort(None, 2).unwrap();
+    assert_eq!(
+        vec![sig1, sig2],
+        signatures.iter().unwrap().collect::<Vec<_>>()
+    );
+    assert_eq!(Ok(Some(sig1)), signatures.min_sighash());
+    assert_eq!(Ok(Some(sig2)), signatures.max_sighash());
+    assert_eq!(
+        Ok(crate::merkle::root(&[sig1, sig2])),
+        signatures.merkle_root()
+    );
+}
+
+#[test]
+fn test_position() {
+    const MAGIC: algo::Magic = algo::Ed25519::MAGIC;
+
+    let [sig1, sig2, _] = test_sighashes();
+    let mut data = test_data(&sig1, &sig2);
+    let key = Pubkey::new_unique();
+    let mut lamports: u64 = 42;
+    let account = test_account(&key, &key, &mut lamports, &mut data);
+    let signatures = SignaturesAccount(&account);
+    let position = |pubkey, signature, message| {
+        signatures.position(MAGIC, pubkey, signature, message)
+    };
+
+    assert_eq!(Ok(None), position(&[11; 32], &[12; 64], b"FOO"));
+
+    signatures.write_count_and_sort(None, 2).unwrap();
+    assert_eq!(Ok(Some(0)), position(&[11; 32], &[12; 64], b"FOO"));
+    assert_eq!(Ok(Some(1)), position(&[21; 32], &[22; 64], b"bar"));
+    assert_eq!(Ok(None), position(&[31; 32], &[32; 64], b"qux"));
+}
+
+#[test]
+fn test_find_hash_and_many() {
+    const MAGIC: algo::Magic = algo::Ed25519::MAGIC;
+
+    let [sig1, sig2, sig3] = test_sighashes();
+    let mut data = test_data(&sig1, &sig3);
+    let key = Pubkey::new_unique();
+    let mut lamports: u64 = 42;
+    let account = test_account(&key, &key, &mut lamports, &mut data);
+    let signatures = SignaturesAccount(&account);
+    signatures.write_count_and_sort(None, 2).unwrap();
+
+    assert_eq!(Ok(true), signatures.find_hash(&sig1));
+    assert_eq!(Ok(false), signatures.find_hash(&sig2));
+    assert_eq!(Ok(true), signatures.find_hash(&sig3));
+    assert_eq!(
+        Ok(vec![true, false, true, false]),
+        signatures.find_many(&[
+            (MAGIC, &[11; 32], &[12; 64], b"FOO"),
+            (MAGIC, &[21; 32], &[22; 64], b"bar"),
+            (MAGIC, &[31; 32], &[32; 64], b"qux"),
+            (algo::Secp256k1::MAGIC, &[31; 32], &[32; 64], b"qux"),
+        ])
+    );
+    assert_eq!(Ok(Vec::new()), signatures.find_many(&[]));
+}
+
+#[test]
+fn test_read_epoch_and_count() {
+    let [sig1, sig2, _] = test_sighashes();
+    let mut data = test_data(&sig1, &sig2);
+    let key = Pubkey::new_unique();
+    let mut lamports: u64 = 42;
+    let account = test_account(&key, &key, &mut lamports, &mut data);
+    let signatures = SignaturesAccount(&account);
+
+    assert_eq!(Ok((0, 0)), signatures.read_epoch_and_count());
+    assert_eq!(Ok((0, 1)), signatures.write_count_and_sort(None, 1));
+    assert_eq!(Ok((0, 1)), signatures.read_epoch_and_count());
+    assert_eq!(Ok((2, 2)), signatures.write_count_and_sort(Some(2), 2));
+    assert_eq!(Ok((2, 2)), signatures.read_epoch_and_count());
+}
+
+#[test]
+fn test_write_signature_bounds() {
+    type SA<'a, 'info> = SignaturesAccount<'a, 'info>;
+
+    assert_eq!(327_679, MAX_SIGNATURES_PER_ACCOUNT);
+    assert_eq!(MAX_SIGNATURES_PER_ACCOUNT, SA::MAX_SIGNATURES);
+    assert!(
+        SA::size_for(SA::MAX_SIGNATURES) <=
+            solana_system_interface::MAX_PERMITTED_DATA_LENGTH as usize
+    );
+    assert!(
+        SA::size_for(SA::MAX_SIGNATURES + 1) >
+            solana_system_interface::MAX_PERMITTED_DATA_LENGTH as usize
+    );
+
+    let mut data = [0; 44];
+    let key = Pubkey::new_unique();
+    let mut lamports: u64 = 42;
+    let account = test_account(&key, &key, &mut lamports, &mut data);
+    let signatures = SignaturesAccount(&account);
+    let sighash = SigHash([1; 32]);
+    let err = Err(ProgramError::AccountDataTooSmall);
+
+    // The last index which fits in an account.  The account is too small so
+    // `enlarge` is called but since it doesn’t resize the account, the write
+    // fails.
+    let mut called = false;
+    let res = signatures.write_signature(
+        SA::MAX_SIGNATURES - 1,
+        algo::Ed25519::MAGIC,
+        &sighash,
+        || {
+            called = true;
+            Ok(())
+        },
+    );
+    assert!(called);
+    assert_eq!(err, res);
+
+    // Indexes which would never fit are rejected upfront.
+    let full = Err(AccountError::AccountFull.into());
+    for index in [SA::MAX_SIGNATURES, u32::MAX / 32 + 1, u32::MAX] {
+        assert_eq!(
+            full,
+            signatures.write_signature(
+                index,
+                algo::Ed25519::MAGIC,
+                &sighash,
+                || panic!()
+            )
+        );
+    }
+
+    assert_eq!(
+        Ok(()),
+        signatures.write_signature(
+            0,
+            algo::Ed25519::MAGIC,
+            &sighash,
+            || panic!()
+        )
+    );
+}
+
+#[test]
+fn test_size_for() {
+    type SA<'a, 'info> = SignaturesAccount<'a, 'info>;
+
+    assert_eq!(16, SA::size_for(0));
+    assert_eq!(48, SA::size_for(1));
+    assert_eq!(16 + 32 * 1000, SA::size_for(1000));
+    assert_eq!(16, SA::size_for_full_entry(0));
+    assert_eq!(16 + 36 * 1000, SA::size_for_full_entry(1000));
+    assert!(
+        SA::size_for_full_entry(MAX_FULL_ENTRY_SIGNATURES_PER_ACCOUNT) <=
+            solana_system_interface::MAX_PERMITTED_DATA_LENGTH as usize
+    );
+    assert!(
+        SA::size_for_full_entry(MAX_FULL_ENTRY_SIGNATURES_PER_ACCOUNT + 1) >
+            solana_system_interface::MAX_PERMITTED_DATA_LENGTH as usize
+    );
+
+    let entry = Entry { signature: &[0; 64], pubkey: &[0; 32], message: b"" };
+    assert_eq!(16, SA::required_account_size(&[]));
+    assert_eq!(80, SA::required_account_size(&[entry, entry]));
+
+    let rent = Rent::default();
+    assert_eq!(rent.minimum_balance(80), SA::required_rent(&[entry; 2], &rent));
+}
+
+#[test]
+fn test_length_prefixed() {
+    const MAGIC: algo::Magic = algo::Ed25519::MAGIC;
+    let new = |msg| SigHash::new(MAGIC, &[1; 32], &[2; 64], msg);
+    let pre =
+        |msg| SigHash::new_length_prefixed(MAGIC, &[1; 32], &[2; 64], msg);
+
+    assert_ne!(new(b"foo"), new(b"foobar"));
+    assert_ne!(pre(b"foo"), pre(b"foobar"));
+    assert_ne!(new(b"foo"), pre(b"foo"));
+    assert_ne!(new(b""), pre(b""));
+
+    // The length isn’t simply prepended to the message.
+    let prefixed = [&3u64.to_le_bytes()[..], b"foo"].concat();
+    assert_ne!(new(&prefixed), pre(b"foo"));
+
+    let entry =
+        Entry { signature: &[2; 64], pubkey: &[1; 32], message: b"foo" };
+    assert_eq!(pre(b"foo"), SigHash::from_entry_length_prefixed(MAGIC, entry));
+}
+
+#[test]
+fn test_hash_backend() {
+    fn check<B: HashBackend>() {
+        // SHA-256 test vectors from FIPS 180-2.
+        assert_eq!(
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            hex(&B::hashv(&[b"a", b"", b"bc"]))
+        );
+        assert_eq!(
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+            hex(&B::hashv(&[
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+            ]))
+        );
+
+        const MAGIC: algo::Magic = algo::Ed25519::MAGIC;
+        for message in [&b""[..], b"foo", &[42; 1000]] {
+            assert_eq!(
+                SigHash::new(MAGIC, &[1; 32], &[2; 64], message),
+                SigHash::new_with_backend::<B>(
+                    MAGIC, &[1; 32], &[2; 64], message
+                )
+            );
+        }
+    }
+
+    fn hex(bytes: &[u8]) -> alloc::string::String {
+        bytes.iter().map(|byte| alloc::format!("{byte:02x}")).collect()
+    }
+
+    check::<SolanaHashBackend>();
+    #[cfg(feature = "sha2")]
+    check::<Sha2HashBackend>();
+}
+
+#[test]
+fn test_solana_hash() {
+    let hash = solana_program::hash::hash(b"foo");
+    let sighash = SigHash::from(hash);
+    assert_eq!(hash.to_bytes(), <[u8; 32]>::from(sighash));
+    assert_eq!(hash, solana_program::hash::Hash::from(sighash));
+}
+
+#[test]
+fn test_hashed() {
+    const MAGIC: algo::Magic = algo::Ed25519::MAGIC;
+    let message = [42; 1000];
+    let hash = solana_native_sigverify::hash_message(&message);
+    let entry = Entry::with_message_hash(&[2; 64], &[1; 32], &hash);
+
+    let want = SigHash::new_hashed(MAGIC, &[1; 32], &[2; 64], &message);
+    assert_eq!(
+        want,
+        SigHash::from_message_hash(MAGIC, &[1; 32], &[2; 64], &hash)
+    );
+    assert_ne!(want, SigHash::from_entry(MAGIC, entry));
+    assert_ne!(want, SigHash::new(MAGIC, &[1; 32], &[2; 64], &message));
+}
+
+#[test]
+fn test_secp256k1() {
+    const MAGIC: algo::Magic = algo::Secp256k1::MAGIC;
+    let mut signature = [2; 65];
+    signature[64] = 1;
+    let entry = solana_native_sigverify::Secp256k1Entry {
+        signature: &[2; 64],
+        recovery_id: 1,
+        eth_address: &[3; 20],
+        message: b"foo",
+    };
+    let want = SigHash::new_secp256k1(MAGIC, &[3; 20], &signature, b"foo");
+    assert_eq!(want, SigHash::from_secp256k1_entry(MAGIC, entry));
+
+    let mut preimage = vec![0, 0, 0, 0, 1];
+    preimage.extend_from_slice(&MAGIC.to_bytes());
+    preimage.extend_from_slice(&[3; 20]);
+    preimage.extend_from_slice(&signature);
+    preimage.extend_from_slice(b"foo");
+    assert_eq!(
+        SigHash::from(solana_program::hash::hash(&preimage).to_bytes()),
+        want
+    );
+
+    // Without domain separation, the Ethereum address and signature could
+    // absorb the public key and the beginning of the signature of a plain
+    // SigHash with the rest of the signature moved into the message.
+    let pubkey = [4; 32];
+    let plain = [5; 64];
+    let mut signature = [0; 65];
+    signature[..12].copy_from_slice(&pubkey[20..]);
+    signature[12..].copy_from_slice(&plain[..53]);
+    let message = [&plain[53..], b"foo"].concat();
+    assert_ne!(
+        SigHash::new(MAGIC, &pubkey, &plain, b"foo"),
+        SigHash::new_secp256k1(
+            MAGIC,
+            pubkey[..20].try_into().unwrap(),
+            &signature,
+            &message
+        )
+    );
+}
+
+#[test]
+fn test_new_batch() {
+    const MAGIC: algo::Magic = algo::Ed25519::MAGIC;
+    let entries = [
+        Entry { signature: &[1; 64], pubkey: &[2; 32], message: b"foo" },
+        Entry { signature: &[3; 64], pubkey: &[4; 32], message: b"" },
+        Entry { signature: &[5; 64], pubkey: &[2; 32], message: b"bar" },
+    ];
+    let want = entries
+        .iter()
+        .map(|entry| SigHash::from_entry(MAGIC, *entry))
+        .collect::<Vec<_>>();
+    assert_eq!(want, SigHash::new_batch(MAGIC, &entries));
+    assert_eq!(
+        want.iter().rev().copied().collect::<Vec<_>>(),
+        SigHash::batch_iter(MAGIC, &entries).rev().collect::<Vec<_>>()
+    );
+    assert_eq!(Vec::<SigHash>::new(), SigHash::new_batch(MAGIC, &[]));
+}
+
+#[test]
+fn test_const_bytes() {
+    const HASHES: [SigHash; 2] =
+        [SigHash::from_bytes([1; 32]), SigHash::from_bytes([2; 32])];
+    const BYTES: [u8; 32] = HASHES[1].to_bytes();
+    assert_eq!(SigHash::from([1; 32]), HASHES[0]);
+    assert_eq!([2; 32], BYTES);
+}
+
+#[test]
+fn test_display_from_str() {
+    let mut bytes = [0; 32];
+    bytes[0] = 0x0f;
+    bytes[1] = 0xa0;
+    bytes[31] = 0xff;
+    let hash = SigHash::from(bytes);
+    let want =
+        "0fa00000000000000000000000000000000000000000000000000000000000ff";
+    let got = hash.to_string();
+    assert_eq!(want, got);
+    assert_eq!(Ok(hash), got.parse());
+    assert_eq!(Ok(hash), got.to_uppercase().parse());
+
+    assert_eq!(Err(ParseSigHashError), "".parse::<SigHash>());
+    assert_eq!(Err(ParseSigHashError), got[1..].parse::<SigHash>());
+    assert_eq!(Err(ParseSigHashError), (got.clone() + "0").parse::<SigHash>());
+    let bad = got.replacen('0', "g", 1);
+    assert_eq!(Err(ParseSigHashError), bad.parse::<SigHash>());
+    let bad = got.replacen('0', "+", 1);
+    assert_eq!(Err(ParseSigHashError), bad.parse::<SigHash>());
+}
+
+#[test]
+fn test_write_count_and_sort_dedup() {
+    let sighash = |n| [n; 32];
+    let mut data = [0; 12 + 6 * 32];
+    for (idx, n) in [3, 1, 3, 2, 1, 3].into_iter().enumerate() {
+        data[12 + idx * 32..][..32].copy_from_slice(&sighash(n));
+    }
+
+    let key = Pubkey::new_unique();
+    let owner = Pubkey::new_unique();
+    let mut lamports: u64 = 42;
+    let account = test_account(&key, &owner, &mut lamports, &mut data);
+    let signatures =
+        SignaturesAccount::new_checked_owner(&account, &owner).unwrap();
+
+    assert_eq!(Ok((0, 3)), signatures.write_count_and_sort(None, 6));
+    assert_eq!(Ok(3), signatures.count());
+    let want = [1, 2, 3].map(|n| SigHash(sighash(n)));
+    assert_eq!(&want[..], signatures.iter().unwrap().collect::<Vec<_>>());
+
+    // Appending a duplicate doesn’t increase the count.
+    signatures
+        .write_signature(3, algo::Ed25519::MAGIC, &want[1], || panic!())
+        .unwrap();
+    assert_eq!(Ok((0, 3)), signatures.write_count_and_sort(None, 4));
+    assert_eq!(Ok(3), signatures.count());
+    assert_eq!(&want[..], signatures.iter().unwrap().collect::<Vec<_>>());
+
+    signatures.write_count_and_sort(None, 0).unwrap();
+    assert_eq!(Ok(0), signatures.count());
+}
+
+#[test]
+fn test_write_count_and_sort_epoch_change() {
+    let old = [[5; 32], [6; 32], [7; 32]].map(SigHash);
+    let new = [[9; 32], [1; 32]].map(SigHash);
+    let mut data = [0; 12 + 3 * 32];
+
+    let key = Pubkey::new_unique();
+    let owner = Pubkey::new_unique();
+    let mut lamports: u64 = 42;
+    let account = test_account(&key, &owner, &mut lamports, &mut data);
+    let signatures = SignaturesAccount(&account);
+    let contains =
+        |sighash: &SigHash| find_sighash(&account.data.borrow(), *sighash);
+
+    // Mimic what Update operation does: read count for the epoch, write new
+    // signatures after it and then sort.
+    let write = |epoch, sighashes: &[SigHash]| {
+        let count = signatures.read_count(Some(epoch)).unwrap();
+        for (idx, sighash) in sighashes.iter().enumerate() {
+            let idx = count + idx as u32;
+            signatures
+                .write_signature(
+                    idx,
+                    algo::Ed25519::MAGIC,
+                    sighash,
+                    || panic!(),
+                )
+                .unwrap();
+        }
+        let count = count + sighashes.len() as u32;
+        signatures.write_count_and_sort(Some(epoch), count).unwrap();
+    };
+
+    write(1, &old);
+    assert_eq!(Ok(1), signatures.epoch());
+    assert_eq!(Ok(3), signatures.count());
+    for sighash in old.iter() {
+        assert_eq!(Ok(true), contains(sighash));
+    }
+
+    // New epoch resets the count.  The last old entry is left in the account
+    // data past the count but mustn’t be found.
+    write(2, &new);
+    assert_eq!(Ok(2), signatures.epoch());
+    assert_eq!(Ok(2), signatures.count());
+    assert_eq!(old[2].0, account.data.borrow()[12 + 2 * 32..]);
+    for sighash in old.iter() {
+        assert_eq!(Ok(false), contains(sighash));
+    }
+    for sighash in new.iter() {
+        assert_eq!(Ok(true), contains(sighash));
+    }
+    let want = vec![new[1], new[0]];
+    assert_eq!(want, signatures.iter().unwrap().collect::<Vec<_>>());
+}
+
+#[test]
+fn test_remove_signature() {
+    let sighashes = [[1; 32], [2; 32], [3; 32]].map(SigHash);
+    let mut data = [0; 12 + 3 * 32];
+    for (dst, sighash) in data[12..].chunks_exact_mut(32).zip(&sighashes) {
+        dst.copy_from_slice(&sighash.0);
+    }
+    data[8] = 3;
+
+    let key = Pubkey::new_unique();
+    let owner = Pubkey::new_unique();
+    let mut lamports: u64 = 42;
+    let account = test_account(&key, &owner, &mut lamports, &mut data);
+    let signatures = SignaturesAccount(&account);
+    let stored = || signatures.iter().unwrap().collect::<Vec<_>>();
+
+    assert_eq!(Ok(false), signatures.remove_signature(&SigHash([4; 32])));
+    assert_eq!(sighashes.to_vec(), stored());
+
+    assert_eq!(Ok(true), signatures.remove_signature(&sighashes[1]));
+    assert_eq!(vec![sighashes[0], sighashes[2]], stored());
+    assert_eq!(Ok(false), signatures.remove_signature(&sighashes[1]));
+
+    assert_eq!(Ok(true), signatures.remove_signature(&sighashes[2]));
+    assert_eq!(Ok(true), signatures.remove_signature(&sighashes[0]));
+    assert_eq!(Ok(0), signatures.count());
+    assert_eq!(Ok(false), signatures.remove_signature(&sighashes[0]));
+}
+
+#[test]
+fn test_full_entry() {
+    const ED25519: algo::Magic = algo::Ed25519::MAGIC;
+    const SECP256K1: algo::Magic = algo::Secp256k1::MAGIC;
+
+    let sighashes = [[1; 32], [2; 32], [3; 32]].map(SigHash);
+    let mut data = [0; 16 + 4 * 36];
+    let key = Pubkey::new_unique();
+    let mut lamports: u64 = 42;
+    let account = test_account(&key, &key, &mut lamports, &mut data);
+    let signatures = SignaturesAccount(&account);
+    let stored = || signatures.iter().unwrap().collect::<Vec<_>>();
+
+    signatures.write_version(true).unwrap();
+    assert_eq!(Ok(true), signatures.is_full_entry());
+    assert_eq!(Ok(4), signatures.capacity());
+
+    // Records are written out of order with a duplicate and sorted by the
+    // signature hash.
+    let magics = [SECP256K1, ED25519, SECP256K1, ED25519];
+    for (idx, (sighash, magic)) in
+        [2, 0, 2, 1].map(|idx| sighashes[idx]).iter().zip(magics).enumerate()
+    {
+        signatures
+            .write_signature(idx as u32, magic, sighash, || panic!())
+            .unwrap();
+    }
+    assert_eq!(Ok((0, 3)), signatures.write_count_and_sort(None, 4));
+    assert_eq!(sighashes.to_vec(), stored());
+    assert_eq!(Ok(Some(sighashes[0])), signatures.min_sighash());
+    assert_eq!(Ok(Some(sighashes[2])), signatures.max_sighash());
+    let data = account.try_borrow_data().unwrap();
+    assert_eq!(Ok(Some(1)), position_sighash(*data, sighashes[1]));
+    drop(data);
+    assert_eq!(Ok(true), signatures.find_hash(&sighashes[2]));
+    assert_eq!(Ok(false), signatures.find_hash(&SigHash([4; 32])));
+    assert_eq!(Ok(crate::merkle::root(&sighashes)), signatures.merkle_root());
+
+    // Magic is stored next to each signature.
+    assert_eq!(Ok(Some(ED25519)), signatures.magic_of(&sighashes[0]));
+    assert_eq!(Ok(Some(ED25519)), signatures.magic_of(&sighashes[1]));
+    assert_eq!(Ok(Some(SECP256K1)), signatures.magic_of(&sighashes[2]));
+    assert_eq!(Ok(None), signatures.magic_of(&SigHash([4; 32])));
+
+    // Removing a signature keeps the magic of the others.
+    assert_eq!(Ok(true), signatures.remove_signature(&sighashes[0]));
+    assert_eq!(sighashes[1..].to_vec(), stored());
+    assert_eq!(Ok(Some(SECP256K1)), signatures.magic_of(&sighashes[2]));
+
+    // Clearing by magic removes only signatures of that algorithm.
+    assert_eq!(Ok(1), signatures.clear_magic(ED25519));
+    assert_eq!(vec![sighashes[2]], stored());
+    assert_eq!(Ok(0), signatures.clear_magic(ED25519));
+    assert_eq!(Ok(1), signatures.clear_magic(SECP256K1));
+    assert_eq!(Ok(0), signatures.count());
+
+    // Full-entry accounts hold fewer signatures.
+    let full = Err(AccountError::AccountFull.into());
+    let index = MAX_FULL_ENTRY_SIGNATURES_PER_ACCOUNT;
+    assert!(index < MAX_SIGNATURES_PER_ACCOUNT);
+    assert_eq!(
+        full,
+        signatures.write_signature(index, ED25519, &sighashes[0], || panic!())
+    );
+}
+
+#[test]
+fn test_magic_not_stored() {
+    let mut data = test_data(&SigHash([1; 32]), &SigHash([2; 32]));
+    data[8] = 2;
+    let key = Pubkey::new_unique();
+    let mut lamports: u64 = 42;
+    let account = test_account(&key, &key, &mut lamports, &mut data);
+    let signatures = SignaturesAccount(&account);
+
+    let err = ProgramError::from(AccountError::MagicNotStored);
+    assert_eq!(Ok(false), signatures.is_full_entry());
+    assert_eq!(Err(err.clone()), signatures.magic_of(&SigHash([1; 32])));
+    assert_eq!(Err(err), signatures.clear_magic(algo::Ed25519::MAGIC));
+    assert_eq!(Ok(2), signatures.count());
+}
+
+#[test]
+fn test_count_in_data() {
+    let mut data = [0; 12];
+    assert_eq!(
+        Err(ProgramError::AccountDataTooSmall),
+        count_in_data(&data[..11])
+    );
+    assert_eq!(Ok(0), count_in_data(&data));
+    data[8..11].copy_from_slice(&[1, 2, 3]);
+    assert_eq!(Ok(0x030201), count_in_data(&data));
+    data[11] = 2;
+    assert_eq!(
+        Err(AccountError::UnsupportedVersion.into()),
+        count_in_data(&data)
+    );
+
+    // Versioned accounts need the discriminator.
+    let mut data = [0; 16];
+    data[8..11].copy_from_slice(&[1, 2, 3]);
+    data[11] = ACCOUNT_VERSION;
+    assert_eq!(
+        Err(ProgramError::AccountDataTooSmall),
+        count_in_data(&data[..15])
+    );
+    assert_eq!(Err(ProgramError::InvalidAccountData), count_in_data(&data));
+    data[12..].copy_from_slice(b"sigv");
+    assert_eq!(Ok(0x030201), count_in_data(&data));
+}
+
+#[test]
+fn test_epoch_in_data() {
+    let mut data = [0; 12];
+    assert_eq!(
+        Err(ProgramError::AccountDataTooSmall),
+        epoch_in_data(&data[..11])
+    );
+    assert_eq!(Ok(0), epoch_in_data(&data));
+    data[..8].copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+    data[8] = 42;
+    assert_eq!(Ok(0x0807060504030201), epoch_in_data(&data));
+    data[11] = 2;
+    assert_eq!(
+        Err(AccountError::UnsupportedVersion.into()),
+        epoch_in_data(&data)
+    );
+
+    let mut data = [data.as_slice(), b"sigv"].concat();
+    data[11] = ACCOUNT_VERSION;
+    assert_eq!(Ok(0x0807060504030201), epoch_in_data(&data));
+    data[12] = 0;
+    assert_eq!(Err(ProgramError::InvalidAccountData), epoch_in_data(&data));
+}
+
+#[test]
+fn test_version() {
+    let mut data = [0xff; 48];
+    {
+        let key = Pubkey::new_unique();
+        let mut lamports: u64 = 42;
+        let account = test_account(&key, &key, &mut lamports, &mut data);
+        SignaturesAccount(&account).write_version(false).unwrap();
+    }
+    assert_eq!(Some(ACCOUNT_VERSION), account_version(&data));
+    assert_ne!(0, ACCOUNT_VERSION);
+    assert_eq!(b"sigv", &data[12..16]);
+    data[..11].fill(0);
+    assert_eq!(Ok(0), read_records(&data).map(|records| records.len()));
+
+    // Signature hashes follow the discriminator.
+    data[8] = 1;
+    assert_eq!(Ok(Records::Plain(&[[0xff; 32]])), read_records(&data));
+    {
+        let key = Pubkey::new_unique();
+        let mut lamports: u64 = 42;
+        let account = test_account(&key, &key, &mut lamports, &mut data);
+        let signatures = SignaturesAccount(&account);
+        assert_eq!(Ok(1), signatures.capacity());
+        signatures
+            .write_signature(
+                0,
+                algo::Ed25519::MAGIC,
+                &SigHash([1; 32]),
+                || panic!(),
+            )
+            .unwrap();
+        assert_eq!(Ok((0, 1)), signatures.write_count_and_sort(None, 1));
+    }
+    assert_eq!(Ok(Records::Plain(&[[1; 32]])), read_records(&data));
+
+    data[11] = 2;
+    assert_eq!(Some(2), account_version(&data));
+    assert_eq!(
+        Err(AccountError::UnsupportedVersion.into()),
+        read_records(&data).map(|records| records.len())
+    );
+}
+
+#[test]
+fn test_legacy_layout() {
+    // Legacy accounts have no discriminator and keep their layout when
+    // written to.
+    let mut data = [0; 12 + 32];
+    assert_eq!(None, account_version(&data[..11]));
+    assert_eq!(Some(0), account_version(&data));
+    assert_eq!(Ok(0), read_records(&data).map(|records| records.len()));
+    {
+        let key = Pubkey::new_unique();
+        let mut lamports: u64 = 42;
+        let account = test_account(&key, &key, &mut lamports, &mut data);
+        let signatures = SignaturesAccount(&account);
+        assert_eq!(Ok(1), signatures.capacity());
+        signatures
+            .write_signature(
+                0,
+                algo::Ed25519::MAGIC,
+                &SigHash([1; 32]),
+                || panic!(),
+            )
+            .unwrap();
+        assert_eq!(Ok((0, 1)), signatures.write_count_and_sort(None, 1));
+    }
+    assert_eq!(Some(0), account_version(&data));
+    assert_eq!(Ok(Records::Plain(&[[1; 32]])), read_records(&data));
+}
+
+#[test]
+fn test_legacy_reader() {
+    // Reader from before versioning was introduced.  It interprets the version
+    // byte as part of the count.
+    fn legacy_read(data: &[u8]) -> Result<&[[u8; 32]]> {
+        let (head, tail) = stdx::split_at::<12, u8>(data)
+            .ok_or(ProgramError::AccountDataTooSmall)?;
+        let count = u32::from_le_bytes(head[8..].try_into().unwrap());
+        stdx::as_chunks::<32, u8>(tail)
+            .0
+            .get(..usize::try_from(count).unwrap())
+            .ok_or(ProgramError::InvalidAccountData)
+    }
+
+    // Legacy accounts are read by both readers the same way.
+    let mut data = [1; 12 + 32];
+    data[..12].fill(0);
+    data[8] = 1;
+    assert_eq!(Ok(&[[1; 32]][..]), legacy_read(&data));
+    assert_eq!(legacy_read(&data).map(Records::Plain), read_records(&data));
+
+    // Versioned account is rejected by legacy reader rather than misread.  The
+    // reader sees a count of at least 2²⁴ which no account can hold.
+    const { assert!(1 << 24 > MAX_SIGNATURES_PER_ACCOUNT) };
+    let mut data = [1; 16 + 32];
+    data[..12].fill(0);
+    {
+        let key = Pubkey::new_unique();
+        let mut lamports: u64 = 42;
+        let account = test_account(&key, &key, &mut lamports, &mut data);
+        let signatures = SignaturesAccount(&account);
+        signatures.write_version(false).unwrap();
+        assert_eq!(Ok((0, 1)), signatures.write_count_and_sort(None, 1));
+    }
+    assert_eq!(Err(ProgramError::InvalidAccountData), legacy_read(&data));
+    assert_eq!(Ok(Records::Plain(&[[1; 32]])), read_records(&data));
+}
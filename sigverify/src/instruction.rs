@@ -1,4 +1,5 @@
 use core::num::NonZeroU16;
+use core::ops::Range;
 
 use solana_program::instruction::{AccountMeta, Instruction};
 use solana_program::program_error::ProgramError;
@@ -7,11 +8,33 @@ use solana_program::pubkey::Pubkey;
 type Result<T = (), E = ProgramError> = core::result::Result<T, E>;
 
 
+/// Returns address and bump of the signatures account for given payer and
+/// seed.
+///
+/// The signatures account is a PDA of the sigverify program with `[payer,
+/// seed]` seeds.  If the account uses a separate authority (see
+/// [`update_with_authority`]), the authority should be passed as `payer`.
+///
+/// Returns [`ProgramError::MaxSeedLengthExceeded`] if `seed` is longer than 31
+/// bytes.  The program doesn’t accept such seeds (it needs one seed slot for
+/// the bump) even though the PDA could be derived.
+pub fn signatures_account(
+    sigverify_program: &Pubkey,
+    payer: &Pubkey,
+    seed: &[u8],
+) -> Result<(Pubkey, u8)> {
+    check_seed(seed)?;
+    Ok(Pubkey::find_program_address(&[payer.as_ref(), seed], sigverify_program))
+}
+
 /// Creates an instruction calling Update operation of the sigverify program.
 ///
 /// For the instruction to work, it must be executed in a transaction with call
 /// to native signature verification program *directly* preceding it.  To create
-/// such instruction use [`solana_native_sigverify::new_instruction`].
+/// such instruction use [`solana_native_sigverify::new_instruction`].  If
+/// there are multiple consecutive calls to native signature verification
+/// programs directly preceding the instruction, signatures from all of them
+/// are aggregated.
 ///
 /// Together with the instruction, returns the signatures account address and
 /// bump.  The account is where the program will collect all the signatures.
@@ -30,17 +53,214 @@ pub fn update(
     seed: &[u8],
     epoch: Option<u64>,
 ) -> Result<(Instruction, Pubkey, u8)> {
-    let (account, bump) = Pubkey::find_program_address(
-        &[payer.as_ref(), seed],
-        &sigverify_program,
-    );
+    new_update(sigverify_program, payer, None, None, seed, epoch, None)
+}
+
+/// Creates an instruction calling Update operation of the sigverify program
+/// with a capacity hint.
+///
+/// This is like [`update`] except that it tells the program how many
+/// signatures the signatures account is expected to hold.  When creating or
+/// enlarging the account, the program sizes it to fit `capacity` signatures
+/// rather than growing it in 10 KiB steps.  For small batches this reduces
+/// rent paid for the account.  The hint doesn’t limit number of signatures the
+/// account can hold; if more are added, the account is grown as usual.
+pub fn update_with_capacity(
+    sigverify_program: Pubkey,
+    payer: Pubkey,
+    seed: &[u8],
+    epoch: Option<u64>,
+    capacity: u32,
+) -> Result<(Instruction, Pubkey, u8)> {
+    new_update(
+        sigverify_program,
+        payer,
+        None,
+        None,
+        seed,
+        epoch,
+        Some(capacity),
+    )
+}
+
+/// Creates an instruction calling Update operation of the sigverify program in
+/// strict mode.
+///
+/// This is like [`update`] except that the program fails if the instruction
+/// isn’t directly preceded by a call to a native signature verification
+/// program.  By default such Update succeeds without aggregating any
+/// signatures which may hide misconfigured transactions.
+pub fn update_strict(
+    sigverify_program: Pubkey,
+    payer: Pubkey,
+    seed: &[u8],
+    epoch: Option<u64>,
+) -> Result<(Instruction, Pubkey, u8)> {
+    let (mut instruction, account, bump) =
+        update(sigverify_program, payer, seed, epoch)?;
+    instruction.data[0] |= 0x20;
+    Ok((instruction, account, bump))
+}
+
+/// Creates an instruction calling Update operation of the sigverify program in
+/// hashed-message mode.
+///
+/// This is like [`update`] except that messages in the preceding native
+/// program calls are treated as hashes of the actual messages (see
+/// [`solana_native_sigverify::Entry::with_message_hash`]).  The program
+/// stores signatures with [`SigHash::from_message_hash`] so they can be looked
+/// up with [`SigHash::new_hashed`].  All messages must be 32-byte hashes and
+/// calls to the Secp256k1 program aren’t allowed.
+///
+/// [`SigHash::from_message_hash`]: crate::SigHash::from_message_hash
+/// [`SigHash::new_hashed`]: crate::SigHash::new_hashed
+pub fn update_hashed(
+    sigverify_program: Pubkey,
+    payer: Pubkey,
+    seed: &[u8],
+    epoch: Option<u64>,
+) -> Result<(Instruction, Pubkey, u8)> {
+    let (mut instruction, account, bump) =
+        update(sigverify_program, payer, seed, epoch)?;
+    instruction.data[0] |= 0x10;
+    Ok((instruction, account, bump))
+}
+
+/// Creates an instruction calling Update operation of the sigverify program in
+/// length-prefixed mode.
+///
+/// This is like [`update`] except that the program stores signatures with
+/// [`SigHash::from_entry_length_prefixed`] so they can be looked up with
+/// [`SigHash::new_length_prefixed`].  Calls to the Secp256k1 program aren’t
+/// allowed.
+///
+/// [`SigHash::from_entry_length_prefixed`]: crate::SigHash::from_entry_length_prefixed
+/// [`SigHash::new_length_prefixed`]: crate::SigHash::new_length_prefixed
+pub fn update_length_prefixed(
+    sigverify_program: Pubkey,
+    payer: Pubkey,
+    seed: &[u8],
+    epoch: Option<u64>,
+) -> Result<(Instruction, Pubkey, u8)> {
+    let (mut instruction, account, bump) =
+        update(sigverify_program, payer, seed, epoch)?;
+    instruction.data[0] |= 0x08;
+    Ok((instruction, account, bump))
+}
+
+/// Creates an instruction calling Update operation of the sigverify program
+/// with a full-entry signatures account.
+///
+/// This is like [`update`] except that the signatures account stores magic
+/// next to each signature (see [`SignaturesAccount`]) which allows clearing
+/// signatures of a single algorithm with [`clear`].  If the signatures account
+/// already exists, it must be a full-entry account.
+///
+/// [`SignaturesAccount`]: crate::SignaturesAccount
+pub fn update_full_entry(
+    sigverify_program: Pubkey,
+    payer: Pubkey,
+    seed: &[u8],
+    epoch: Option<u64>,
+) -> Result<(Instruction, Pubkey, u8)> {
+    let (mut instruction, account, bump) =
+        update(sigverify_program, payer, seed, epoch)?;
+    instruction.data[0] |= 0x04;
+    Ok((instruction, account, bump))
+}
+
+/// Creates an instruction calling Update operation of the sigverify program
+/// with a separate authority.
+///
+/// This is like [`update`] except that the signatures account is a PDA of the
+/// `authority` rather than of the `payer`, i.e. it uses `[authority, seed]`
+/// seeds.  The authority must sign the transaction; typically it’s a PDA of
+/// another program which invokes the sigverify program through CPI.  The
+/// `payer` still pays for creation and enlargement of the account.
+pub fn update_with_authority(
+    sigverify_program: Pubkey,
+    payer: Pubkey,
+    authority: Pubkey,
+    seed: &[u8],
+    epoch: Option<u64>,
+) -> Result<(Instruction, Pubkey, u8)> {
+    new_update(
+        sigverify_program,
+        payer,
+        Some(authority),
+        None,
+        seed,
+        epoch,
+        None,
+    )
+}
+
+/// Creates an instruction calling Update operation of the sigverify program
+/// with explicitly given signatures account.
+///
+/// This is like [`update`] (or [`update_with_authority`] if `authority` is
+/// given) except that the address of the signatures account and its bump are
+/// provided by the caller rather than derived.  This is useful when the
+/// account is a fixed, known PDA (e.g. one derived from a multisig authority)
+/// which the caller has already computed.
+///
+/// The program still verifies that `account` is a PDA with `[authority, seed,
+/// &[bump]]` seeds (or `[payer, seed, &[bump]]` if `authority` is `None`) and
+/// rejects the instruction otherwise.  As with `update_with_authority`, the
+/// authority must sign the transaction.
+pub fn update_with_account(
+    sigverify_program: Pubkey,
+    payer: Pubkey,
+    authority: Option<Pubkey>,
+    account: Pubkey,
+    seed: &[u8],
+    bump: u8,
+    epoch: Option<u64>,
+) -> Result<Instruction> {
+    new_update(
+        sigverify_program,
+        payer,
+        authority,
+        Some((account, bump)),
+        seed,
+        epoch,
+        None,
+    )
+    .map(|(instruction, ..)| instruction)
+}
+
+fn new_update(
+    sigverify_program: Pubkey,
+    payer: Pubkey,
+    authority: Option<Pubkey>,
+    signatures_account: Option<(Pubkey, u8)>,
+    seed: &[u8],
+    epoch: Option<u64>,
+    capacity: Option<u32>,
+) -> Result<(Instruction, Pubkey, u8)> {
+    let seed_len = check_seed(seed)?;
+    let (account, bump) = match signatures_account {
+        Some(account) => account,
+        None => self::signatures_account(
+            &sigverify_program,
+            &authority.unwrap_or(payer),
+            seed,
+        )?,
+    };
 
     let data = {
-        let mut buf = [0; 40];
-        buf[1] = check_seed(seed)?;
+        // Tag, seed length, seed, bump, capacity and epoch.  check_seed
+        // guarantees the seed is shorter than MAX_SEED_LEN.
+        let mut buf = [0; { solana_program::pubkey::MAX_SEED_LEN + 14 }];
+        buf[0] = tag(0, authority) | if capacity.is_some() { 0x40 } else { 0 };
+        buf[1] = seed_len;
         buf[2..2 + seed.len()].copy_from_slice(seed);
         buf[2 + seed.len()] = bump;
         let mut len = 2 + seed.len() + 1;
+        if let Some(capacity) = capacity {
+            buf[len..len + 4].copy_from_slice(&capacity.to_le_bytes());
+            len += 4;
+        }
         if let Some(epoch) = epoch {
             buf[len..len + 8].copy_from_slice(&epoch.to_le_bytes());
             len += 8;
@@ -48,16 +268,12 @@ pub fn update(
         buf[..len].to_vec()
     };
 
-    let instruction = Instruction {
-        program_id: sigverify_program,
-        accounts: vec![
-            AccountMeta::new(payer, true),
-            AccountMeta::new(account, false),
-            AccountMeta::new(solana_program::sysvar::instructions::ID, false),
-            AccountMeta::new(solana_program::system_program::ID, false),
-        ],
-        data,
-    };
+    let accounts = new_accounts(payer, account, authority, [
+        AccountMeta::new(solana_program::sysvar::instructions::ID, false),
+        AccountMeta::new(solana_program::system_program::ID, false),
+    ]);
+    let instruction =
+        Instruction { program_id: sigverify_program, accounts, data };
 
     Ok((instruction, account, bump))
 }
@@ -65,15 +281,31 @@ pub fn update(
 
 /// Iterator generating Solana instructions calling the sigverify program
 /// filling given account with given data.
+///
+/// Each item is a list of instructions to be executed in a single transaction.
+/// By default, it consists of two instructions: call to the native signature
+/// verification program followed by call to the sigverify program.  Additional
+/// leading instructions can be added with [`Self::prefix_instructions`].
 pub struct UpdateIter<'a> {
     native_program: &'a Pubkey,
     sigverify_instruction: Instruction,
+    prefix: Vec<Instruction>,
     entries: &'a [solana_native_sigverify::Entry<'a>],
+    /// Index of the first of `entries` in the slice the iterator has been
+    /// created with.
+    offset: usize,
     seed_len: u8,
     max_data_size: NonZeroU16,
 }
 
 impl<'a> UpdateIter<'a> {
+    /// Smallest maximum data size accepted by [`Self::max_data_size`].
+    ///
+    /// This is size of instruction data with a single signature of an empty
+    /// message: two-byte header, 14-byte offsets, 64-byte signature and 32-byte
+    /// public key.
+    pub const MIN_DATA_SIZE: usize = 2 + 14 + 64 + 32;
+
     pub fn new(
         native_program: &'a Pubkey,
         sigverify_program: Pubkey,
@@ -89,14 +321,57 @@ impl<'a> UpdateIter<'a> {
         let mut this = Self {
             native_program,
             sigverify_instruction,
+            prefix: Vec::new(),
             entries,
+            offset: 0,
             seed_len,
             max_data_size: NonZeroU16::MIN,
         };
-        this.max_data_size(800);
+        this.max_data_size(800)?;
         Ok((this, account, bump))
     }
 
+    /// Sets instructions to include at the start of each generated transaction.
+    ///
+    /// The instructions are cloned into each item yielded by the iterator
+    /// before the call to the native signature verification program.  This can
+    /// be used to include a compute budget instruction in each transaction such
+    /// that items are complete, submittable lists of instructions.  By default
+    /// no additional instructions are included.
+    ///
+    /// Note that [`Self::max_data_size`] doesn’t account for the prefix
+    /// instructions.  If they are large, the maximum data size may need to be
+    /// lowered so the transactions fit size limits.
+    pub fn prefix_instructions(&mut self, instructions: Vec<Instruction>) {
+        self.prefix = instructions;
+    }
+
+    /// Returns an iterator which, together with the instructions, yields
+    /// range of entries each group of instructions covers.
+    ///
+    /// The range indexes the `entries` slice the iterator has been created
+    /// with.  This allows the caller to correlate a failed transaction with
+    /// signatures it included and retry just those.
+    pub fn enumerate_entries(self) -> EnumerateEntries<'a> {
+        EnumerateEntries(self)
+    }
+
+    /// Returns number of transactions the iterator is going to generate.
+    ///
+    /// Grouping of signatures into transactions is deterministic so this
+    /// calculates number of remaining items without consuming the iterator.
+    /// This can be used to estimate duration or fees of the aggregation before
+    /// sending any transactions.
+    pub fn transaction_count(&self) -> usize {
+        let mut entries = self.entries;
+        let mut count = 0;
+        while !entries.is_empty() {
+            entries = &entries[self.batch_len(entries)..];
+            count += 1;
+        }
+        count
+    }
+
     /// Sets maximum signature verification native program instruction data
     /// size.
     ///
@@ -108,27 +383,67 @@ impl<'a> UpdateIter<'a> {
     /// The default value is on the safe side leaving enough space in the
     /// transaction to include Update instruction and additional instructions.
     ///
+    /// Length of the seed is subtracted from the limit (since the seed is
+    /// included in the Update instruction in the same transaction) and the
+    /// result must be at least [`Self::MIN_DATA_SIZE`].  Otherwise, no
+    /// instruction would fit the limit so the function returns
+    /// [`ProgramError::InvalidArgument`] and leaves the limit unchanged.
+    ///
     /// Note that the iterate will always output instruction with at least one
-    /// signature, even if that exceeds the limit.
-    pub fn max_data_size(&mut self, max_data_size: usize) {
+    /// signature, even if that exceeds the limit (which happens if message of
+    /// the signature is too long).
+    pub fn max_data_size(&mut self, max_data_size: usize) -> Result {
         let size = u16::try_from(max_data_size)
             .unwrap_or(u16::MAX)
             .saturating_sub(u16::from(self.seed_len));
+        if usize::from(size) < Self::MIN_DATA_SIZE {
+            return Err(ProgramError::InvalidArgument);
+        }
         self.max_data_size = NonZeroU16::new(size).unwrap_or(NonZeroU16::MIN);
+        Ok(())
     }
 }
 
 impl core::iter::Iterator for UpdateIter<'_> {
-    type Item = [solana_program::instruction::Instruction; 2];
+    type Item = Vec<Instruction>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        self.next_with_range().map(|(_, item)| item)
+    }
+}
+
+impl UpdateIter<'_> {
+    /// Returns the next group of instructions together with the range of
+    /// entries it covers.
+    fn next_with_range(&mut self) -> Option<(Range<usize>, Vec<Instruction>)> {
         if self.entries.is_empty() {
             return None;
         }
 
+        let count = self.batch_len(self.entries);
+        let native_instruction = solana_native_sigverify::new_instruction(
+            *self.native_program,
+            &self.entries[..count],
+        )
+        .unwrap();
+        self.entries = &self.entries[count..];
+        let range = self.offset..self.offset + count;
+        self.offset += count;
+        let mut instructions = Vec::with_capacity(self.prefix.len() + 2);
+        instructions.extend_from_slice(&self.prefix);
+        instructions.push(native_instruction);
+        instructions.push(self.sigverify_instruction.clone());
+        Some((range, instructions))
+    }
+
+    /// Returns number of leading `entries` to include in a single native
+    /// program instruction.
+    ///
+    /// Collects as many entries as fit in [`Self::max_data_size`] but always
+    /// at least one.
+    fn batch_len(&self, entries: &[solana_native_sigverify::Entry]) -> usize {
         let mut limit = usize::from(self.max_data_size.get()).saturating_sub(2);
-        let count = self
-            .entries
+        let count = entries
             .iter()
             .take_while(|entry| {
                 let size = 14 + 64 + 32 + entry.message.len();
@@ -139,18 +454,22 @@ impl core::iter::Iterator for UpdateIter<'_> {
                 true
             })
             .count();
-        let count = count.max(1);
-
-        let native_instruction = solana_native_sigverify::new_instruction(
-            *self.native_program,
-            &self.entries[..count],
-        )
-        .unwrap();
-        self.entries = &self.entries[count..];
-        Some([native_instruction, self.sigverify_instruction.clone()])
+        count.max(1)
     }
 }
 
+/// Iterator generating Solana instructions calling the sigverify program
+/// together with range of entries each group of instructions covers.
+///
+/// Created by [`UpdateIter::enumerate_entries`].
+pub struct EnumerateEntries<'a>(UpdateIter<'a>);
+
+impl core::iter::Iterator for EnumerateEntries<'_> {
+    type Item = (Range<usize>, Vec<Instruction>);
+
+    fn next(&mut self) -> Option<Self::Item> { self.0.next_with_range() }
+}
+
 /// Generates instruction data for Free operation.
 ///
 /// `seed` and `bump` specifies seed and bump of the signatures PDA.  Note that
@@ -165,9 +484,79 @@ pub fn free(
     signatures_account: Option<Pubkey>,
     seed: &[u8],
     bump: u8,
+) -> Result<Instruction> {
+    new_free(sigverify_program, payer, None, signatures_account, seed, bump)
+}
+
+/// Generates Free operation instructions for multiple signatures accounts.
+///
+/// Each element of `seeds_and_bumps` specifies seed and bump of a signatures
+/// PDA (see [`free`]).  Returns one instruction for each of the accounts in
+/// the same order.  Returns an error if any of the seeds is too long or
+/// doesn’t, together with the bump, form a valid PDA.
+pub fn free_many(
+    sigverify_program: Pubkey,
+    payer: Pubkey,
+    seeds_and_bumps: &[(&[u8], u8)],
+) -> Result<Vec<Instruction>> {
+    seeds_and_bumps
+        .iter()
+        .map(|&(seed, bump)| free(sigverify_program, payer, None, seed, bump))
+        .collect()
+}
+
+/// Generates instruction data for Free operation deriving the bump.
+///
+/// This is like [`free`] except that the address and bump of the signatures
+/// account are derived from the sigverify program id, Payer account and seed.
+/// This is useful when the bump returned by [`update`] hasn’t been stored.
+/// Together with the instruction, returns the signatures account address and
+/// bump.
+pub fn free_find(
+    sigverify_program: Pubkey,
+    payer: Pubkey,
+    seed: &[u8],
+) -> Result<(Instruction, Pubkey, u8)> {
+    let (account, bump) = signatures_account(&sigverify_program, &payer, seed)?;
+    let instruction =
+        new_free(sigverify_program, payer, None, Some(account), seed, bump)?;
+    Ok((instruction, account, bump))
+}
+
+/// Generates instruction data for Free operation with a separate authority.
+///
+/// This is like [`free`] except that the signatures account is a PDA of the
+/// `authority` rather than of the `payer` (see [`update_with_authority`]).  The
+/// authority must sign the transaction.  All lamports held by the signatures
+/// account are transferred to the `payer`.
+pub fn free_with_authority(
+    sigverify_program: Pubkey,
+    payer: Pubkey,
+    authority: Pubkey,
+    signatures_account: Option<Pubkey>,
+    seed: &[u8],
+    bump: u8,
+) -> Result<Instruction> {
+    new_free(
+        sigverify_program,
+        payer,
+        Some(authority),
+        signatures_account,
+        seed,
+        bump,
+    )
+}
+
+fn new_free(
+    sigverify_program: Pubkey,
+    payer: Pubkey,
+    authority: Option<Pubkey>,
+    signatures_account: Option<Pubkey>,
+    seed: &[u8],
+    bump: u8,
 ) -> Result<Instruction> {
     let mut buf = [0; { solana_program::pubkey::MAX_SEED_LEN + 2 }];
-    buf[0] = 1;
+    buf[0] = tag(1, authority);
     buf[1] = check_seed(seed)?;
     buf[2..seed.len() + 2].copy_from_slice(seed);
     buf[seed.len() + 2] = bump;
@@ -177,22 +566,237 @@ pub fn free(
         acc
     } else {
         Pubkey::create_program_address(
-            &[payer.as_ref(), seed, &[bump]],
+            &[authority.unwrap_or(payer).as_ref(), seed, &[bump]],
             &sigverify_program,
         )?
     };
 
+    let accounts = new_accounts(payer, account, authority, [AccountMeta::new(
+        solana_program::system_program::ID,
+        false,
+    )]);
     Ok(Instruction {
         program_id: sigverify_program,
-        accounts: vec![
-            AccountMeta::new(payer, true),
-            AccountMeta::new(account, false),
-            AccountMeta::new(solana_program::system_program::ID, false),
-        ],
+        accounts,
         data: data.to_vec(),
     })
 }
 
+/// Creates instructions calling Update and Free operations of the sigverify
+/// program for use in a single transaction.
+///
+/// Returns the Update and Free instructions together with the signatures
+/// account address and bump.  This is useful when signatures are aggregated
+/// and consumed within a single transaction, in which case the signatures
+/// account doesn’t need to outlive it.  The instructions must be placed in the
+/// transaction in the following order:
+///
+/// 1. calls to native signature verification programs (see [`update`]),
+/// 2. the returned Update instruction,
+/// 3. instructions of programs which read the signatures account and
+/// 4. the returned Free instruction.
+///
+/// Since Free returns all lamports held by the account to the payer, the
+/// payer only temporarily funds the account.  Note that the Free must come
+/// last; once it’s executed, the signatures are no longer available.
+pub fn update_then_free(
+    sigverify_program: Pubkey,
+    payer: Pubkey,
+    seed: &[u8],
+    epoch: Option<u64>,
+) -> Result<(Instruction, Instruction, Pubkey, u8)> {
+    let (update, account, bump) =
+        update(sigverify_program, payer, seed, epoch)?;
+    let free = free(sigverify_program, payer, Some(account), seed, bump)?;
+    Ok((update, free, account, bump))
+}
+
+/// Generates instruction data for Remove operation.
+///
+/// The operation removes `sighash` from the signatures account.  `seed` and
+/// `bump` specifies seed and bump of the signatures PDA.  Note that the actual
+/// seed used to create the PDA is `[payer.key, seed]` rather than just `seed`.
+///
+/// If `signatures_account` is not given, it’s going to be generated from
+/// provided sigverify program id, Payer account, seed and bump.
+pub fn remove(
+    sigverify_program: Pubkey,
+    payer: Pubkey,
+    signatures_account: Option<Pubkey>,
+    seed: &[u8],
+    bump: u8,
+    sighash: &crate::SigHash,
+) -> Result<Instruction> {
+    new_remove(
+        sigverify_program,
+        payer,
+        None,
+        signatures_account,
+        seed,
+        bump,
+        sighash.as_ref(),
+    )
+}
+
+/// Generates instruction data for Remove operation with a separate authority.
+///
+/// This is like [`remove`] except that the signatures account is a PDA of the
+/// `authority` rather than of the `payer` (see [`update_with_authority`]).  The
+/// authority must sign the transaction.
+pub fn remove_with_authority(
+    sigverify_program: Pubkey,
+    payer: Pubkey,
+    authority: Pubkey,
+    signatures_account: Option<Pubkey>,
+    seed: &[u8],
+    bump: u8,
+    sighash: &crate::SigHash,
+) -> Result<Instruction> {
+    new_remove(
+        sigverify_program,
+        payer,
+        Some(authority),
+        signatures_account,
+        seed,
+        bump,
+        sighash.as_ref(),
+    )
+}
+
+/// Generates instruction data for Clear operation.
+///
+/// The operation removes all signatures of the algorithm with given `magic`
+/// from a full-entry signatures account (see [`update_full_entry`]).  `seed`
+/// and `bump` specifies seed and bump of the signatures PDA.  Note that the
+/// actual seed used to create the PDA is `[payer.key, seed]` rather than just
+/// `seed`.
+///
+/// If `signatures_account` is not given, it’s going to be generated from
+/// provided sigverify program id, Payer account, seed and bump.
+pub fn clear(
+    sigverify_program: Pubkey,
+    payer: Pubkey,
+    signatures_account: Option<Pubkey>,
+    seed: &[u8],
+    bump: u8,
+    magic: crate::algo::Magic,
+) -> Result<Instruction> {
+    new_remove(
+        sigverify_program,
+        payer,
+        None,
+        signatures_account,
+        seed,
+        bump,
+        &magic.to_bytes(),
+    )
+}
+
+/// Generates instruction data for Clear operation with a separate authority.
+///
+/// This is like [`clear`] except that the signatures account is a PDA of the
+/// `authority` rather than of the `payer` (see [`update_with_authority`]).  The
+/// authority must sign the transaction.
+pub fn clear_with_authority(
+    sigverify_program: Pubkey,
+    payer: Pubkey,
+    authority: Pubkey,
+    signatures_account: Option<Pubkey>,
+    seed: &[u8],
+    bump: u8,
+    magic: crate::algo::Magic,
+) -> Result<Instruction> {
+    new_remove(
+        sigverify_program,
+        payer,
+        Some(authority),
+        signatures_account,
+        seed,
+        bump,
+        &magic.to_bytes(),
+    )
+}
+
+/// Generates instruction for Remove or Clear operation.
+///
+/// The program tells the operations apart by length of `payload`, i.e. the
+/// data following seed and bump, which is a signature hash for Remove and
+/// magic for Clear.
+fn new_remove(
+    sigverify_program: Pubkey,
+    payer: Pubkey,
+    authority: Option<Pubkey>,
+    signatures_account: Option<Pubkey>,
+    seed: &[u8],
+    bump: u8,
+    payload: &[u8],
+) -> Result<Instruction> {
+    let mut buf = [0; { solana_program::pubkey::MAX_SEED_LEN + 35 }];
+    buf[0] = tag(2, authority);
+    buf[1] = check_seed(seed)?;
+    buf[2..seed.len() + 2].copy_from_slice(seed);
+    buf[seed.len() + 2] = bump;
+    let len = seed.len() + 3 + payload.len();
+    buf[seed.len() + 3..len].copy_from_slice(payload);
+    let data = &buf[..len];
+
+    let account = if let Some(acc) = signatures_account {
+        acc
+    } else {
+        Pubkey::create_program_address(
+            &[authority.unwrap_or(payer).as_ref(), seed, &[bump]],
+            &sigverify_program,
+        )?
+    };
+
+    let accounts = new_accounts(payer, account, authority, []);
+    Ok(Instruction {
+        program_id: sigverify_program,
+        accounts,
+        data: data.to_vec(),
+    })
+}
+
+/// Generates instruction for Query operation.
+///
+/// The operation logs epoch and number of signatures stored in the
+/// `signatures_account`.  It doesn’t modify the account and is meant for
+/// debugging.
+pub fn query(
+    sigverify_program: Pubkey,
+    signatures_account: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: sigverify_program,
+        accounts: vec![AccountMeta::new_readonly(signatures_account, false)],
+        data: vec![3],
+    }
+}
+
+/// Returns operation tag with the authority bit set if authority is given.
+fn tag(operation: u8, authority: Option<Pubkey>) -> u8 {
+    operation | if authority.is_some() { 0x80 } else { 0 }
+}
+
+/// Constructs list of accounts for an instruction of the sigverify program.
+///
+/// The list starts with Payer, Signatures and (if given) Authority accounts
+/// which are then followed by the `rest` of the accounts.
+fn new_accounts<const N: usize>(
+    payer: Pubkey,
+    signatures_account: Pubkey,
+    authority: Option<Pubkey>,
+    rest: [AccountMeta; N],
+) -> Vec<AccountMeta> {
+    let authority =
+        authority.map(|authority| AccountMeta::new_readonly(authority, true));
+    [AccountMeta::new(payer, true), AccountMeta::new(signatures_account, false)]
+        .into_iter()
+        .chain(authority)
+        .chain(rest)
+        .collect()
+}
+
 /// Checks that seed is below the maximum length; returns length cast to `u8`.
 fn check_seed(seed: &[u8]) -> Result<u8> {
     if seed.len() < solana_program::pubkey::MAX_SEED_LEN {
@@ -201,3 +805,237 @@ fn check_seed(seed: &[u8]) -> Result<u8> {
         Err(ProgramError::MaxSeedLengthExceeded)
     }
 }
+
+
+#[test]
+fn test_update_iter() {
+    let messages = [[1u8; 300], [2; 300], [3; 300], [4; 300], [5; 300]];
+    let entries = messages
+        .iter()
+        .map(|message| solana_native_sigverify::Entry {
+            signature: &[0; 64],
+            pubkey: &[0; 32],
+            message,
+        })
+        .collect::<Vec<_>>();
+    let (mut iter, ..) = UpdateIter::new(
+        &solana_program::ed25519_program::ID,
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        b"seed",
+        None,
+        &entries,
+    )
+    .unwrap();
+    let prefix = Instruction {
+        program_id: Pubkey::new_unique(),
+        accounts: Vec::new(),
+        data: vec![42],
+    };
+    iter.prefix_instructions(vec![prefix.clone()]);
+    // Limits which leave no space for any signature (after subtracting seed
+    // length) are rejected.
+    let min = UpdateIter::MIN_DATA_SIZE + b"seed".len();
+    assert_eq!(Err(ProgramError::InvalidArgument), iter.max_data_size(min - 1));
+    assert_eq!(Ok(()), iter.max_data_size(min));
+
+    // Each entry takes 410 bytes so two fit in an instruction.
+    iter.max_data_size(1000).unwrap();
+    let transaction_count = iter.transaction_count();
+    assert_eq!(3, transaction_count);
+
+    // Each group must cover entries directly following the previous group.
+    let mut next = 0;
+    let mut groups = 0;
+    for (range, insts) in iter.enumerate_entries() {
+        groups += 1;
+        assert_eq!(next, range.start);
+        assert!(range.start < range.end);
+        next = range.end;
+        assert_eq!(3, insts.len());
+        assert_eq!(prefix, insts[0]);
+        let got = solana_native_sigverify::parse_data(&insts[1].data)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(&entries[range], got.as_slice());
+    }
+    assert_eq!(entries.len(), next);
+    assert_eq!(transaction_count, groups);
+}
+
+#[test]
+fn test_max_seed() {
+    let program = Pubkey::new_unique();
+    let payer = Pubkey::new_unique();
+    let sighash = crate::SigHash::from([42; 32]);
+    let seed = [1; solana_program::pubkey::MAX_SEED_LEN - 1];
+
+    let (ix, _, bump) =
+        update_with_capacity(program, payer, &seed, Some(42), 1000).unwrap();
+    assert_eq!(2 + seed.len() + 1 + 4 + 8, ix.data.len());
+    assert_eq!(&seed[..], &ix.data[2..2 + seed.len()]);
+    assert_eq!(bump, ix.data[2 + seed.len()]);
+
+    let ix = free(program, payer, None, &seed, bump).unwrap();
+    assert_eq!(2 + seed.len() + 1, ix.data.len());
+    let ix = remove(program, payer, None, &seed, bump, &sighash).unwrap();
+    assert_eq!(2 + seed.len() + 1 + 32, ix.data.len());
+
+    let err = ProgramError::MaxSeedLengthExceeded;
+    let seed = [1; solana_program::pubkey::MAX_SEED_LEN];
+    let res = update_with_capacity(program, payer, &seed, Some(42), 1);
+    assert_eq!(Err(err.clone()), res);
+    assert_eq!(Err(err.clone()), free_find(program, payer, &seed));
+    let res = free(program, payer, Some(payer), &seed, 0);
+    assert_eq!(Err(err.clone()), res);
+    let res = remove(program, payer, Some(payer), &seed, 0, &sighash);
+    assert_eq!(Err(err.clone()), res);
+    let res = signatures_account(&program, &payer, &seed);
+    assert_eq!(Err(err), res);
+    let seed = &seed[..seed.len() - 1];
+    assert_eq!(
+        Ok(Pubkey::find_program_address(&[payer.as_ref(), seed], &program)),
+        signatures_account(&program, &payer, seed)
+    );
+}
+
+#[test]
+fn test_update_strict() {
+    let program = Pubkey::new_unique();
+    let payer = Pubkey::new_unique();
+    let (want, account, bump) =
+        update(program, payer, b"seed", Some(42)).unwrap();
+    let got = update_strict(program, payer, b"seed", Some(42)).unwrap();
+    assert_eq!((account, bump), (got.1, got.2));
+    assert_eq!(0x20, got.0.data[0]);
+    assert_eq!(want.data[1..], got.0.data[1..]);
+    assert_eq!(want.accounts, got.0.accounts);
+}
+
+#[test]
+fn test_update_hashed() {
+    let program = Pubkey::new_unique();
+    let payer = Pubkey::new_unique();
+    let (want, account, bump) =
+        update(program, payer, b"seed", Some(42)).unwrap();
+    let got = update_hashed(program, payer, b"seed", Some(42)).unwrap();
+    assert_eq!((account, bump), (got.1, got.2));
+    assert_eq!(0x10, got.0.data[0]);
+    assert_eq!(want.data[1..], got.0.data[1..]);
+    assert_eq!(want.accounts, got.0.accounts);
+}
+
+#[test]
+fn test_update_length_prefixed() {
+    let program = Pubkey::new_unique();
+    let payer = Pubkey::new_unique();
+    let (want, account, bump) =
+        update(program, payer, b"seed", Some(42)).unwrap();
+    let got =
+        update_length_prefixed(program, payer, b"seed", Some(42)).unwrap();
+    assert_eq!((account, bump), (got.1, got.2));
+    assert_eq!(0x08, got.0.data[0]);
+    assert_eq!(want.data[1..], got.0.data[1..]);
+    assert_eq!(want.accounts, got.0.accounts);
+}
+
+#[test]
+fn test_update_full_entry() {
+    let program = Pubkey::new_unique();
+    let payer = Pubkey::new_unique();
+    let (want, account, bump) =
+        update(program, payer, b"seed", Some(42)).unwrap();
+    let got = update_full_entry(program, payer, b"seed", Some(42)).unwrap();
+    assert_eq!((account, bump), (got.1, got.2));
+    assert_eq!(0x04, got.0.data[0]);
+    assert_eq!(want.data[1..], got.0.data[1..]);
+    assert_eq!(want.accounts, got.0.accounts);
+}
+
+#[test]
+fn test_clear() {
+    let program = Pubkey::new_unique();
+    let payer = Pubkey::new_unique();
+    let authority = Pubkey::new_unique();
+    let magic = crate::algo::Ed25519::MAGIC;
+    let (account, bump) =
+        signatures_account(&program, &payer, b"seed").unwrap();
+
+    let ix = clear(program, payer, None, b"seed", bump, magic).unwrap();
+    let mut want = vec![2, 4, b's', b'e', b'e', b'd', bump];
+    want.extend_from_slice(&magic.to_bytes());
+    assert_eq!(want, ix.data);
+    assert_eq!(
+        vec![AccountMeta::new(payer, true), AccountMeta::new(account, false)],
+        ix.accounts
+    );
+
+    // Remove uses the same tag but a 32-byte signature hash.
+    let sighash = crate::SigHash::from([1; 32]);
+    let ix = remove(program, payer, None, b"seed", bump, &sighash).unwrap();
+    assert_eq!(want[..7], ix.data[..7]);
+    assert_eq!(&[1; 32][..], &ix.data[7..]);
+
+    let (account, bump) =
+        signatures_account(&program, &authority, b"seed").unwrap();
+    let ix = clear_with_authority(
+        program, payer, authority, None, b"seed", bump, magic,
+    )
+    .unwrap();
+    assert_eq!(0x82, ix.data[0]);
+    assert_eq!(&magic.to_bytes()[..], &ix.data[7..]);
+    assert_eq!(
+        vec![
+            AccountMeta::new(payer, true),
+            AccountMeta::new(account, false),
+            AccountMeta::new_readonly(authority, true),
+        ],
+        ix.accounts
+    );
+}
+
+#[test]
+fn test_with_authority() {
+    let program = Pubkey::new_unique();
+    let payer = Pubkey::new_unique();
+    let authority = Pubkey::new_unique();
+    let (account, bump) =
+        signatures_account(&program, &authority, b"seed").unwrap();
+    let want_accounts = |rest: &[AccountMeta]| {
+        let mut accounts = vec![
+            AccountMeta::new(payer, true),
+            AccountMeta::new(account, false),
+            AccountMeta::new_readonly(authority, true),
+        ];
+        accounts.extend_from_slice(rest);
+        accounts
+    };
+
+    // The Signatures account is derived from the authority rather than the
+    // payer and the authority is a signer.
+    let (ix, got_account, got_bump) =
+        update_with_authority(program, payer, authority, b"seed", None)
+            .unwrap();
+    assert_eq!((account, bump), (got_account, got_bump));
+    assert_eq!(&[0x80, 4, b's', b'e', b'e', b'd', bump][..], &ix.data[..]);
+    assert_eq!(
+        want_accounts(&[
+            AccountMeta::new(solana_program::sysvar::instructions::ID, false),
+            AccountMeta::new(solana_program::system_program::ID, false),
+        ]),
+        ix.accounts
+    );
+
+    let ix =
+        free_with_authority(program, payer, authority, None, b"seed", bump)
+            .unwrap();
+    assert_eq!(&[0x81, 4, b's', b'e', b'e', b'd', bump][..], &ix.data[..]);
+    assert_eq!(
+        want_accounts(&[AccountMeta::new(
+            solana_program::system_program::ID,
+            false
+        )]),
+        ix.accounts
+    );
+}
@@ -22,14 +22,25 @@ pub mod algo;
 mod api;
 #[cfg(feature = "client")]
 pub mod instruction;
+pub mod merkle;
 #[cfg(not(any(feature = "client", feature = "lib")))]
 mod program;
+#[cfg(feature = "serde")]
+mod serde_impl;
 mod stdx;
 #[cfg(feature = "lib")]
 mod verifier;
 
-pub use api::{SigHash, SignaturesAccount};
+#[cfg(feature = "sha2")]
+pub use api::Sha2HashBackend;
+pub use api::{
+    account_version, count_in_data, epoch_in_data, AccountError, HashBackend,
+    ParseSigHashError, Secp256k1Query, SigHash, SigHashIter, SignatureQuery,
+    SignaturesAccount, SolanaHashBackend, ACCOUNT_VERSION,
+    MAX_FULL_ENTRY_SIGNATURES_PER_ACCOUNT, MAX_SIGNATURES_PER_ACCOUNT,
+};
 #[cfg(feature = "lib")]
 pub use verifier::{
-    Ed25519Verifier, Secp256k1Verifier, Secp256r1Verifier, Verifier,
+    assert_sigverify_program_id, Ed25519Verifier, Secp256k1Verifier,
+    Secp256r1Verifier, Verifier, VerifySource,
 };
@@ -0,0 +1,173 @@
+//! Merkle tree over signature hashes.
+//!
+//! The tree commits to a list of [`SigHash`]es (typically the sorted list
+//! stored in a [`SignaturesAccount`](crate::SignaturesAccount)) with a single
+//! 32-byte root.  This lets light clients check that a signature has been
+//! verified given just the root and a short inclusion proof.
+//!
+//! The tree is constructed as follows:
+//!
+//! - Each leaf is `sha256(0x00 || sighash)`.
+//! - Each internal node is `sha256(0x01 || left || right)`.
+//! - If a level has odd number of nodes, the last node is promoted to the next
+//!   level unchanged.
+//! - The root is the single node on the top level.  Root of an empty tree is
+//!   all zeros.
+//!
+//! The distinct prefixes for leaves and internal nodes prevent an internal node
+//! from being passed off as a leaf.
+
+use crate::SigHash;
+
+const LEAF_PREFIX: &[u8] = &[0];
+const NODE_PREFIX: &[u8] = &[1];
+
+/// A proof that a signature hash is included in a Merkle tree.
+///
+/// Created by [`prove`] and checked by [`verify_inclusion`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MerkleProof {
+    /// Index of the leaf in the tree.
+    pub index: u32,
+    /// Number of leaves in the tree.
+    pub count: u32,
+    /// Sibling hashes on the path from the leaf to the root, starting from the
+    /// leaf’s level.  Levels where the node has been promoted have no sibling.
+    pub siblings: Vec<[u8; 32]>,
+}
+
+/// Calculates Merkle root of given signature hashes.
+///
+/// See module documentation for description of the tree construction.
+pub fn root(sighashes: &[SigHash]) -> [u8; 32] {
+    let mut level = sighashes.iter().map(hash_leaf).collect::<Vec<_>>();
+    if level.is_empty() {
+        return [0; 32];
+    }
+    while level.len() > 1 {
+        level = next_level(&level);
+    }
+    level[0]
+}
+
+/// Generates proof that `sighashes[index]` is included in the Merkle tree of
+/// `sighashes`.
+///
+/// Returns `None` if `index` is out of bounds or there are more than
+/// `u32::MAX` signature hashes.
+pub fn prove(sighashes: &[SigHash], index: usize) -> Option<MerkleProof> {
+    let count = u32::try_from(sighashes.len()).ok()?;
+    let mut level = sighashes.iter().map(hash_leaf).collect::<Vec<_>>();
+    let proof_index = u32::try_from(index).ok().filter(|&idx| idx < count)?;
+
+    let mut siblings = Vec::new();
+    let mut index = index;
+    while level.len() > 1 {
+        if let Some(sibling) = level.get(index ^ 1) {
+            siblings.push(*sibling);
+        }
+        level = next_level(&level);
+        index /= 2;
+    }
+    Some(MerkleProof { index: proof_index, count, siblings })
+}
+
+/// Verifies that `sighash` is included in the Merkle tree with given root.
+///
+/// Recomputes the root from the leaf and the proof and compares it with
+/// `root`.  See module documentation for description of the tree construction.
+pub fn verify_inclusion(
+    root: &[u8; 32],
+    sighash: &SigHash,
+    proof: &MerkleProof,
+) -> bool {
+    if proof.index >= proof.count {
+        return false;
+    }
+    let mut siblings = proof.siblings.iter();
+    let mut node = hash_leaf(sighash);
+    let mut index = proof.index;
+    let mut count = proof.count;
+    while count > 1 {
+        if index ^ 1 < count {
+            let sibling = match siblings.next() {
+                Some(sibling) => sibling,
+                None => return false,
+            };
+            node = if index & 1 == 0 {
+                hash_node(&node, sibling)
+            } else {
+                hash_node(sibling, &node)
+            };
+        }
+        index /= 2;
+        count = count.div_ceil(2);
+    }
+    siblings.next().is_none() && node == *root
+}
+
+fn hash_leaf(sighash: &SigHash) -> [u8; 32] {
+    solana_program::hash::hashv(&[LEAF_PREFIX, sighash.as_ref()]).to_bytes()
+}
+
+fn hash_node(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    solana_program::hash::hashv(&[NODE_PREFIX, left, right]).to_bytes()
+}
+
+fn next_level(level: &[[u8; 32]]) -> Vec<[u8; 32]> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [left, right] => hash_node(left, right),
+            [node] => *node,
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+
+#[test]
+fn test_prove_and_verify() {
+    let sighashes =
+        (0..=20u8).map(|n| SigHash::from([n; 32])).collect::<Vec<_>>();
+    for count in 0..sighashes.len() {
+        let sighashes = &sighashes[..count];
+        let root = root(sighashes);
+        assert!(prove(sighashes, count).is_none());
+        for (index, sighash) in sighashes.iter().enumerate() {
+            let proof = prove(sighashes, index).unwrap();
+            assert!(verify_inclusion(&root, sighash, &proof));
+
+            // Wrong hash, wrong index or tampered proof must be rejected.
+            let other = SigHash::from([42; 32]);
+            assert!(!verify_inclusion(&root, &other, &proof));
+            if count > 1 {
+                let mut bad = proof.clone();
+                bad.index = (bad.index + 1) % bad.count;
+                assert!(!verify_inclusion(&root, sighash, &bad));
+                let mut bad = proof.clone();
+                bad.siblings[0][0] ^= 1;
+                assert!(!verify_inclusion(&root, sighash, &bad));
+                let mut bad = proof.clone();
+                bad.siblings.pop();
+                assert!(!verify_inclusion(&root, sighash, &bad));
+            }
+            let mut bad = proof.clone();
+            bad.siblings.push([0; 32]);
+            assert!(!verify_inclusion(&root, sighash, &bad));
+        }
+    }
+}
+
+#[test]
+fn test_root() {
+    let leaf = |n| SigHash::from([n; 32]);
+    assert_eq!([0; 32], root(&[]));
+    assert_eq!(hash_leaf(&leaf(1)), root(&[leaf(1)]));
+    let node = hash_node(&hash_leaf(&leaf(1)), &hash_leaf(&leaf(2)));
+    assert_eq!(node, root(&[leaf(1), leaf(2)]));
+    assert_eq!(
+        hash_node(&node, &hash_leaf(&leaf(3))),
+        root(&[leaf(1), leaf(2), leaf(3)])
+    );
+}
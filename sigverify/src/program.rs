@@ -13,13 +13,36 @@ use solana_system_interface::MAX_PERMITTED_DATA_LENGTH;
 
 type Result<T = (), E = ProgramError> = core::result::Result<T, E>;
 
-use crate::{stdx, SigHash, SignaturesAccount};
+use crate::{algo, stdx, AccountError, SigHash, SignaturesAccount};
 
 solana_program::entrypoint!(process_instruction);
 
+/// Bit in the operation tag indicating that an Authority account is used.
+const AUTHORITY_FLAG: u8 = 0x80;
+
+/// Bit in the Update operation tag indicating that a capacity hint is given.
+const CAPACITY_FLAG: u8 = 0x40;
+
+/// Bit in the Update operation tag requesting strict mode in which the
+/// operation fails if it isn’t preceded by a native program call.
+const STRICT_FLAG: u8 = 0x20;
+
+/// Bit in the Update operation tag indicating that messages in native program
+/// calls are hashes of the actual messages.
+const HASHED_FLAG: u8 = 0x10;
+
+/// Bit in the Update operation tag indicating that signatures are stored as
+/// length-prefixed hashes.
+const LENGTH_PREFIXED_FLAG: u8 = 0x08;
+
+/// Bit in the Update operation tag indicating that the Signatures account is
+/// a full-entry account which stores magic next to each signature.
+const FULL_ENTRY_FLAG: u8 = 0x04;
+
 /// Processes the Solana instruction.
 ///
-/// The program supports two operations: Update and Free.
+/// The program supports five operations: Update, Free, Remove, Clear and
+/// Query.
 ///
 /// # Update
 ///
@@ -32,6 +55,7 @@ solana_program::entrypoint!(process_instruction);
 ///     seed_len: u8,  // at most 31
 ///     seed: [u8; seed_len],
 ///     bump: u8,
+///     capacity: u32,  // present only if `0x40` bit of the tag is set
 ///     epoch: Option<u64>,
 /// }
 /// ```
@@ -47,22 +71,86 @@ solana_program::entrypoint!(process_instruction);
 ///    `Sysvar1nstructions1111111111111111111111111`) and
 /// 4. System program (optional; should be `11111111111111111111111111111111`).
 ///
-/// The smart contract expects instruction priory to the current one to be call
-/// to a native signature verification program (i.e. Ed25519, Secp256k1 or
-/// Secp256r1 program).  It parses the instruction to determine which
-/// instructions the program verified.  All those signatures are added to the
-/// Signatures account.  [`SignaturesAccount`] provides abstraction which allows
-/// checking whether particular signature has been aggregated.
+/// The smart contract expects instructions priory to the current one to be
+/// calls to native signature verification programs (i.e. Ed25519 or Secp256k1
+/// program).  Starting from the instruction directly preceding the current
+/// one, it scans the transaction backwards until it encounters an instruction
+/// which isn’t a call to a signature verification program (or the start of
+/// the transaction).  It parses each of those instructions to
+/// determine which signatures the programs verified.  All those signatures are
+/// added to the Signatures account.  This allows a single Update to aggregate
+/// signatures from multiple calls, e.g. one to Ed25519 and one to Secp256k1
+/// program.  [`SignaturesAccount`] provides abstraction which allows
+/// checking whether particular signature has been aggregated.  Signatures
+/// from Secp256k1 program calls are stored as [`SigHash::new_secp256k1`]
+/// hashes and can be checked with [`SignaturesAccount::find_secp256k1`].
+///
+/// Calls to the Secp256r1 program aren’t supported since its 33-byte public
+/// keys don’t fit [`SigHash`].  Such calls are skipped (i.e. the scan
+/// continues past them but none of their signatures are stored) unless strict
+/// mode (see below) is used in which case the operation fails with
+/// [`ProgramError::InvalidInstructionData`].
 ///
 /// The Signatures account must be a PDA with seeds `[payer.key, seed,
-/// &[bump]]`.  If the Signatures account doesn’t exist, creates the account.
-/// Similarly, if it’s too small, increases its size.
+/// &[bump]]` (or `[authority.key, seed, &[bump]]` if authority is used; see
+/// below).  If the Signatures account doesn’t exist, creates the account.
+/// Similarly, if it’s too small, increases its size.  If the account already
+/// holds [`SignaturesAccount::MAX_SIGNATURES`] signatures and new ones need to
+/// be added, the operation fails with [`AccountError::AccountFull`].
+///
+/// If `capacity` is given (i.e. `0x40` bit of the tag is set), it specifies
+/// number of signatures the Signatures account is expected to hold.  When
+/// creating or enlarging the account, the program sizes it to fit that many
+/// signatures rather than growing it in 10 KiB steps.  The size is still
+/// capped at [`MAX_PERMITTED_DATA_LENGTH`] and, when enlarging existing
+/// account, by the maximum increase permitted in a single instruction.  The
+/// hint is only an optimisation; if more signatures are written, the account
+/// is grown as usual.
 ///
 /// If `epoch` is given, the value is compared with epoch stored in the PDA.  If
 /// they differ, the PDA will be cleared first from any stored signatures.  The
 /// epoch allows reusing the same PDA without the need to synchronously clear
 /// it.
 ///
+/// By default, if the instruction directly preceding the Update isn’t a call
+/// to a native signature verification program, the operation succeeds without
+/// adding any signatures.  If `0x20` bit of the tag is set, the operation runs
+/// in strict mode and fails with [`ProgramError::InvalidInstructionData`]
+/// instead.  This catches misconfigured transactions which would otherwise
+/// silently aggregate nothing.
+///
+/// If `0x10` bit of the tag is set, the operation runs in hashed-message mode.
+/// Messages in the native program calls are treated as hashes of the actual
+/// messages (see [`solana_native_sigverify::Entry::with_message_hash`]) and
+/// the signatures are stored with [`SigHash::from_message_hash`] rather than
+/// [`SigHash::new`].  This keeps signatures of hashed and raw messages in
+/// separate domains.  In this mode each message must be exactly 32 bytes and
+/// calls to the Secp256k1 program (which hashes messages itself) aren’t
+/// allowed.  Otherwise the operation fails with
+/// [`ProgramError::InvalidInstructionData`].
+///
+/// If `0x08` bit of the tag is set, the operation runs in length-prefixed mode.
+/// The signatures are stored with [`SigHash::from_entry_length_prefixed`]
+/// rather than [`SigHash::from_entry`] so they can be looked up with
+/// [`SigHash::new_length_prefixed`].  This mode cannot be combined with
+/// hashed-message mode and calls to the Secp256k1 program (whose signatures
+/// are stored with their own scheme) aren’t allowed.  Otherwise the operation
+/// fails with [`ProgramError::InvalidInstructionData`].
+///
+/// If `0x04` bit of the tag is set, the Signatures account is a full-entry
+/// account which stores magic of the signature’s algorithm next to each
+/// signature (see [`SignaturesAccount`]).  Signatures of a single algorithm
+/// can then be removed from the account with the Clear operation.  The bit
+/// selects the layout when the account is created and must match the layout
+/// of an existing account.  Otherwise the operation fails with
+/// [`ProgramError::InvalidAccountData`].  The bit can be combined with any
+/// other Update flags.
+///
+/// Once done, the operation logs the epoch and total number of signatures
+/// stored in the Signatures account in the same `sigverify: epoch=<epoch>,
+/// count=<count>` format as the Query operation.  This lets indexers track
+/// progress of the aggregation without fetching the account.
+///
 /// # Free
 ///
 /// The Free operation is represented by the following pseudo-Rust structure:
@@ -83,6 +171,84 @@ solana_program::entrypoint!(process_instruction);
 /// 3. System program (should be `11111111111111111111111111111111`).
 ///
 /// It frees the Signatures account transferring all lamports to the payer.
+///
+/// # Remove
+///
+/// The Remove operation is represented by the following pseudo-Rust structure:
+///
+/// ```ignore
+/// #[repr(C, packed)]
+/// struct Instruction {
+///     always_two: u8,  // always 2u8,
+///     seed_len: u8,  // at most 31
+///     seed: [u8; seed_len],
+///     bump: u8,
+///     sighash: [u8; 32],
+/// }
+/// ```
+///
+/// It takes two required accounts:
+/// 1. Payer account (signer, writable) and
+/// 2. Signatures account (writable).
+///
+/// It removes given signature hash from the Signatures account.  If the hash
+/// isn’t stored in the account, does nothing.  This allows invalidating
+/// a single aggregated signature without clearing the whole account.
+///
+/// # Clear
+///
+/// The Clear operation is represented by the following pseudo-Rust structure:
+///
+/// ```ignore
+/// #[repr(C, packed)]
+/// struct Instruction {
+///     always_two: u8,  // always 2u8,
+///     seed_len: u8,  // at most 31
+///     seed: [u8; seed_len],
+///     bump: u8,
+///     magic: u32,
+/// }
+/// ```
+///
+/// It shares the tag with the Remove operation and is distinguished by the
+/// length of the data following the seed and bump.  It takes the same
+/// accounts as the Remove operation.
+///
+/// It removes all signatures of the algorithm with given magic (see
+/// [`algo::Magic`]) from the Signatures account.  The account must be
+/// a full-entry account (see the Update operation).  Otherwise the operation
+/// fails with [`AccountError::MagicNotStored`].  This allows clearing
+/// signatures of one algorithm while keeping signatures of others.
+///
+/// # Query
+///
+/// The Query operation is represented by a single `3u8` byte.  It takes
+/// a single required Signatures account (read-only).
+///
+/// It logs the epoch and number of signatures stored in the Signatures
+/// account as `sigverify: epoch=<epoch>, count=<count>`.  It doesn’t modify
+/// anything and is meant for debugging.  If the account doesn’t exist (i.e.
+/// has no lamports), reports zero for both values.  The Query operation
+/// doesn’t support any flags (including the authority flag described below)
+/// nor any data after the tag.  Otherwise it fails with
+/// [`ProgramError::InvalidInstructionData`].
+///
+/// # Authority
+///
+/// If the most significant bit of the first byte of the instruction data is
+/// set (i.e. it’s `0x80` for Update, `0x81` for Free and `0x82` for Remove
+/// and Clear),
+/// the operation uses a separate Authority account.  The Authority account is
+/// passed directly after the Signatures account (shifting all other accounts
+/// by one) and must be a signer.  Typically it’s a PDA of another program
+/// which signs the call through CPI.
+///
+/// In this mode, the Signatures account must be a PDA with seeds
+/// `[authority.key, seed, &[bump]]` rather than the default `[payer.key, seed,
+/// &[bump]]`.  The payer still pays for creating and enlarging the account and
+/// receives all the lamports when the account is freed.  This allows the
+/// signatures account to be tied to an authority which may be different from
+/// the account paying for the transactions.
 fn process_instruction<'a>(
     program_id: &'a Pubkey,
     mut accounts: &'a [AccountInfo],
@@ -92,22 +258,72 @@ fn process_instruction<'a>(
         .split_first()
         .ok_or(ProgramError::InvalidInstructionData)?;
 
-    let ctx = Context::get(program_id, &mut accounts, &mut instruction)?;
+    // Query doesn’t use the Payer account nor seeds so handle it separately.
+    // It takes no flags.
+    const FLAGS: u8 = AUTHORITY_FLAG |
+        CAPACITY_FLAG |
+        STRICT_FLAG |
+        HASHED_FLAG |
+        LENGTH_PREFIXED_FLAG |
+        FULL_ENTRY_FLAG;
+    if *tag == 3 {
+        return handle_query(program_id, accounts, instruction);
+    } else if *tag & !FLAGS == 3 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
 
-    match (tag, instruction.len()) {
-        (0, _) => handle_update(ctx, accounts, instruction),
+    let with_authority = tag & AUTHORITY_FLAG != 0;
+    let ctx = Context::get(
+        program_id,
+        &mut accounts,
+        &mut instruction,
+        with_authority,
+    )?;
+
+    const UPDATE_FLAGS: u8 = CAPACITY_FLAG |
+        STRICT_FLAG |
+        HASHED_FLAG |
+        LENGTH_PREFIXED_FLAG |
+        FULL_ENTRY_FLAG;
+    match (tag & !AUTHORITY_FLAG, instruction.len()) {
+        (op, _) if op & !UPDATE_FLAGS == 0 => {
+            handle_update(ctx, accounts, instruction, op)
+        }
         (1, 0) => ctx.free_signatures_account(),
+        (2, 4) => handle_clear(ctx, instruction),
+        (2, _) => handle_remove(ctx, instruction),
         _ => Err(ProgramError::InvalidInstructionData),
     }
 }
 
 
 /// Handles the Update operation.
+///
+/// `flags` is the operation tag without the authority bit.
 fn handle_update(
     ctx: Context,
     accounts: &[AccountInfo],
-    instruction: &[u8],
+    mut instruction: &[u8],
+    flags: u8,
 ) -> Result {
+    let with_capacity = flags & CAPACITY_FLAG != 0;
+    let strict = flags & STRICT_FLAG != 0;
+    let hashed = flags & HASHED_FLAG != 0;
+    let length_prefixed = flags & LENGTH_PREFIXED_FLAG != 0;
+    let full_entry = flags & FULL_ENTRY_FLAG != 0;
+
+    // Hashed-message and length-prefixed hashes are separate schemes.
+    if hashed && length_prefixed {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    // Read the capacity hint if present.
+    let capacity = if with_capacity {
+        Some(read(&mut instruction, u32::from_le_bytes)?)
+    } else {
+        None
+    };
+
     // Read `epoch` from instruction data.  If given, remove all the signatures
     // if the epoch doesn’t match one stored in the account.
     let epoch = if instruction.is_empty() {
@@ -118,53 +334,208 @@ fn handle_update(
         return Err(ProgramError::InvalidInstructionData);
     };
 
-    // Initialise the Signatures account and read number of signatures stored there.
-    ctx.initialise_signatures_account()?;
+    // Initialise the Signatures account and read number of signatures stored
+    // there.  Existing account must use the requested layout.
+    ctx.initialise_signatures_account(capacity, full_entry)?;
+    if ctx.signatures.is_full_entry()? != full_entry {
+        return Err(ProgramError::InvalidAccountData);
+    }
     let mut count = ctx.signatures.read_count(epoch)?;
 
-    // Get the previous instruction.  We expect it to be a call to a signature
-    // verification native program.
+    // Go through preceding instructions.  We expect them to be calls to
+    // signature verification native programs.  Parse signatures from each of
+    // the calls and copy them to the Signatures account.  Stop at the first
+    // instruction which isn’t such a call.  Since we start with instruction
+    // before the current one, the Update instruction itself is never
+    // processed.
     let ix_sysvar =
         accounts.first().ok_or(ProgramError::NotEnoughAccountKeys)?;
-    let prev_ix = instructions::get_instruction_relative(-1, ix_sysvar)?;
-
-    // Parse signatures from the call to the signature verification native
-    // program and copy them to the Signatures account.
-    process_verify_instruction(prev_ix, |signature| {
-        ctx.signatures.write_signature(count, &signature, || {
-            ctx.enlarge_signatures_account()
-        })?;
-        count = count.checked_add(1).ok_or(ProgramError::ArithmeticOverflow)?;
-        Ok::<(), ProgramError>(())
-    })?;
-
-    // Update number of signatures saved in the Signatures account and sort
-    // the entries.
-    ctx.signatures.write_count_and_sort(epoch, count)
+    let current = instructions::load_current_index_checked(ix_sysvar)?;
+    let mut processed = 0;
+    for index in (0..current).rev() {
+        let ix =
+            instructions::load_instruction_at_checked(index.into(), ix_sysvar)?;
+        let found = process_verify_instruction(
+            ix,
+            index,
+            hashed,
+            length_prefixed,
+            strict,
+            |magic, signature| {
+                ctx.signatures.write_signature(
+                    count,
+                    magic,
+                    &signature,
+                    || ctx.enlarge_signatures_account(capacity, full_entry),
+                )?;
+                count = count
+                    .checked_add(1)
+                    .ok_or(ProgramError::ArithmeticOverflow)?;
+                Ok::<(), ProgramError>(())
+            },
+        )?;
+        if !found {
+            break;
+        }
+        processed += 1;
+    }
+    if strict && processed == 0 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    // Sort the entries, remove duplicates and update number of signatures
+    // saved in the Signatures account.
+    let (epoch, count) = ctx.signatures.write_count_and_sort(epoch, count)?;
+    solana_program::msg!("sigverify: epoch={}, count={}", epoch, count);
+    Ok(())
+}
+
+
+/// Handles the Remove operation.
+fn handle_remove(ctx: Context, instruction: &[u8]) -> Result {
+    let sighash = <[u8; SigHash::SIZE]>::try_from(instruction)
+        .map_err(|_| ProgramError::InvalidInstructionData)?;
+    ctx.signatures.remove_signature(&SigHash::from(sighash))?;
+    Ok(())
+}
+
+
+/// Handles the Clear operation.
+fn handle_clear(ctx: Context, instruction: &[u8]) -> Result {
+    let magic = <[u8; 4]>::try_from(instruction)
+        .ok()
+        .and_then(algo::Magic::from_bytes)
+        .ok_or(ProgramError::InvalidInstructionData)?;
+    ctx.signatures.clear_magic(magic)?;
+    Ok(())
+}
+
+
+/// Handles the Query operation.
+fn handle_query(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction: &[u8],
+) -> Result {
+    let (epoch, count) = query(program_id, accounts, instruction)?;
+    solana_program::msg!("sigverify: epoch={}, count={}", epoch, count);
+    Ok(())
+}
+
+/// Returns epoch and number of signatures reported by the Query operation.
+fn query(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction: &[u8],
+) -> Result<(u64, u32)> {
+    if !instruction.is_empty() {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let account = accounts.first().ok_or(ProgramError::NotEnoughAccountKeys)?;
+    if account.lamports() == 0 {
+        Ok((0, 0))
+    } else {
+        SignaturesAccount::new_checked_owner(account, program_id)?
+            .read_epoch_and_count()
+    }
 }
 
 
 /// Extracts signatures from a call to signature verification native program.
 ///
 /// If the `instruction` doesn’t correspond to call to a supported signature
-/// verification native program, does nothing.  Otherwise invokes specified
-/// callback for each signature specified in the instruction.
+/// verification native program, does nothing and returns `false`.  Otherwise
+/// invokes specified callback with the algorithm’s magic and hash of each
+/// signature specified in the instruction and returns `true`.
+///
+/// `index` is the index of the instruction in the transaction.  It’s needed
+/// to parse Secp256k1 program calls whose data references the instruction by
+/// its index rather than with `u16::MAX` special value.  Signatures from
+/// Secp256k1 program calls are hashed with [`SigHash::from_secp256k1_entry`].
+/// Calls to the Secp256r1 program are skipped (callback isn’t invoked but
+/// `true` is returned) unless `strict` is true in which case they result in
+/// an error.
+///
+/// If `hashed` is true, messages are hashes of the actual messages and
+/// signatures are hashed with [`SigHash::from_message_hash`].  Returns an
+/// error if any of the messages isn’t 32 bytes or if the instruction is
+/// a call to the Secp256k1 program.
+///
+/// If `length_prefixed` is true, signatures are hashed with
+/// [`SigHash::from_entry_length_prefixed`].  Returns an error if the
+/// instruction is a call to the Secp256k1 program.  `hashed` and
+/// `length_prefixed` must not both be true.
 fn process_verify_instruction(
     instruction: Instruction,
-    mut callback: impl FnMut(SigHash) -> Result,
-) -> Result {
+    index: u16,
+    hashed: bool,
+    length_prefixed: bool,
+    strict: bool,
+    mut callback: impl FnMut(algo::Magic, SigHash) -> Result,
+) -> Result<bool> {
     use solana_native_sigverify::Error;
 
-    let magic = match crate::algo::from_id(instruction.program_id) {
-        Some(magic) => magic,
-        None => return Ok(()),
+    let algo = match crate::algo::Algo::from_id(instruction.program_id) {
+        Some(algo) => algo,
+        None => return Ok(false),
     };
-    solana_native_sigverify::parse_data(instruction.data.as_slice())?
-        .try_for_each(|entry| match entry {
-            Ok(entry) => callback(SigHash::from_entry(magic, entry)),
-            Err(Error::UnsupportedFeature) => Ok(()),
+    let magic = algo.magic();
+    let data = instruction.data.as_slice();
+
+    fn map_err<T>(entry: Result<T, Error>) -> Result<Option<T>> {
+        match entry {
+            Ok(entry) => Ok(Some(entry)),
+            Err(Error::UnsupportedFeature) => Ok(None),
             Err(Error::BadData) => Err(ProgramError::InvalidInstructionData),
-        })
+        }
+    }
+
+    if algo == crate::algo::Algo::Secp256r1 {
+        // Secp256r1 program takes 33-byte public keys which the Ed25519 layout
+        // parsed by parse_data cannot describe.
+        return if strict {
+            Err(ProgramError::InvalidInstructionData)
+        } else {
+            Ok(true)
+        };
+    } else if algo == crate::algo::Algo::Secp256k1 {
+        // Secp256k1 program hashes the message itself so there’s no
+        // hashed-message variant of its SigHashes.  Its SigHashes use their
+        // own scheme so there’s no length-prefixed variant either.
+        if hashed || length_prefixed {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        // Secp256k1 program uses 8-bit instruction indexes so a call with
+        // larger index couldn’t reference its own data.
+        let index = u8::try_from(index)
+            .map_err(|_| ProgramError::InvalidInstructionData)?;
+        for entry in solana_native_sigverify::parse_secp256k1_data(data, index)?
+        {
+            if let Some(entry) = map_err(entry)? {
+                callback(magic, SigHash::from_secp256k1_entry(magic, entry))?;
+            }
+        }
+    } else {
+        for entry in solana_native_sigverify::parse_data(data)? {
+            let Some(entry) = map_err(entry)? else { continue };
+            let sighash = if hashed {
+                let hash = <&[u8; 32]>::try_from(entry.message)
+                    .map_err(|_| ProgramError::InvalidInstructionData)?;
+                SigHash::from_message_hash(
+                    magic,
+                    entry.pubkey,
+                    entry.signature,
+                    hash,
+                )
+            } else if length_prefixed {
+                SigHash::from_entry_length_prefixed(magic, entry)
+            } else {
+                SigHash::from_entry(magic, entry)
+            };
+            callback(magic, sighash)?;
+        }
+    }
+    Ok(true)
 }
 
 /// Accounts used when processing instruction.
@@ -172,10 +543,14 @@ struct Context<'a, 'info> {
     /// Our program id.
     program_id: &'a Pubkey,
 
-    /// The Payer account which pays and ‘owns’ the Signatures account.
+    /// The Payer account which pays for the Signatures account.
     payer: &'a AccountInfo<'info>,
 
-    /// The Signatures account.  It’s address is a PDA using `[payer.key,
+    /// The account which ‘owns’ the Signatures account.  This is the same as
+    /// `payer` unless a separate Authority account has been given.
+    authority: &'a AccountInfo<'info>,
+
+    /// The Signatures account.  It’s address is a PDA using `[authority.key,
     /// seed_and_bump]` seeds.
     signatures: SignaturesAccount<'a, 'info>,
 
@@ -188,8 +563,12 @@ impl<'a, 'info> Context<'a, 'info> {
     ///
     /// Expects the following accounts in the `accounts` slice:
     /// 1. Payer account which is signer and writable,
-    /// 2. Signatures account which is writable and a PDA using `[payer.key, seed,
-    ///    bump]` seeds.
+    /// 2. Signatures account which is writable and a PDA using `[authority.key,
+    ///    seed, bump]` seeds and
+    /// 3. if `with_authority` is true, Authority account which is signer.
+    ///
+    /// If `with_authority` is false, the Payer account is used as the
+    /// authority.
     ///
     /// Reads seed and bump from `instruction` advancing it.  Specifically,
     /// reads the following dynamically-sized structure:
@@ -206,6 +585,7 @@ impl<'a, 'info> Context<'a, 'info> {
         program_id: &'a Pubkey,
         accounts: &mut &'a [AccountInfo<'info>],
         instruction: &mut &'a [u8],
+        with_authority: bool,
     ) -> Result<Self> {
         let ([payer, signatures], remaining) = stdx::split_at::<2, _>(accounts)
             .ok_or(ProgramError::NotEnoughAccountKeys)?;
@@ -218,14 +598,32 @@ impl<'a, 'info> Context<'a, 'info> {
             return Err(ProgramError::InvalidAccountData);
         }
 
+        // Authority.  If given, must be signer.
+        let authority = if with_authority {
+            let (authority, remaining) = accounts
+                .split_first()
+                .ok_or(ProgramError::NotEnoughAccountKeys)?;
+            *accounts = remaining;
+            if !authority.is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            authority
+        } else {
+            payer
+        };
+
         // Signatures account.  Must be writable and PDA.
         if !signatures.is_writable {
             return Err(ProgramError::InvalidAccountData);
         }
         let signatures = SignaturesAccount(signatures);
         let seed_len = read(instruction, u8::from_le_bytes)?;
+        if usize::from(seed_len) >= solana_program::pubkey::MAX_SEED_LEN {
+            return Err(ProgramError::MaxSeedLengthExceeded);
+        }
         let seed_and_bump = read_slice(instruction, seed_len as usize + 1)?;
-        let this = Self { program_id, payer, signatures, seed_and_bump };
+        let this =
+            Self { program_id, payer, authority, signatures, seed_and_bump };
 
         match Pubkey::create_program_address(&this.write_seeds(), program_id) {
             Ok(pda) if &pda == this.signatures.key => Ok(this),
@@ -236,8 +634,16 @@ impl<'a, 'info> Context<'a, 'info> {
     /// Sets up the Signatures account if it doesn’t exist.
     ///
     /// If the account doesn’t exist, creates it with size of 10 KiB (i.e.
-    /// [`MAX_PERMITTED_DATA_INCREASE`]).
-    fn initialise_signatures_account(&self) -> Result {
+    /// [`MAX_PERMITTED_DATA_INCREASE`]) or, if `capacity` is given, with size
+    /// needed to hold that many signatures (capped at
+    /// [`MAX_PERMITTED_DATA_LENGTH`]).  The header of a new account is
+    /// initialised with the current layout version and, if `full_entry` is
+    /// true, as a full-entry account.
+    fn initialise_signatures_account(
+        &self,
+        capacity: Option<u32>,
+        full_entry: bool,
+    ) -> Result {
         let lamports = self.signatures.lamports();
 
         // If the account has zero lamports it needs to be created first.
@@ -245,7 +651,10 @@ impl<'a, 'info> Context<'a, 'info> {
             return Ok(());
         }
 
-        let size = MAX_PERMITTED_DATA_INCREASE;
+        let size = capacity.map_or(MAX_PERMITTED_DATA_INCREASE, |capacity| {
+            size_for(capacity, full_entry)
+                .min(MAX_PERMITTED_DATA_LENGTH as usize)
+        });
         let required_lamports = Rent::get()?.minimum_balance(size);
         let instruction = solana_system_interface::instruction::create_account(
             self.payer.key,
@@ -259,7 +668,7 @@ impl<'a, 'info> Context<'a, 'info> {
             &[self.payer.clone(), (*self.signatures).clone()],
             &[&self.write_seeds()],
         )?;
-        Ok(())
+        self.signatures.write_version(full_entry)
     }
 
     /// Frees the Signatures account returning lamports to the payer.
@@ -278,17 +687,34 @@ impl<'a, 'info> Context<'a, 'info> {
         self.signatures.resize(0)
     }
 
-    /// Enlarges the Signatures account by 10 KiB (or to maximum allowable size).
-    fn enlarge_signatures_account(&self) -> Result {
+    /// Enlarges the Signatures account by 10 KiB (or to maximum allowable
+    /// size).
+    ///
+    /// If `capacity` is given and the account is too small to hold that many
+    /// signatures, enlarges the account to size needed to hold them instead
+    /// (but still by at most 10 KiB).  `full_entry` specifies whether the
+    /// account is a full-entry account.
+    fn enlarge_signatures_account(
+        &self,
+        capacity: Option<u32>,
+        full_entry: bool,
+    ) -> Result {
         let current_size = self.signatures.try_data_len()?;
-        let size = (current_size + MAX_PERMITTED_DATA_INCREASE)
-            .min(MAX_PERMITTED_DATA_LENGTH as usize);
+        let mut size = current_size + MAX_PERMITTED_DATA_INCREASE;
+        if let Some(capacity) = capacity {
+            let want = size_for(capacity, full_entry);
+            if want > current_size {
+                size = size.min(want);
+            }
+        }
+        let size = size.min(MAX_PERMITTED_DATA_LENGTH as usize);
 
-        // Do nothing if account is already maximum size.  We don’t report
-        // error.  Instead caller will fail trying to access data past account’s
-        // size.
+        // The account is already maximum size so it cannot hold any more
+        // signatures.  write_signature rejects indexes past the limit before
+        // calling us so this shouldn’t happen but report it explicitly rather
+        // than letting the caller fail accessing data past account’s size.
         if size <= current_size {
-            return Ok(());
+            return Err(AccountError::AccountFull.into());
         }
 
         // We may need to transfer more lamports to keep the account as
@@ -312,7 +738,7 @@ impl<'a, 'info> Context<'a, 'info> {
 
     /// Returns seeds used to generate Signatures account PDA.
     fn write_seeds(&self) -> [&'a [u8]; 2] {
-        [self.payer.key.as_ref(), self.seed_and_bump]
+        [self.authority.key.as_ref(), self.seed_and_bump]
     }
 }
 
@@ -343,3 +769,743 @@ fn read_slice<'a>(bytes: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
     *bytes = tail;
     Ok(head)
 }
+
+
+/// Signs message with Secp256k1 key made of `secretkey` bytes.
+///
+/// Returns the signature, recovery id and Ethereum address of the signer.
+#[cfg(test)]
+fn secp256k1_sign(message: &[u8], secretkey: u8) -> ([u8; 64], u8, [u8; 20]) {
+    let key = libsecp256k1::SecretKey::parse(&[secretkey; 32]).unwrap();
+    let pubkey = libsecp256k1::PublicKey::from_secret_key(&key);
+    let pubkey = pubkey.serialize()[1..].try_into().unwrap();
+    let address = solana_secp256k1_program::eth_address_from_pubkey(&pubkey);
+    let (signature, recovery_id) =
+        solana_secp256k1_program::sign_message(&key.serialize(), message)
+            .unwrap();
+    (signature, recovery_id, address)
+}
+
+#[test]
+fn test_process_secp256k1_instruction() {
+    use solana_native_sigverify::Secp256k1Entry;
+
+    let (sig1, rec1, addr1) = secp256k1_sign(b"foo", 1);
+    let (sig2, rec2, addr2) = secp256k1_sign(b"bar", 2);
+    let entries = [
+        Secp256k1Entry {
+            signature: &sig1,
+            recovery_id: rec1,
+            eth_address: &addr1,
+            message: b"foo",
+        },
+        Secp256k1Entry {
+            signature: &sig2,
+            recovery_id: rec2,
+            eth_address: &addr2,
+            message: b"bar",
+        },
+    ];
+    let magic = crate::algo::Secp256k1::MAGIC;
+    let want = entries.map(|entry| SigHash::from_secp256k1_entry(magic, entry));
+
+    let process = |instruction: &Instruction, index| {
+        let mut got = Vec::new();
+        process_verify_instruction(
+            instruction.clone(),
+            index,
+            false,
+            false,
+            false,
+            |got_magic, sighash| {
+                assert_eq!(magic, got_magic);
+                got.push(sighash);
+                Ok(())
+            },
+        )
+        .map(|found| (found, got))
+    };
+
+    let instruction =
+        solana_native_sigverify::new_secp256k1_instruction(2, &entries)
+            .unwrap();
+    #[allow(deprecated)]
+    solana_secp256k1_program::verify(
+        &instruction.data,
+        &[b"", b"", &instruction.data],
+        &Default::default(),
+    )
+    .unwrap();
+    assert_eq!(Ok((true, want.to_vec())), process(&instruction, 2));
+    for (hashed, length_prefixed) in [(true, false), (false, true)] {
+        let res = process_verify_instruction(
+            instruction.clone(),
+            2,
+            hashed,
+            length_prefixed,
+            false,
+            |_, _| panic!(),
+        );
+        assert_eq!(Err(ProgramError::InvalidInstructionData), res);
+    }
+
+    // Entries referencing other instructions are skipped.
+    assert_eq!(Ok((true, Vec::new())), process(&instruction, 1));
+    assert_eq!(
+        Err(ProgramError::InvalidInstructionData),
+        process(&instruction, 258)
+    );
+
+    // Instructions which aren’t calls to signature verification programs are
+    // not processed.
+    let instruction = Instruction {
+        program_id: solana_program::system_program::ID,
+        ..instruction
+    };
+    assert_eq!(Ok((false, Vec::new())), process(&instruction, 2));
+}
+
+#[test]
+fn test_process_hashed_instruction() {
+    use solana_native_sigverify::Entry;
+
+    use crate::algo::Algorithm;
+
+    let magic = crate::algo::Ed25519::MAGIC;
+    let hash = solana_native_sigverify::hash_message(b"foo");
+    let entry = Entry::with_message_hash(&[1; 64], &[2; 32], &hash);
+    let process = |entries: &[Entry], hashed| {
+        let instruction =
+            crate::algo::Ed25519::new_instruction(entries).unwrap();
+        let mut got = Vec::new();
+        let push = |got_magic, sighash| {
+            assert_eq!(magic, got_magic);
+            got.push(sighash);
+            Ok(())
+        };
+        process_verify_instruction(instruction, 0, hashed, false, false, push)
+            .map(|_| got)
+    };
+
+    let want = SigHash::new_hashed(magic, &[2; 32], &[1; 64], b"foo");
+    assert_eq!(Ok(vec![want]), process(&[entry], true));
+    let plain = SigHash::from_entry(magic, entry);
+    assert_ne!(want, plain);
+    assert_eq!(Ok(vec![plain]), process(&[entry], false));
+
+    // In hashed-message mode all messages must be hashes.
+    let raw = Entry { message: b"foo", ..entry };
+    assert_eq!(
+        Err(ProgramError::InvalidInstructionData),
+        process(&[entry, raw], true)
+    );
+}
+
+/// Returns size of the Signatures account data needed to hold `count`
+/// signatures.
+fn size_for(count: u32, full_entry: bool) -> usize {
+    if full_entry {
+        SignaturesAccount::size_for_full_entry(count)
+    } else {
+        SignaturesAccount::size_for(count)
+    }
+}
+
+/// Returns a transaction instruction for use in tests.
+#[cfg(test)]
+fn test_instruction<'a>(
+    program_id: &'a Pubkey,
+    data: &'a [u8],
+) -> instructions::BorrowedInstruction<'a> {
+    instructions::BorrowedInstruction { program_id, accounts: Vec::new(), data }
+}
+
+/// Runs the Update operation as the last of given instructions.
+///
+/// `account` is used as the Signatures account as well as the Payer.  It must
+/// have lamports so that the operation doesn’t try to create it.  Returns
+/// number of signatures in the account afterwards.
+#[cfg(test)]
+fn test_update(
+    account: &AccountInfo,
+    instructions: &[instructions::BorrowedInstruction],
+    epoch: Option<u64>,
+    strict: bool,
+) -> Result<u32> {
+    let flags = if strict { STRICT_FLAG } else { 0 };
+    test_update_with_flags(account, instructions, epoch, flags)
+}
+
+/// Runs the Update operation with given tag flags as the last of given
+/// instructions.
+///
+/// See [`test_update`].  `flags` may include [`STRICT_FLAG`], [`HASHED_FLAG`],
+/// [`LENGTH_PREFIXED_FLAG`] and [`FULL_ENTRY_FLAG`].
+#[cfg(test)]
+fn test_update_with_flags(
+    account: &AccountInfo,
+    instructions: &[instructions::BorrowedInstruction],
+    epoch: Option<u64>,
+    flags: u8,
+) -> Result<u32> {
+    let mut data = instructions::construct_instructions_data(instructions);
+    // Current instruction index is stored at the end of the data.
+    let len = data.len();
+    let current = instructions.len() as u16 - 1;
+    data[len - 2..].copy_from_slice(&current.to_le_bytes());
+    let mut lamports = 0;
+    let ix_sysvar = crate::api::test_account(
+        &instructions::ID,
+        &solana_program::sysvar::ID,
+        &mut lamports,
+        &mut data,
+    );
+
+    let ctx = Context {
+        program_id: account.owner,
+        payer: account,
+        authority: account,
+        signatures: SignaturesAccount(account),
+        seed_and_bump: &[0],
+    };
+    let epoch = epoch.map(u64::to_le_bytes);
+    let instruction = epoch.as_ref().map_or(&[][..], |epoch| &epoch[..]);
+    handle_update(ctx, &[ix_sysvar], instruction, flags)?;
+    SignaturesAccount(account).count()
+}
+
+#[test]
+fn test_update_strict() {
+    let program_id = Pubkey::new_unique();
+    let other = Pubkey::new_unique();
+    let entry = solana_native_sigverify::Entry {
+        signature: &[1; 64],
+        pubkey: &[2; 32],
+        message: b"foo",
+    };
+    let native = solana_native_sigverify::new_instruction_data(&[entry]);
+    let native = native.unwrap();
+
+    let update = |instructions: &[_], strict| {
+        let mut sig_data = [0; 12 + 32];
+        let mut sig_lamports = 42;
+        let key = Pubkey::new_unique();
+        let account = crate::api::test_account(
+            &key,
+            &program_id,
+            &mut sig_lamports,
+            &mut sig_data,
+        );
+        test_update(&account, instructions, None, strict)
+    };
+
+    let good = [
+        test_instruction(&crate::algo::Ed25519::ID, &native),
+        test_instruction(&program_id, &[0x20]),
+    ];
+    assert_eq!(Ok(1), update(&good, false));
+    assert_eq!(Ok(1), update(&good, true));
+
+    let bad = [
+        test_instruction(&other, b"foo"),
+        test_instruction(&program_id, &[0x20]),
+    ];
+    assert_eq!(Ok(0), update(&bad, false));
+    assert_eq!(Err(ProgramError::InvalidInstructionData), update(&bad, true));
+
+    let alone = [test_instruction(&program_id, &[0x20])];
+    assert_eq!(Ok(0), update(&alone, false));
+    assert_eq!(Err(ProgramError::InvalidInstructionData), update(&alone, true));
+}
+
+#[test]
+fn test_update_secp256k1() {
+    use solana_native_sigverify::Secp256k1Entry;
+
+    let program_id = Pubkey::new_unique();
+    let (signature, recovery_id, eth_address) = secp256k1_sign(b"foo", 1);
+    let entry = Secp256k1Entry {
+        signature: &signature,
+        recovery_id,
+        eth_address: &eth_address,
+        message: b"foo",
+    };
+    let native =
+        solana_native_sigverify::new_secp256k1_instruction_data(0, &[entry])
+            .unwrap();
+
+    let update = |native_program| {
+        let instructions = [
+            test_instruction(native_program, &native),
+            test_instruction(&program_id, &[0]),
+        ];
+        // The account has lamports so it’s not created again.
+        let mut sig_data = [0; 12 + 32];
+        let mut sig_lamports = 42;
+        let key = Pubkey::new_unique();
+        let account = crate::api::test_account(
+            &key,
+            &program_id,
+            &mut sig_lamports,
+            &mut sig_data,
+        );
+        test_update(&account, &instructions, None, false)?;
+        let account = SignaturesAccount(&account);
+        let mut full_signature = [0; 65];
+        full_signature[..64].copy_from_slice(&signature);
+        full_signature[64] = recovery_id;
+        Ok::<_, ProgramError>((
+            account.find_secp256k1(&eth_address, &full_signature, b"foo")?,
+            account.position_secp256k1(
+                &eth_address,
+                &full_signature,
+                b"foo",
+            )?,
+            account.find_many_secp256k1(&[
+                (&eth_address, &full_signature, b"foo"),
+                (&eth_address, &full_signature, b"bar"),
+            ])?,
+        ))
+    };
+
+    let got = update(&crate::algo::Secp256k1::ID);
+    assert_eq!(Ok((true, Some(0), vec![true, false])), got);
+}
+
+#[test]
+fn test_update_secp256r1() {
+    let program_id = Pubkey::new_unique();
+    let entry = solana_native_sigverify::Entry {
+        signature: &[1; 64],
+        pubkey: &[2; 32],
+        message: b"foo",
+    };
+    let native = solana_native_sigverify::new_instruction_data(&[entry]);
+    let native = native.unwrap();
+    let magic = crate::algo::Ed25519::MAGIC;
+
+    // Secp256r1 calls aren’t parsed.  In lenient mode they are skipped and
+    // the scan continues past them; in strict mode they are rejected.
+    let instructions = [
+        test_instruction(&crate::algo::Ed25519::ID, &native),
+        test_instruction(&crate::algo::Secp256r1::ID, b"r1"),
+        test_instruction(&program_id, &[0]),
+    ];
+    let update = |strict| {
+        let mut sig_data = [0; 12 + 32];
+        let mut sig_lamports = 42;
+        let key = Pubkey::new_unique();
+        let account = crate::api::test_account(
+            &key,
+            &program_id,
+            &mut sig_lamports,
+            &mut sig_data,
+        );
+        test_update(&account, &instructions, None, strict)?;
+        SignaturesAccount(&account)
+            .find_hash(&SigHash::from_entry(magic, entry))
+    };
+    assert_eq!(Ok(true), update(false));
+    assert_eq!(Err(ProgramError::InvalidInstructionData), update(true));
+}
+
+#[test]
+fn test_update_length_prefixed() {
+    use solana_native_sigverify::Entry;
+
+    let program_id = Pubkey::new_unique();
+    let entries = [b"foo".as_slice(), b"foobar"].map(|message| Entry {
+        signature: &[1; 64],
+        pubkey: &[2; 32],
+        message,
+    });
+    let native = solana_native_sigverify::new_instruction_data(&entries);
+    let native = native.unwrap();
+    let magic = crate::algo::Ed25519::MAGIC;
+
+    let mut data = [0; 12 + 2 * 32];
+    let mut lamports = 42;
+    let key = Pubkey::new_unique();
+    let account =
+        crate::api::test_account(&key, &program_id, &mut lamports, &mut data);
+    let signatures = SignaturesAccount(&account);
+    let instructions = [
+        test_instruction(&crate::algo::Ed25519::ID, &native),
+        test_instruction(&program_id, &[LENGTH_PREFIXED_FLAG]),
+    ];
+    let update =
+        |flags| test_update_with_flags(&account, &instructions, None, flags);
+
+    assert_eq!(Ok(2), update(LENGTH_PREFIXED_FLAG));
+    for entry in entries {
+        let find = |sighash| signatures.find_hash(&sighash);
+        let prefixed = SigHash::from_entry_length_prefixed(magic, entry);
+        assert_eq!(Ok(true), find(prefixed));
+        assert_eq!(Ok(false), find(SigHash::from_entry(magic, entry)));
+    }
+    let found = signatures.find_hash(&SigHash::new_length_prefixed(
+        magic, &[2; 32], &[1; 64], b"foobar",
+    ));
+    assert_eq!(Ok(true), found);
+
+    // Length-prefixed and hashed-message modes cannot be combined.
+    assert_eq!(
+        Err(ProgramError::InvalidInstructionData),
+        update(LENGTH_PREFIXED_FLAG | HASHED_FLAG)
+    );
+}
+
+#[test]
+fn test_update_contiguous_calls() {
+    use solana_native_sigverify::Entry;
+
+    let program_id = Pubkey::new_unique();
+    let other = Pubkey::new_unique();
+    let entries = [b"foo", b"bar", b"baz"].map(|message| Entry {
+        signature: &[1; 64],
+        pubkey: &[2; 32],
+        message,
+    });
+    let native = entries
+        .map(|entry| solana_native_sigverify::new_instruction_data(&[entry]))
+        .map(Result::unwrap);
+
+    // The scan goes backwards from the Update and stops at the first
+    // instruction which isn’t a native program call so the first call isn’t
+    // processed.
+    let instructions = [
+        test_instruction(&crate::algo::Ed25519::ID, &native[0]),
+        test_instruction(&other, b"foo"),
+        test_instruction(&crate::algo::Ed25519::ID, &native[1]),
+        test_instruction(&crate::algo::Ed25519::ID, &native[2]),
+        test_instruction(&program_id, &[0]),
+    ];
+
+    let mut sig_data = [0; 12 + 3 * 32];
+    let mut sig_lamports = 42;
+    let key = Pubkey::new_unique();
+    let account = crate::api::test_account(
+        &key,
+        &program_id,
+        &mut sig_lamports,
+        &mut sig_data,
+    );
+    assert_eq!(Ok(2), test_update(&account, &instructions, None, true));
+    let signatures = SignaturesAccount(&account);
+    let magic = crate::algo::Ed25519::MAGIC;
+    let find = |entry| signatures.find_hash(&SigHash::from_entry(magic, entry));
+    assert_eq!(Ok(false), find(entries[0]));
+    assert_eq!(Ok(true), find(entries[1]));
+    assert_eq!(Ok(true), find(entries[2]));
+}
+
+#[test]
+fn test_free_with_authority() {
+    use core::cell::UnsafeCell;
+
+    let program_id = Pubkey::new_unique();
+    let payer_key = Pubkey::new_unique();
+    let authority_key = Pubkey::new_unique();
+    let seed = b"seed";
+    let find = |key: &Pubkey| {
+        Pubkey::find_program_address(&[key.as_ref(), seed], &program_id)
+    };
+
+    let free = |signatures_key: &Pubkey, bump, authority_signs| {
+        let mut data = vec![0x81, seed.len() as u8];
+        data.extend_from_slice(seed);
+        data.push(bump);
+
+        let mut payer_lamports = 10;
+        let mut sig_lamports = 42;
+        let mut authority_lamports = 0;
+        // Free assigns the Signatures account to the system program which
+        // writes through the owner reference.
+        let owner = UnsafeCell::new(program_id);
+        let accounts = [
+            AccountInfo {
+                is_signer: true,
+                is_writable: true,
+                ..crate::api::test_account(
+                    &payer_key,
+                    &solana_program::system_program::ID,
+                    &mut payer_lamports,
+                    &mut [],
+                )
+            },
+            AccountInfo {
+                is_writable: true,
+                ..crate::api::test_account(
+                    signatures_key,
+                    // SAFETY: owner isn’t accessed other than through the
+                    // account until the account is dropped.
+                    unsafe { &*owner.get() },
+                    &mut sig_lamports,
+                    &mut [],
+                )
+            },
+            AccountInfo {
+                is_signer: authority_signs,
+                ..crate::api::test_account(
+                    &authority_key,
+                    &solana_program::system_program::ID,
+                    &mut authority_lamports,
+                    &mut [],
+                )
+            },
+        ];
+        process_instruction(&program_id, &accounts, &data)?;
+        drop(accounts);
+        assert_eq!(solana_program::system_program::ID, owner.into_inner());
+        Ok((payer_lamports, sig_lamports, authority_lamports))
+    };
+
+    // The Signatures account is a PDA of the authority and its lamports go to
+    // the payer.
+    let (key, bump) = find(&authority_key);
+    assert_eq!(Ok((52, 0, 0)), free(&key, bump, true));
+
+    // Authority must sign.
+    assert_eq!(
+        Err(ProgramError::MissingRequiredSignature),
+        free(&key, bump, false)
+    );
+
+    // PDA of the payer isn’t accepted when authority is used.
+    let (key, bump) = find(&payer_key);
+    assert_eq!(Err(ProgramError::InvalidSeeds), free(&key, bump, true));
+}
+
+#[test]
+fn test_remove() {
+    use solana_native_sigverify::Entry;
+
+    let program_id = Pubkey::new_unique();
+    let entries = [b"foo", b"bar"].map(|message| Entry {
+        signature: &[1; 64],
+        pubkey: &[2; 32],
+        message,
+    });
+    let native = solana_native_sigverify::new_instruction_data(&entries);
+    let native = native.unwrap();
+    let magic = crate::algo::Ed25519::MAGIC;
+    let sighashes = entries.map(|entry| SigHash::from_entry(magic, entry));
+
+    let mut sig_data = [0; 12 + 2 * 32];
+    let mut sig_lamports = 42;
+    let key = Pubkey::new_unique();
+    let account = crate::api::test_account(
+        &key,
+        &program_id,
+        &mut sig_lamports,
+        &mut sig_data,
+    );
+    let signatures = SignaturesAccount(&account);
+    let remove = |sighash: &SigHash| {
+        let ctx = Context {
+            program_id: &program_id,
+            payer: &account,
+            authority: &account,
+            signatures,
+            seed_and_bump: &[0],
+        };
+        handle_remove(ctx, sighash.as_ref())?;
+        signatures.count()
+    };
+
+    let instructions = [
+        test_instruction(&crate::algo::Ed25519::ID, &native),
+        test_instruction(&program_id, &[0]),
+    ];
+    assert_eq!(Ok(2), test_update(&account, &instructions, Some(1), false));
+
+    // Update → Remove → the signature is no longer found.
+    assert_eq!(Ok(1), remove(&sighashes[0]));
+    assert_eq!(Ok(false), signatures.find_hash(&sighashes[0]));
+    assert_eq!(Ok(true), signatures.find_hash(&sighashes[1]));
+
+    // Removing missing signature is a no-op.
+    assert_eq!(Ok(1), remove(&sighashes[0]));
+    assert_eq!(Ok(1), remove(&SigHash::from([42; 32])));
+
+    // Update with a different epoch resets the account.  The signature from
+    // the old epoch is left in the account data past the count but it’s
+    // neither found nor removed.
+    let instructions = [test_instruction(&program_id, &[0])];
+    assert_eq!(Ok(0), test_update(&account, &instructions, Some(2), false));
+    assert_eq!(Ok(false), signatures.find_hash(&sighashes[1]));
+    assert_eq!(Ok(0), remove(&sighashes[1]));
+    assert_eq!(Ok(2), signatures.epoch());
+
+    // Malformed instruction data is rejected.
+    let ctx = Context {
+        program_id: &program_id,
+        payer: &account,
+        authority: &account,
+        signatures,
+        seed_and_bump: &[0],
+    };
+    assert_eq!(
+        Err(ProgramError::InvalidInstructionData),
+        handle_remove(ctx, &[0; 31])
+    );
+}
+
+#[test]
+fn test_clear_magic() {
+    use solana_native_sigverify::{Entry, Secp256k1Entry};
+
+    let program_id = Pubkey::new_unique();
+    let (signature, recovery_id, eth_address) = secp256k1_sign(b"foo", 1);
+    let secp256k1 = Secp256k1Entry {
+        signature: &signature,
+        recovery_id,
+        eth_address: &eth_address,
+        message: b"foo",
+    };
+    let secp256k1_sighash =
+        SigHash::from_secp256k1_entry(crate::algo::Secp256k1::MAGIC, secp256k1);
+    let secp256k1 =
+        solana_native_sigverify::new_secp256k1_instruction_data(0, &[
+            secp256k1,
+        ])
+        .unwrap();
+    let entries = [b"foo", b"bar"].map(|message| Entry {
+        signature: &[1; 64],
+        pubkey: &[2; 32],
+        message,
+    });
+    let ed25519 = solana_native_sigverify::new_instruction_data(&entries);
+    let ed25519 = ed25519.unwrap();
+    let magic = crate::algo::Ed25519::MAGIC;
+    let sighashes = entries.map(|entry| SigHash::from_entry(magic, entry));
+
+    let payer_key = Pubkey::new_unique();
+    let seed = b"seed";
+    let (key, bump) =
+        Pubkey::find_program_address(&[payer_key.as_ref(), seed], &program_id);
+    let mut payer_lamports = 10;
+    let payer = AccountInfo {
+        is_signer: true,
+        is_writable: true,
+        ..crate::api::test_account(
+            &payer_key,
+            &solana_program::system_program::ID,
+            &mut payer_lamports,
+            &mut [],
+        )
+    };
+    let mut sig_data = [0; 16 + 3 * 36];
+    let mut sig_lamports = 42;
+    let account = AccountInfo {
+        is_writable: true,
+        ..crate::api::test_account(
+            &key,
+            &program_id,
+            &mut sig_lamports,
+            &mut sig_data,
+        )
+    };
+    let signatures = SignaturesAccount(&account);
+    signatures.write_version(true).unwrap();
+
+    // Runs Remove or Clear operation, depending on length of `data`, through
+    // the program’s entrypoint.
+    let remove = |data: &[u8]| {
+        let data = [&[2, seed.len() as u8][..], seed, &[bump], data].concat();
+        let accounts = [payer.clone(), account.clone()];
+        process_instruction(&program_id, &accounts, &data)?;
+        signatures.count()
+    };
+
+    let instructions = [
+        test_instruction(&crate::algo::Secp256k1::ID, &secp256k1),
+        test_instruction(&crate::algo::Ed25519::ID, &ed25519),
+        test_instruction(&program_id, &[FULL_ENTRY_FLAG]),
+    ];
+    let update =
+        |flags| test_update_with_flags(&account, &instructions, None, flags);
+    assert_eq!(Ok(3), update(FULL_ENTRY_FLAG));
+    assert_eq!(Ok(Some(magic)), signatures.magic_of(&sighashes[0]));
+
+    // Update must use the account’s layout.
+    assert_eq!(Err(ProgramError::InvalidAccountData), update(0));
+
+    // Clear → Ed25519 signatures are gone and the Secp256k1 one remains.
+    assert_eq!(Ok(1), remove(&magic.to_bytes()));
+    assert_eq!(Ok(false), signatures.find_hash(&sighashes[0]));
+    assert_eq!(Ok(false), signatures.find_hash(&sighashes[1]));
+    assert_eq!(Ok(true), signatures.find_hash(&secp256k1_sighash));
+
+    // Clearing algorithm with no stored signatures is a no-op.
+    assert_eq!(Ok(1), remove(&magic.to_bytes()));
+
+    // Malformed magic is rejected.
+    assert_eq!(Err(ProgramError::InvalidInstructionData), remove(&[0; 4]));
+
+    // Signature hash still selects the Remove operation.
+    assert_eq!(Ok(0), remove(secp256k1_sighash.as_ref()));
+
+    // Plain accounts don’t store magic so they cannot be cleared.
+    let mut plain_data = [0; 12 + 32];
+    let mut plain_lamports = 42;
+    let plain = crate::api::test_account(
+        &key,
+        &program_id,
+        &mut plain_lamports,
+        &mut plain_data,
+    );
+    let ctx = Context {
+        program_id: &program_id,
+        payer: &payer,
+        authority: &payer,
+        signatures: SignaturesAccount(&plain),
+        seed_and_bump: &[0],
+    };
+    assert_eq!(
+        Err(AccountError::MagicNotStored.into()),
+        handle_clear(ctx, &magic.to_bytes())
+    );
+}
+
+#[test]
+fn test_query() {
+    let program_id = Pubkey::new_unique();
+    let mut data = [0; 12 + 32];
+    data[..8].copy_from_slice(&7u64.to_le_bytes());
+    data[8] = 1;
+    let mut lamports = 42;
+    let key = Pubkey::new_unique();
+    let account =
+        crate::api::test_account(&key, &program_id, &mut lamports, &mut data);
+    let accounts = [account];
+
+    assert_eq!(Ok((7, 1)), query(&program_id, &accounts, &[]));
+    assert_eq!(Ok(()), process_instruction(&program_id, &accounts, &[3]));
+
+    // Trailing data and flags are rejected.
+    let err = ProgramError::InvalidInstructionData;
+    assert_eq!(Err(err.clone()), query(&program_id, &accounts, &[0]));
+    let res = process_instruction(&program_id, &accounts, &[3, 0]);
+    assert_eq!(Err(err.clone()), res);
+    for tag in [0x83, 0x43, 0x23, 0x13, 0x0b, 0x07] {
+        let res = process_instruction(&program_id, &accounts, &[tag]);
+        assert_eq!(Err(err.clone()), res);
+    }
+
+    // Account owned by a different program is rejected.
+    let other = Pubkey::new_unique();
+    assert_eq!(
+        Err(ProgramError::InvalidAccountOwner),
+        query(&other, &accounts, &[])
+    );
+    assert_eq!(
+        Err(ProgramError::NotEnoughAccountKeys),
+        query(&program_id, &[], &[])
+    );
+
+    // Account which doesn’t exist reports zeros.
+    let [account] = accounts;
+    **account.lamports.borrow_mut() = 0;
+    assert_eq!(Ok((0, 0)), query(&program_id, &[account], &[]));
+}
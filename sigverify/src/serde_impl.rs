@@ -0,0 +1,81 @@
+//! Serde support for [`SigHash`].
+//!
+//! In human-readable formats (such as JSON) the hash is serialised as
+//! a string of 64 lowercase hexadecimal digits, same as its [`Display`]
+//! representation.  In other formats it’s serialised as bytes.
+//!
+//! [`Display`]: core::fmt::Display
+
+use serde::de::{self, Deserializer, SeqAccess, Visitor};
+use serde::ser::Serializer;
+
+use crate::SigHash;
+
+impl serde::Serialize for SigHash {
+    fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        if ser.is_human_readable() {
+            ser.collect_str(self)
+        } else {
+            ser.serialize_bytes(self.as_ref())
+        }
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for SigHash {
+    fn deserialize<D: Deserializer<'de>>(de: D) -> Result<Self, D::Error> {
+        if de.is_human_readable() {
+            de.deserialize_str(SigHashVisitor)
+        } else {
+            de.deserialize_bytes(SigHashVisitor)
+        }
+    }
+}
+
+struct SigHashVisitor;
+
+impl<'de> Visitor<'de> for SigHashVisitor {
+    type Value = SigHash;
+
+    fn expecting(&self, fmt: &mut core::fmt::Formatter) -> core::fmt::Result {
+        fmt.write_str("a 32-byte signature hash")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+        value.parse().map_err(E::custom)
+    }
+
+    fn visit_bytes<E: de::Error>(self, value: &[u8]) -> Result<Self::Value, E> {
+        <[u8; 32]>::try_from(value)
+            .map(SigHash::from)
+            .map_err(|_| E::invalid_length(value.len(), &self))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(
+        self,
+        mut seq: A,
+    ) -> Result<Self::Value, A::Error> {
+        let mut hash = [0; 32];
+        for (idx, byte) in hash.iter_mut().enumerate() {
+            *byte = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(idx, &self))?;
+        }
+        if seq.next_element::<u8>()?.is_some() {
+            return Err(de::Error::invalid_length(33, &self));
+        }
+        Ok(SigHash::from(hash))
+    }
+}
+
+
+#[test]
+fn test_json() {
+    let hash = SigHash::from([0xab; 32]);
+    let json = serde_json::to_string(&hash).unwrap();
+    assert_eq!(format!("\"{}\"", "ab".repeat(32)), json);
+    assert_eq!(hash, serde_json::from_str::<SigHash>(&json).unwrap());
+
+    assert!(serde_json::from_str::<SigHash>("\"abab\"").is_err());
+    let json = format!("\"{}\"", "zz".repeat(32));
+    assert!(serde_json::from_str::<SigHash>(&json).is_err());
+}
@@ -47,7 +47,8 @@ pub(crate) fn as_chunks<const N: usize, T>(slice: &[T]) -> (&[[T; N]], &[T]) {
 
     // SAFETY: We cast a slice of `len * N` elements into a slice of `len` many
     // `N` elements chunks.
-    let head = unsafe { std::slice::from_raw_parts(head.as_ptr().cast(), len) };
+    let head =
+        unsafe { core::slice::from_raw_parts(head.as_ptr().cast(), len) };
     (head, tail)
 }
 
@@ -63,7 +64,7 @@ pub(crate) fn as_chunks_mut<const N: usize, T>(
     // SAFETY: We cast a slice of `len * N` elements into a slice of `len` many
     // `N` elements chunks.
     let head = unsafe {
-        std::slice::from_raw_parts_mut(head.as_mut_ptr().cast(), len)
+        core::slice::from_raw_parts_mut(head.as_mut_ptr().cast(), len)
     };
     (head, tail)
 }
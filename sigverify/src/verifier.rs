@@ -1,8 +1,8 @@
-use solana_native_sigverify::Entry;
+use solana_native_sigverify::{Entry, OwnedEntry};
 use solana_program::account_info::AccountInfo;
 use solana_program::program_error::ProgramError;
 use solana_program::pubkey::Pubkey;
-use solana_program::sysvar::instructions::get_instruction_relative;
+use solana_program::sysvar::instructions;
 
 use crate::algo;
 
@@ -21,14 +21,46 @@ type Result<T = (), E = ProgramError> = core::result::Result<T, E>;
 /// this crate.  The program aggregates into a single account checks done by
 /// multiple calls to the native program and this verifier accesses that account
 /// to look for signatures being checked.
+///
+/// Additionally, the verifier can be configured with a set of known signature
+/// hashes (see [`Self::set_known_sighashes`]) which are accepted without any
+/// further checks.  This is useful for small static allowlists (e.g. hard-coded
+/// emergency signatures) which don’t warrant a separate account.
+///
+/// # Security
+///
+/// The verifier trusts that the signatures account contains only signatures
+/// which have been verified.  This is true only if the account is owned by
+/// a genuine sigverify program.  An attacker can deploy their own program
+/// which writes arbitrary signature hashes into accounts it owns.  If the
+/// verifier accepted such an account, any signature could be forged.
+///
+/// It’s therefore critical that the program using the verifier knows the
+/// address of the trusted sigverify program (typically by hard-coding it) and
+/// only accepts accounts owned by it.  The recommended way is to create the
+/// verifier with [`Self::new`] passing the trusted program id and then use
+/// [`Self::set_trusted_sigverify_account`].  [`assert_sigverify_program_id`]
+/// can be used to check at compile time that the hard-coded address isn’t an
+/// obvious mistake.
 #[derive(Clone)]
 pub struct Verifier<'info, Algo> {
-    /// Instruction data of a call to a native signature verification program.
-    native_data: Option<Vec<u8>>,
+    /// Signatures verified by calls to a native signature verification
+    /// program.  The entries are sorted so they can be binary searched.
+    native_entries: Vec<OwnedEntry>,
 
-    /// Account data owned by sigverify program with aggregated signature
+    /// Data of accounts owned by sigverify program with aggregated signature
     /// checks.
-    sigverify_data: Option<AccountData<'info>>,
+    sigverify_data: Vec<AccountData<'info>>,
+
+    /// Owned copies of sigverify accounts data, e.g. fetched over RPC.  See
+    /// [`Self::set_sigverify_data`].
+    owned_sigverify_data: Vec<Vec<u8>>,
+
+    /// Address of the trusted sigverify program.
+    sigverify_program_id: Option<Pubkey>,
+
+    /// Signature hashes which are accepted unconditionally.
+    known_sighashes: &'info [crate::SigHash],
 
     phantom: core::marker::PhantomData<Algo>,
 }
@@ -43,6 +75,25 @@ pub type Secp256k1Verifier<'info> = Verifier<'info, algo::Secp256k1>;
 pub type Secp256r1Verifier<'info> = Verifier<'info, algo::Secp256r1>;
 
 
+/// Source which confirmed a signature.
+///
+/// Returned by [`Verifier::verify_source`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum VerifySource {
+    /// The signature has been checked by a call to native signature
+    /// verification program in the current transaction.
+    NativeInstruction,
+
+    /// The signature has been found in an account of the sigverify program
+    /// with aggregated signature checks.
+    AggregatedAccount,
+
+    /// The signature is one of the known signatures (see
+    /// [`Verifier::set_known_sighashes`]).
+    KnownSigHash,
+}
+
+
 /// Error during signature verification.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Error {
@@ -71,13 +122,36 @@ impl<Algo> Default for Verifier<'_, Algo> {
     /// signatures.
     fn default() -> Self {
         Self {
-            native_data: None,
-            sigverify_data: None,
+            native_entries: Vec::new(),
+            sigverify_data: Vec::new(),
+            owned_sigverify_data: Vec::new(),
+            sigverify_program_id: None,
+            known_sighashes: &[],
             phantom: Default::default(),
         }
     }
 }
 
+impl<Algo> Verifier<'_, Algo> {
+    /// Creates a new verifier which trusts given sigverify program.
+    ///
+    /// `sigverify_program_id` is the address of the trusted sigverify program.
+    /// Only accounts owned by that program will be accepted by
+    /// [`Self::set_trusted_sigverify_account`].  See [Security section] for
+    /// discussion why this is important.
+    ///
+    /// Other than that, the verifier is the same as one created with
+    /// [`Self::default`] and needs to be initialised the same way.
+    ///
+    /// [Security section]: Verifier#security
+    pub fn new(sigverify_program_id: Pubkey) -> Self {
+        Self {
+            sigverify_program_id: Some(sigverify_program_id),
+            ..Self::default()
+        }
+    }
+}
+
 impl<'info, Algo: algo::Algorithm> Verifier<'info, Algo> {
     /// Specifies instructions sysvar to use to get call to Ed25519 native
     /// program.
@@ -85,26 +159,127 @@ impl<'info, Algo: algo::Algorithm> Verifier<'info, Algo> {
     /// The account must be owned by the [Instructions sysvar].  The account is
     /// used to retrieve the previous instruction and check if it was call to
     /// [Ed25519 native program].  If it was, that instruction’s data will be
-    /// used to check for signatures.
+    /// used to check for signatures.  Calling this method (or
+    /// [`Self::set_ix_sysvar_at`]) multiple times adds data of each of the
+    /// instructions such that signatures checked by any of them are accepted.
+    ///
+    /// Returns [`ProgramError::IncorrectProgramId`] if `account` isn’t the
+    /// Instructions sysvar or if the previous instruction isn’t a call to the
+    /// native program.  The account address is checked explicitly before
+    /// reading the sysvar so that passing a wrong account results in this
+    /// error rather than an error from reading the sysvar.
     ///
     /// [Instruction sysvar]: https://docs.solana.com/developing/runtime-facilities/sysvars#instructions
     /// [Ed25519 native program]: https://docs.solana.com/developing/runtime-facilities/programs#ed25519-program
     #[inline]
     pub fn set_ix_sysvar(&mut self, account: &AccountInfo) -> Result {
-        let ix = get_instruction_relative(-1, account)?;
+        self.set_ix_sysvar_at(account, -1)
+    }
+
+    /// Specifies instructions sysvar to use to get call to native signature
+    /// verification program at given relative index.
+    ///
+    /// This is like [`Self::set_ix_sysvar`] except that rather than using the
+    /// previous instruction, the instruction at `index` relative to the
+    /// current one is used.  For example, `-1` is the previous instruction
+    /// (which is what `set_ix_sysvar` uses) and `-2` is the one before it.
+    /// This is useful if the call to the native program doesn’t directly
+    /// precede the current instruction.
+    ///
+    /// Returns [`ProgramError::IncorrectProgramId`] if `account` isn’t the
+    /// Instructions sysvar or if the instruction at given index isn’t a call
+    /// to the native program.  Returns [`ProgramError::InvalidAccountData`]
+    /// if the instruction data is malformed.
+    pub fn set_ix_sysvar_at(
+        &mut self,
+        account: &AccountInfo,
+        index: i64,
+    ) -> Result {
+        if !instructions::check_id(account.key) {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let ix = instructions::get_instruction_relative(index, account)?;
         if Algo::check_id(ix.program_id) {
-            self.native_data = Some(ix.data);
-            Ok(())
+            self.set_native_data(&ix.data)
         } else {
             Err(ProgramError::IncorrectProgramId)
         }
     }
 
+    /// Uses all calls to native signature verification program in the
+    /// transaction.
+    ///
+    /// This is like [`Self::set_ix_sysvar`] except that rather than looking at
+    /// a single instruction, it goes through all instructions in the
+    /// transaction and uses data of each call to the native program of the
+    /// verifier’s algorithm.  [`Self::verify`] then accepts signatures checked
+    /// by any of those calls.  This makes the verifier independent of the
+    /// position of the native program calls in the transaction (e.g. if
+    /// a wallet reorders instructions).
+    ///
+    /// Note that this costs more compute units than `set_ix_sysvar` since
+    /// every instruction in the transaction is deserialised and data of each
+    /// of the native program calls is parsed.
+    ///
+    /// Returns [`ProgramError::IncorrectProgramId`] if `account` isn’t the
+    /// Instructions sysvar.  Unlike `set_ix_sysvar`, it’s not an error if
+    /// there are no calls to the native program.
+    pub fn scan_all_instructions(&mut self, account: &AccountInfo) -> Result {
+        if !instructions::check_id(account.key) {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        // The Instructions sysvar data starts with number of instructions.
+        let count = {
+            let data = account.try_borrow_data()?;
+            let (count, _) = crate::stdx::split_at::<2, u8>(&data)
+                .ok_or(ProgramError::InvalidAccountData)?;
+            u16::from_le_bytes(*count)
+        };
+        for index in 0..count {
+            let ix = instructions::load_instruction_at_checked(
+                usize::from(index),
+                account,
+            )?;
+            if Algo::check_id(ix.program_id) {
+                self.set_native_data(&ix.data)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses native program instruction data and adds signatures it verifies
+    /// to the set of verified signatures.
+    ///
+    /// This is what [`Self::set_ix_sysvar`] does after fetching the
+    /// instruction from the Instructions sysvar.  Calling it directly is
+    /// useful in tests and off-chain simulations where the native program
+    /// instruction data is already at hand.  Note however that on chain the
+    /// data must come from the Instructions sysvar or otherwise there is no
+    /// guarantee that the signatures have actually been verified.
+    ///
+    /// The data is parsed once so that [`Self::verify`] is a lookup in a sorted
+    /// list of signatures rather than parsing the data on each call.
+    ///
+    /// Returns [`ProgramError::InvalidAccountData`] if the data is malformed.
+    pub fn set_native_data(&mut self, data: &[u8]) -> Result {
+        let entries = parse_native_data(data)?;
+        self.native_entries.extend(entries.into_iter().map(OwnedEntry::from));
+        self.native_entries.sort_unstable();
+        self.native_entries.dedup();
+        Ok(())
+    }
+
     /// Specifies account owned by sigverify program which holds aggregated
     /// attested signatures.
     ///
     /// Returns error if `account` isn’t owned by `expected_owner`.
     /// `expected_owner` should be set to program id of the sigverify program.
+    /// Calling this method multiple times adds each of the accounts such that
+    /// signatures stored in any of them are accepted.
+    /// It must be address of a trusted sigverify program rather than anything
+    /// controlled by the caller; see [Security section].
+    ///
+    /// [Security section]: Verifier#security
     #[inline]
     pub fn set_sigverify_account(
         &mut self,
@@ -112,13 +287,67 @@ impl<'info, Algo: algo::Algorithm> Verifier<'info, Algo> {
         expected_owner: &Pubkey,
     ) -> Result {
         if account.owner == expected_owner {
-            self.sigverify_data = Some(account.data.clone());
+            self.sigverify_data.push(account.data.clone());
             Ok(())
         } else {
             Err(ProgramError::InvalidAccountOwner)
         }
     }
 
+    /// Adds a snapshot of sigverify account data with aggregated attested
+    /// signatures.
+    ///
+    /// This is an off-chain counterpart of [`Self::set_sigverify_account`].
+    /// It’s intended for clients which fetched the account over RPC and want
+    /// to check whether a signature would be accepted before submitting
+    /// a transaction.  Unlike `set_sigverify_account`, the owner of the
+    /// account isn’t checked so it’s up to the caller to make sure the data
+    /// comes from an account owned by a trusted sigverify program; see
+    /// [Security section].
+    ///
+    /// The data isn’t validated until it’s used.  If it’s malformed,
+    /// [`Self::verify`] returns [`Error::BadData`].
+    ///
+    /// [Security section]: Verifier#security
+    pub fn set_sigverify_data(&mut self, data: Vec<u8>) {
+        self.owned_sigverify_data.push(data);
+    }
+
+    /// Specifies account owned by the trusted sigverify program which holds
+    /// aggregated attested signatures.
+    ///
+    /// The trusted sigverify program is the one specified when creating the
+    /// verifier with [`Self::new`].  Returns error if `account` isn’t owned by
+    /// that program or if the verifier has been created without specifying
+    /// the trusted program.
+    #[inline]
+    pub fn set_trusted_sigverify_account(
+        &mut self,
+        account: &AccountInfo<'info>,
+    ) -> Result {
+        let owner = self
+            .sigverify_program_id
+            .ok_or(ProgramError::InvalidAccountOwner)?;
+        self.set_sigverify_account(account, &owner)
+    }
+
+    /// Specifies signature hashes which are accepted unconditionally.
+    ///
+    /// In addition to signatures found in the native program call and the
+    /// sigverify account, [`Self::verify`] accepts signatures whose
+    /// [`SigHash`] is in `sighashes`.  The slice is scanned linearly so it’s
+    /// intended for small sets of signatures known ahead of time (e.g.
+    /// hard-coded in the program).  Replaces any previously set hashes.
+    ///
+    /// The hashes must be calculated with the magic of the verifier’s
+    /// algorithm (see [`algo::Algorithm::sighash`]).
+    ///
+    /// [`SigHash`]: crate::SigHash
+    #[inline]
+    pub fn set_known_sighashes(&mut self, sighashes: &'info [crate::SigHash]) {
+        self.known_sighashes = sighashes;
+    }
+
     /// Verifies given Ed25519 signature.
     ///
     /// For the check to succeed the verifier must be initialised as described
@@ -130,44 +359,239 @@ impl<'info, Algo: algo::Algorithm> Verifier<'info, Algo> {
         pubkey: &[u8; 32],
         signature: &[u8; 64],
     ) -> Result<bool, Error> {
+        self.verify_source(message, pubkey, signature)
+            .map(|source| source.is_some())
+    }
+
+    /// Verifies given signature and returns source which confirmed it.
+    ///
+    /// This is like [`Self::verify`] except that rather than returning
+    /// a boolean, it returns where the signature has been found.  Returns
+    /// `None` if the signature hasn’t been verified.  If multiple sources
+    /// confirm the signature, the first one in order of [`VerifySource`]
+    /// variants is returned.
+    pub fn verify_source(
+        &self,
+        message: &[u8],
+        pubkey: &[u8; 32],
+        signature: &[u8; 64],
+    ) -> Result<Option<VerifySource>, Error> {
         let entry = Entry { signature, pubkey, message };
-        if let Some(data) = self.native_data.as_ref() {
-            if check_native_data(data.as_slice(), &entry)? {
-                return Ok(true);
-            }
+        if self.find_native(&entry) {
+            return Ok(Some(VerifySource::NativeInstruction));
         }
-        if let Some(data) = self.sigverify_data.as_ref() {
+        self.find_sighash(Algo::sighash_entry(entry))
+    }
+
+    /// Looks for given SigHash in the aggregated signatures and known hashes.
+    ///
+    /// Unlike [`Self::verify_source`] this doesn’t look at native program
+    /// calls.
+    fn find_sighash(
+        &self,
+        sighash: crate::SigHash,
+    ) -> Result<Option<VerifySource>, Error> {
+        for data in self.sigverify_data.iter() {
             let data = data.try_borrow().map_err(|_| Error::BorrowFailed)?;
-            if check_sigverify_data(data.as_ref(), Algo::magic(), entry)? {
-                return Ok(true);
+            if check_sigverify_data(data.as_ref(), sighash)? {
+                return Ok(Some(VerifySource::AggregatedAccount));
             }
         }
-        Ok(false)
+        for data in self.owned_sigverify_data.iter() {
+            if check_sigverify_data(data, sighash)? {
+                return Ok(Some(VerifySource::AggregatedAccount));
+            }
+        }
+        if self.known_sighashes.contains(&sighash) {
+            return Ok(Some(VerifySource::KnownSigHash));
+        }
+        Ok(None)
+    }
+
+    /// Verifies given signature over hash of the message.
+    ///
+    /// This is for signatures created over the hash of a message (see
+    /// [`solana_native_sigverify::Entry::with_message_hash`]).  `message` is
+    /// the full message which is hashed with
+    /// [`solana_native_sigverify::hash_message`].
+    ///
+    /// Native program calls are checked for an entry with the hash as the
+    /// message.  Aggregated signatures and known hashes are checked for
+    /// [`SigHash::new_hashed`], i.e. signatures aggregated by the sigverify
+    /// program in hashed-message mode.
+    ///
+    /// [`SigHash::new_hashed`]: crate::SigHash::new_hashed
+    pub fn verify_hashed(
+        &self,
+        message: &[u8],
+        pubkey: &[u8; 32],
+        signature: &[u8; 64],
+    ) -> Result<bool, Error> {
+        let hash = solana_native_sigverify::hash_message(message);
+        let entry = Entry { signature, pubkey, message: &hash };
+        if self.find_native(&entry) {
+            return Ok(true);
+        }
+        let sighash = crate::SigHash::from_message_hash(
+            Algo::magic(),
+            pubkey,
+            signature,
+            &hash,
+        );
+        self.find_sighash(sighash).map(|source| source.is_some())
+    }
+
+    /// Verifies multiple signatures at once.
+    ///
+    /// Returns a vector whose `i`-th element indicates whether `entries[i]`
+    /// has been verified (see [`Self::verify`]).
+    ///
+    /// This is cheaper than calling `verify` for each entry separately.  The
+    /// sigverify account data is borrowed only once and if the same entry is
+    /// present in `entries` multiple times, it is checked (and its [`SigHash`]
+    /// calculated) only once.
+    ///
+    /// [`SigHash`]: crate::SigHash
+    pub fn verify_many(&self, entries: &[Entry]) -> Result<Vec<bool>, Error> {
+        let sigverify_data = self
+            .sigverify_data
+            .iter()
+            .map(|data| data.try_borrow().map_err(|_| Error::BorrowFailed))
+            .collect::<Result<Vec<_>, _>>()?;
+        let records = sigverify_data
+            .iter()
+            .map(|data| &data[..])
+            .chain(self.owned_sigverify_data.iter().map(Vec::as_slice))
+            .map(crate::api::read_records)
+            .collect::<Result<Vec<_>>>()
+            .map_err(|_| Error::BadData)?;
+
+        // Sort indices so that equal entries are next to each other.  This
+        // way each distinct entry is checked only once.
+        let mut order = (0..entries.len()).collect::<Vec<_>>();
+        order.sort_unstable_by_key(|&idx| entries[idx]);
+
+        let mut result = vec![false; entries.len()];
+        for group in order.chunk_by(|&a, &b| entries[a] == entries[b]) {
+            let entry = entries[group[0]];
+            let found =
+                self.find_native(&entry) ||
+                    {
+                        let sighash = Algo::sighash_entry(entry);
+                        records.iter().any(|records| {
+                            records.binary_search(&sighash).is_ok()
+                        }) || self.known_sighashes.contains(&sighash)
+                    };
+            for &idx in group {
+                result[idx] = found;
+            }
+        }
+        Ok(result)
+    }
+
+    /// Checks whether given signature has been verified by a native program
+    /// call.
+    fn find_native(&self, entry: &Entry) -> bool {
+        self.native_entries
+            .binary_search_by(|native| native.as_entry().cmp(entry))
+            .is_ok()
     }
 }
 
-/// Checks that given signature exists in given native program call instruction.
-fn check_native_data(data: &[u8], entry: &Entry) -> Result<bool, Error> {
-    for item in solana_native_sigverify::parse_data(data)? {
-        match item.map(|item| item == *entry) {
-            Ok(true) => return Ok(true),
-            Ok(false) => (),
-            Err(solana_native_sigverify::Error::UnsupportedFeature) => (),
-            Err(_) => return Err(Error::BadData),
+impl Verifier<'_, algo::Secp256k1> {
+    /// Verifies given Secp256k1 signature.
+    ///
+    /// `signature` is the 64-byte signature followed by one-byte recovery id.
+    /// Checks whether the signature has been aggregated by the sigverify
+    /// program from a Secp256k1 program call (i.e. whether its
+    /// [`SigHash::new_secp256k1`] hash is present in the aggregated signatures)
+    /// or is one of the known hashes.
+    ///
+    /// **Note** that native Secp256k1 program calls in the current transaction
+    /// aren’t consulted.
+    ///
+    /// [`SigHash::new_secp256k1`]: crate::SigHash::new_secp256k1
+    pub fn verify_secp256k1(
+        &self,
+        message: &[u8],
+        eth_address: &[u8; 20],
+        signature: &[u8; 65],
+    ) -> Result<bool, Error> {
+        let sighash = crate::SigHash::new_secp256k1(
+            algo::Secp256k1::MAGIC,
+            eth_address,
+            signature,
+            message,
+        );
+        self.find_sighash(sighash).map(|source| source.is_some())
+    }
+}
+
+/// Collects all signatures present in given native program call instruction.
+///
+/// Entries which use unsupported features are ignored.
+fn parse_native_data(data: &[u8]) -> Result<Vec<Entry<'_>>, Error> {
+    solana_native_sigverify::parse_data(data)?
+        .filter(|item| {
+            *item != Err(solana_native_sigverify::Error::UnsupportedFeature)
+        })
+        .map(|item| item.map_err(|_| Error::BadData))
+        .collect()
+}
+
+/// Asserts that given sigverify program address isn’t an obvious mistake.
+///
+/// Panics if `id` is the default (all-zero) address, address of the system
+/// program or address of one of the native signature verification programs.
+/// None of those can be the sigverify program.  The function is `const` and
+/// is intended to be used in const context such that a mistake results in
+/// a compilation error:
+///
+/// ```
+/// use solana_program::pubkey::Pubkey;
+///
+/// const SIGVERIFY_PROGRAM_ID: Pubkey =
+///     solana_program::pubkey!("4VEPe5EMrGkcucbmZXYRSGBKAGt8eXfJYQtMtu2cP3he");
+/// const _: () =
+///     solana_sigverify::assert_sigverify_program_id(&SIGVERIFY_PROGRAM_ID);
+/// ```
+///
+/// Note that this cannot detect addresses of attacker-controlled programs.
+/// It only protects from leaving a placeholder address in the code.
+pub const fn assert_sigverify_program_id(id: &Pubkey) {
+    const fn eq(lhs: &Pubkey, rhs: &Pubkey) -> bool {
+        let (lhs, rhs) = (lhs.as_array(), rhs.as_array());
+        let mut idx = 0;
+        while idx < lhs.len() {
+            if lhs[idx] != rhs[idx] {
+                return false;
+            }
+            idx += 1;
         }
+        true
+    }
+
+    let invalid = [
+        Pubkey::new_from_array([0; 32]),
+        solana_program::system_program::ID,
+        algo::Ed25519::ID,
+        algo::Secp256k1::ID,
+        algo::Secp256r1::ID,
+    ];
+    let mut idx = 0;
+    while idx < invalid.len() {
+        assert!(!eq(id, &invalid[idx]), "invalid sigverify program id");
+        idx += 1;
     }
-    Ok(false)
 }
 
 /// Checks that given sigverify account with aggregated signatures contains
-/// given entry.
+/// given signature hash.
 fn check_sigverify_data(
     data: &[u8],
-    magic: algo::Magic,
-    entry: Entry,
+    sighash: crate::SigHash,
 ) -> Result<bool, Error> {
-    crate::api::find_sighash(data, crate::SigHash::from_entry(magic, entry))
-        .map_err(|_| Error::BadData)
+    crate::api::find_sighash(data, sighash).map_err(|_| Error::BadData)
 }
 
 impl From<solana_native_sigverify::BadData> for Error {
@@ -182,3 +606,219 @@ impl From<Error> for ProgramError {
         }
     }
 }
+
+
+#[test]
+fn test_verify_many() {
+    use algo::Algorithm;
+
+    let ent_a = Entry { signature: &[1; 64], pubkey: &[2; 32], message: b"a" };
+    let ent_b = Entry { signature: &[3; 64], pubkey: &[4; 32], message: b"b" };
+    let ent_c = Entry { signature: &[5; 64], pubkey: &[6; 32], message: b"c" };
+
+    let mut data = [0; 44];
+    data[8] = 1;
+    data[12..].copy_from_slice(algo::Ed25519::sighash_entry(ent_b).as_ref());
+
+    let mut verifier = Ed25519Verifier {
+        sigverify_data: vec![alloc::rc::Rc::new(core::cell::RefCell::new(
+            &mut data[..],
+        ))],
+        ..Default::default()
+    };
+    let native_data =
+        solana_native_sigverify::new_instruction_data(&[ent_a]).unwrap();
+    verifier.set_native_data(&native_data).unwrap();
+
+    let entries = [ent_a, ent_c, ent_b, ent_a, ent_c];
+    let want = [true, false, true, true, false];
+    assert_eq!(Ok(want.to_vec()), verifier.verify_many(&entries));
+    for (entry, want) in entries.iter().zip(want) {
+        let got = verifier.verify(entry.message, entry.pubkey, entry.signature);
+        assert_eq!(Ok(want), got);
+    }
+
+    let source = |entry: Entry| {
+        verifier.verify_source(entry.message, entry.pubkey, entry.signature)
+    };
+    assert_eq!(Ok(Some(VerifySource::NativeInstruction)), source(ent_a));
+    assert_eq!(Ok(Some(VerifySource::AggregatedAccount)), source(ent_b));
+    assert_eq!(Ok(None), source(ent_c));
+}
+
+#[test]
+fn test_set_native_data() {
+    let ent = Entry { signature: &[1; 64], pubkey: &[2; 32], message: b"a" };
+    let mut verifier = Ed25519Verifier::default();
+    assert_eq!(
+        Ok(false),
+        verifier.verify(ent.message, ent.pubkey, ent.signature)
+    );
+
+    let data = solana_native_sigverify::new_instruction_data(&[ent]).unwrap();
+    verifier.set_native_data(&data).unwrap();
+    assert_eq!(
+        Ok(true),
+        verifier.verify(ent.message, ent.pubkey, ent.signature)
+    );
+
+    assert_eq!(
+        Err(ProgramError::InvalidAccountData),
+        verifier.set_native_data(&data[..data.len() - 1])
+    );
+}
+
+#[test]
+fn test_set_sigverify_data() {
+    use algo::Algorithm;
+
+    let ent_a = Entry { signature: &[1; 64], pubkey: &[2; 32], message: b"a" };
+    let ent_b = Entry { signature: &[3; 64], pubkey: &[4; 32], message: b"b" };
+
+    let mut data = vec![0; 44];
+    data[8] = 1;
+    data[12..].copy_from_slice(algo::Ed25519::sighash_entry(ent_a).as_ref());
+
+    let mut verifier = Ed25519Verifier::default();
+    verifier.set_sigverify_data(data);
+
+    let source = |entry: Entry| {
+        verifier.verify_source(entry.message, entry.pubkey, entry.signature)
+    };
+    assert_eq!(Ok(Some(VerifySource::AggregatedAccount)), source(ent_a));
+    assert_eq!(Ok(None), source(ent_b));
+    assert_eq!(Ok(vec![true, false]), verifier.verify_many(&[ent_a, ent_b]));
+
+    verifier.set_sigverify_data(vec![0; 7]);
+    assert_eq!(Err(Error::BadData), verifier.verify_many(&[ent_b]));
+}
+
+#[test]
+fn test_known_sighashes() {
+    use algo::Algorithm;
+
+    let ent_a = Entry { signature: &[1; 64], pubkey: &[2; 32], message: b"a" };
+    let ent_b = Entry { signature: &[3; 64], pubkey: &[4; 32], message: b"b" };
+    let known = [algo::Ed25519::sighash_entry(ent_b)];
+
+    let mut verifier = Ed25519Verifier::default();
+    let verify = |verifier: &Ed25519Verifier, entry: Entry| {
+        verifier.verify(entry.message, entry.pubkey, entry.signature)
+    };
+    assert_eq!(Ok(false), verify(&verifier, ent_b));
+
+    verifier.set_known_sighashes(&known);
+    assert_eq!(Ok(false), verify(&verifier, ent_a));
+    assert_eq!(Ok(true), verify(&verifier, ent_b));
+    assert_eq!(
+        Ok(Some(VerifySource::KnownSigHash)),
+        verifier.verify_source(ent_b.message, ent_b.pubkey, ent_b.signature)
+    );
+    assert_eq!(Ok(vec![false, true]), verifier.verify_many(&[ent_a, ent_b]));
+
+    // Hashes for a different algorithm aren’t accepted.
+    let known = [algo::Secp256r1::sighash_entry(ent_b)];
+    verifier.set_known_sighashes(&known);
+    assert_eq!(Ok(false), verify(&verifier, ent_b));
+}
+
+#[test]
+fn test_verify_hashed() {
+    let hash = solana_native_sigverify::hash_message(b"foo");
+    let magic = algo::Ed25519::MAGIC;
+    let hashed = crate::SigHash::new_hashed(magic, &[2; 32], &[1; 64], b"foo");
+    let plain = crate::SigHash::new(magic, &[2; 32], &[1; 64], &hash);
+
+    let mut verifier = Ed25519Verifier::default();
+    let known = [plain];
+    verifier.set_known_sighashes(&known);
+    assert_eq!(Ok(false), verifier.verify_hashed(b"foo", &[2; 32], &[1; 64]));
+    assert_eq!(Ok(true), verifier.verify(&hash, &[2; 32], &[1; 64]));
+
+    let known = [hashed];
+    verifier.set_known_sighashes(&known);
+    assert_eq!(Ok(true), verifier.verify_hashed(b"foo", &[2; 32], &[1; 64]));
+    assert_eq!(Ok(false), verifier.verify_hashed(b"bar", &[2; 32], &[1; 64]));
+    assert_eq!(Ok(false), verifier.verify(&hash, &[2; 32], &[1; 64]));
+
+    // Native calls carry the hash as the message.
+    let entry = Entry::with_message_hash(&[1; 64], &[2; 32], &hash);
+    let data = solana_native_sigverify::new_instruction_data(&[entry]).unwrap();
+    let mut verifier = Ed25519Verifier::default();
+    verifier.set_native_data(&data).unwrap();
+    assert_eq!(Ok(true), verifier.verify_hashed(b"foo", &[2; 32], &[1; 64]));
+}
+
+#[test]
+fn test_verify_secp256k1() {
+    let magic = algo::Secp256k1::MAGIC;
+    let known =
+        [crate::SigHash::new_secp256k1(magic, &[1; 20], &[2; 65], b"a")];
+    let mut verifier = Secp256k1Verifier::default();
+    assert_eq!(Ok(false), verifier.verify_secp256k1(b"a", &[1; 20], &[2; 65]));
+    verifier.set_known_sighashes(&known);
+    assert_eq!(Ok(true), verifier.verify_secp256k1(b"a", &[1; 20], &[2; 65]));
+    assert_eq!(Ok(false), verifier.verify_secp256k1(b"b", &[1; 20], &[2; 65]));
+}
+
+#[test]
+fn test_scan_all_instructions() {
+    use solana_program::sysvar::instructions::{
+        construct_instructions_data, BorrowedInstruction,
+    };
+
+    let ent_a = Entry { signature: &[1; 64], pubkey: &[2; 32], message: b"a" };
+    let ent_b = Entry { signature: &[3; 64], pubkey: &[4; 32], message: b"b" };
+    let ent_c = Entry { signature: &[5; 64], pubkey: &[6; 32], message: b"c" };
+
+    let data_a = solana_native_sigverify::new_instruction_data(&[ent_a]);
+    let data_b = solana_native_sigverify::new_instruction_data(&[ent_b]);
+    let data_c = solana_native_sigverify::new_instruction_data(&[ent_c]);
+    let program = Pubkey::new_unique();
+    let instruction = |program_id, data| BorrowedInstruction {
+        program_id,
+        accounts: Vec::new(),
+        data,
+    };
+    let mut data = construct_instructions_data(&[
+        instruction(&algo::Ed25519::ID, &data_a.unwrap()),
+        instruction(&program, b"foo"),
+        instruction(&algo::Secp256r1::ID, &data_c.unwrap()),
+        instruction(&algo::Ed25519::ID, &data_b.unwrap()),
+        instruction(&program, b"bar"),
+    ]);
+    // Current instruction index is stored at the end of the data.
+    let len = data.len();
+    data[len - 2..].copy_from_slice(&4u16.to_le_bytes());
+
+    let mut lamports = 0;
+    let account = crate::api::test_account(
+        &instructions::ID,
+        &program,
+        &mut lamports,
+        &mut data,
+    );
+
+    let mut verifier = Ed25519Verifier::default();
+    verifier.scan_all_instructions(&account).unwrap();
+    assert_eq!(
+        Ok(vec![true, true, false]),
+        verifier.verify_many(&[ent_a, ent_b, ent_c])
+    );
+
+    let mut verifier = Ed25519Verifier::default();
+    verifier.set_ix_sysvar(&account).unwrap();
+    assert_eq!(
+        Ok(vec![false, true, false]),
+        verifier.verify_many(&[ent_a, ent_b, ent_c])
+    );
+    assert_eq!(
+        Err(ProgramError::IncorrectProgramId),
+        verifier.set_ix_sysvar_at(&account, -2)
+    );
+    verifier.set_ix_sysvar_at(&account, -4).unwrap();
+    assert_eq!(
+        Ok(vec![true, true, false]),
+        verifier.verify_many(&[ent_a, ent_b, ent_c])
+    );
+}